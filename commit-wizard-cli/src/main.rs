@@ -1,5 +1,5 @@
 use clap::Parser;
-use commit_wizard_core::{execute_commit_wizard_flow, style, CoreCliArgs};
+use commit_wizard_core::{execute_commit_wizard_flow, style, CoreCliArgs, WizardError};
 
 #[tokio::main]
 async fn main() {
@@ -10,7 +10,8 @@ async fn main() {
                 println!("\n{}", style("✨ CLI: ready to commit! ✨").green().bold());
                 println!("{}", style("run this command from your terminal:").cyan());
                 let git_command = format!(
-                    "git commit -m \"{}\"",
+                    "git commit {}-m \"{}\"",
+                    if cli_args.amend { "--amend " } else { "" },
                     final_commit_message.replace("\"", "\\\"")
                 );
                 println!("{}\n", style(git_command).yellow().bold());
@@ -21,9 +22,11 @@ async fn main() {
                 "{} {} {}",
                 style("❌"),
                 style("commit-wizard CLI failed:").red().bold(),
-                style(&e).red()
+                // include the causes, e.g. which certificate or proxy setting was at fault
+                style(format!("{e:#}")).red()
             );
-            std::process::exit(1);
+            // distinct exit codes let scripts tell failures apart
+            std::process::exit(WizardError::find(&e).map_or(1, WizardError::exit_code));
         }
     }
 }
@@ -1,60 +1,26 @@
-// api interaction module - handles openrouter api communication
+// api interaction module - drives commit generation against the configured provider
 
+use crate::error::ValidationRule;
 use crate::git::DiffInfo;
-use crate::Config;
-use anyhow::{Context, Result};
+use crate::usage::{check_spend_cap, record_usage, UsageRecord};
+use crate::{Config, GenerationParams};
+use anyhow::Result;
+use futures::future::join_all;
 use indicatif::{ProgressBar, ProgressStyle};
-use serde::{Deserialize, Serialize};
-use std::env;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::time::{Duration, Instant};
 
 use super::intelligence::{analyse_commit_intelligence, CommitIntelligence};
 use super::models::select_model_for_complexity;
 use super::patterns::PatternType;
-use super::prompts::{
-    construct_intelligent_prompt, extract_meaningful_diff_lines, get_system_prompt,
-};
+use super::prompts::{construct_intelligent_prompt_with_budget, get_system_prompt, sample_hunks};
+use super::providers::{build_provider, ChatRequest, ChatResponse, Message, TokenUsage};
+use super::stream::stream_with_preview;
+use super::structured::{parse_structured_commit, OutputFormat};
+use super::tokens::{estimate_tokens, prompt_budget};
 use super::validation::{
     extract_commit_message, fix_commit_format, post_process_commit_message, validate_commit_message,
 };
 
-// openrouter api structures
-#[derive(Serialize)]
-struct OpenRouterRequest {
-    model: String,
-    messages: Vec<Message>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    temperature: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    top_p: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    max_tokens: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    stop: Option<Vec<String>>,
-}
-
-#[derive(Serialize)]
-struct Message {
-    role: String,
-    content: String,
-}
-
-#[derive(Deserialize)]
-struct OpenRouterResponse {
-    choices: Vec<Choice>,
-}
-
-#[derive(Deserialize)]
-struct Choice {
-    message: ResponseMessage,
-}
-
-#[derive(Deserialize)]
-struct ResponseMessage {
-    content: String,
-}
-
 /// generate a conventional commit message based on the diff information
 pub async fn generate_conventional_commit(
     diff_info: &DiffInfo,
@@ -73,49 +39,78 @@ pub async fn generate_conventional_commit_with_model(
     custom_model: Option<String>,
     config: &Config,
 ) -> Result<String> {
+    generate_commit_outcome(diff_info, debug, smart_model, custom_model, config)
+        .await
+        .map(|outcome| outcome.message)
+}
+
+/// a generated message together with the usage recorded for it
+#[derive(Debug, Clone)]
+pub struct GenerationOutcome {
+    pub message: String,
+    pub usage: Option<UsageRecord>,
+}
+
+/// generate a conventional commit message and record its token usage in the ledger
+pub async fn generate_commit_outcome(
+    diff_info: &DiffInfo,
+    debug: bool,
+    smart_model: bool,
+    custom_model: Option<String>,
+    config: &Config,
+) -> Result<GenerationOutcome> {
     // start spinner immediately to show activity
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.blue} {msg}")
-            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
-    );
-    spinner.set_message("🧙 analysing commit changes...");
-    spinner.enable_steady_tick(Duration::from_millis(80));
+    let mut spinner = new_spinner("🧙 analysing commit changes...");
 
     // analyse commit intelligence (this is the expensive operation)
     let intelligence = analyse_commit_intelligence(diff_info);
 
-    // select model based on complexity or custom choice
-    let model = if let Some(custom) = custom_model {
-        custom
-    } else if smart_model {
-        select_model_for_complexity(&intelligence, debug, config)
-    } else {
-        config.models.default.clone()
-    };
-
-    if debug {
-        println!("🤖 selected model:\n{model}");
-        println!();
+    let model = resolve_model(&intelligence, debug, smart_model, custom_model, config);
+    if let Err(e) = check_spend_cap(config, &model) {
+        spinner.finish_and_clear();
+        return Err(e);
     }
 
+    // the live preview follows the <commit> tags, so structured (json) answers aren't streamed
+    let format = OutputFormat::for_model(config, &model);
+    let stream = config.stream && format == OutputFormat::Tagged;
+
     // update spinner message
-    spinner.set_message(format!("🧙 generating commit message with {model}..."));
+    if config.stream && !stream {
+        spinner.set_message(format!(
+            "🧙 generating commit message with {model} (no live preview for structured output)..."
+        ));
+    } else {
+        spinner.set_message(format!("🧙 generating commit message with {model}..."));
+    }
 
     // construct intelligent prompt
-    let prompt = construct_intelligent_prompt(diff_info, &intelligence);
+    let params = config.generation_params(&model);
+    let prompt = budgeted_prompt(
+        diff_info,
+        &intelligence,
+        &params,
+        format,
+        config,
+        &model,
+        debug,
+    );
 
     if debug {
         print_debug_info(diff_info, &intelligence, &prompt);
     }
 
-    let api_key = env::var("OPENROUTER_API_KEY")
-        .context("OPENROUTER_API_KEY environment variable is not set")?;
+    let provider = match config.active_provider().and_then(|p| build_provider(&p)) {
+        Ok(provider) => provider,
+        Err(e) => {
+            spinner.finish_and_clear();
+            return Err(e);
+        }
+    };
 
     let max_retries = 3;
     let mut retry_count = 0;
+    let mut tally = UsageTally::default();
 
     let result = loop {
         let current_prompt = if retry_count > 0 {
@@ -132,35 +127,33 @@ pub async fn generate_conventional_commit_with_model(
             prompt.clone()
         };
 
-        let request = OpenRouterRequest {
-            model: model.clone(),
-            messages: vec![
-                Message {
-                    role: "system".to_string(),
-                    content: get_system_prompt(&intelligence).to_string(),
-                },
-                Message {
-                    role: "user".to_string(),
-                    content: current_prompt,
-                },
-            ],
-            temperature: Some(0.1),
-            top_p: Some(0.9),
-            max_tokens: Some(400),
-            stop: Some(vec!["</commit>".to_string()]),
-        };
+        let request = build_request(
+            &model,
+            &intelligence,
+            format,
+            &params,
+            current_prompt,
+            params.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+        );
 
-        let response = match make_api_request(&api_key, request).await {
-            Ok(resp) => resp,
-            Err(e) => break Err(e),
+        let response = if stream {
+            // the live preview clears the spinner, so retries need a fresh one
+            if spinner.is_finished() {
+                spinner = new_spinner(&format!("🧙 regenerating commit message with {model}..."));
+            }
+            stream_with_preview(provider.as_ref(), &request, &spinner).await
+        } else {
+            provider.complete(&request).await
         };
-
-        let raw_response = match response.choices.first() {
-            Some(choice) => choice.message.content.clone(),
-            None => {
-                eprintln!("Warning: Empty choices in response");
-                String::new()
+        let raw_response = match response {
+            Ok(resp) => {
+                tally.add(&request, &resp);
+                if debug {
+                    print_debug_reasoning(resp.reasoning.as_deref());
+                }
+                resp.content
             }
+            Err(e) => break Err(e),
         };
 
         if debug {
@@ -171,8 +164,7 @@ pub async fn generate_conventional_commit_with_model(
             println!();
         }
 
-        let commit_msg = extract_commit_message(&raw_response);
-        let commit_msg = post_process_commit_message(&commit_msg);
+        let commit_msg = parse_commit(&raw_response, format, debug);
 
         if debug {
             println!("🐛 debug: extracted and processed commit message:");
@@ -183,18 +175,7 @@ pub async fn generate_conventional_commit_with_model(
         }
 
         // check if type matches hint
-        let generated_type = commit_msg
-            .split(':')
-            .next()
-            .unwrap_or("")
-            .split('(')
-            .next()
-            .unwrap_or("")
-            .split('!')
-            .next()
-            .unwrap_or("")
-            .trim()
-            .to_string();
+        let generated_type = commit_type(&commit_msg);
         let expected_type = intelligence.commit_type_hint.clone();
 
         // first try to validate as-is
@@ -227,7 +208,8 @@ pub async fn generate_conventional_commit_with_model(
                 }
 
                 // if we couldn't fix it, handle specific errors
-                if e.to_string().contains("description too long") && retry_count < max_retries {
+                let rule = e.rule();
+                if rule == Some(ValidationRule::DescriptionTooLong) && retry_count < max_retries {
                     retry_count += 1;
                     if debug {
                         println!(
@@ -235,7 +217,7 @@ pub async fn generate_conventional_commit_with_model(
                         );
                     }
                     continue;
-                } else if e.to_string().contains("invalid scope") && retry_count < max_retries {
+                } else if rule == Some(ValidationRule::InvalidScope) && retry_count < max_retries {
                     // try again but force a concrete scope if we have none
                     retry_count += 1;
                     if debug {
@@ -245,91 +227,492 @@ pub async fn generate_conventional_commit_with_model(
                     }
                     continue;
                 } else {
-                    break Err(e);
+                    // only a failure the retries didn't fix is worth handing to a fallback model
+                    break Err(e.after_attempts(retry_count + 1).into());
                 }
             }
         }
     };
 
     spinner.finish_and_clear();
-    result
+    let usage = tally.record(config, &model, debug);
+    result.map(|message| GenerationOutcome { message, usage })
 }
 
-/// make api request to openrouter
-async fn make_api_request(api_key: &str, request: OpenRouterRequest) -> Result<OpenRouterResponse> {
-    let max_retries = 3;
-    let mut retry_delay = Duration::from_secs(1);
-
-    for attempt in 0..max_retries {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .connect_timeout(Duration::from_secs(10))
-            .build()?;
-
-        let response = client
-            .post("https://openrouter.ai/api/v1/chat/completions")
-            .header("Authorization", format!("Bearer {api_key}"))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await;
-
-        match response {
-            Ok(resp) => {
-                if resp.status().is_success() {
-                    let body = resp
-                        .json::<OpenRouterResponse>()
-                        .await
-                        .context("failed to parse openrouter api response")?;
-                    return Ok(body);
-                } else {
-                    let status = resp.status();
-                    let error_text = resp
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "unknown error".to_string());
-
-                    if status.is_server_error() || status == 429 {
-                        if attempt < max_retries - 1 {
-                            eprintln!(
-                                "Retryable error ({status}): {error_text}. Retrying in {retry_delay:?}..."
-                            );
-                            sleep(retry_delay).await;
-                            retry_delay *= 2;
-                            continue;
-                        }
-                    } else if status == 400 && error_text.to_lowercase().contains("model") {
-                        return Err(anyhow::anyhow!(
-                            "invalid model '{}'. use the model settings menu to select a different model",
-                            request.model
-                        ));
-                    }
+/// a generated message alongside its validation outcome
+#[derive(Debug, Clone)]
+pub struct CommitCandidate {
+    pub message: String,
+    pub validation_error: Option<String>,
+    pub matches_type_hint: bool,
+}
+
+impl CommitCandidate {
+    pub fn is_valid(&self) -> bool {
+        self.validation_error.is_none()
+    }
+}
+
+// room left in the context window for the model's answer, unless the model's params say otherwise
+const MAX_OUTPUT_TOKENS: u32 = 400;
+// reasoning eats into the same limit, so models asked to think get more
+const REASONING_OUTPUT_TOKENS: u32 = 4000;
+
+// candidates beyond the first are sampled hotter so they actually differ
+const DEFAULT_TEMPERATURE: f32 = 0.1;
+const MAX_CANDIDATE_TEMPERATURE: f32 = 0.9;
+
+/// several ranked candidates together with the usage recorded for generating them
+#[derive(Debug, Clone)]
+pub struct CandidateSet {
+    pub candidates: Vec<CommitCandidate>,
+    pub usage: Option<UsageRecord>,
+}
+
+/// generate several commit messages in parallel, validated and ranked best first
+pub async fn generate_commit_candidates(
+    diff_info: &DiffInfo,
+    debug: bool,
+    smart_model: bool,
+    custom_model: Option<String>,
+    config: &Config,
+    count: usize,
+) -> Result<CandidateSet> {
+    let spinner = new_spinner("🧙 analysing commit changes...");
+
+    let intelligence = analyse_commit_intelligence(diff_info);
+    let model = resolve_model(&intelligence, debug, smart_model, custom_model, config);
+    if let Err(e) = check_spend_cap(config, &model) {
+        spinner.finish_and_clear();
+        return Err(e);
+    }
+    let count = count.max(1);
+    spinner.set_message(format!(
+        "🧙 generating {count} commit messages with {model}..."
+    ));
+
+    let format = OutputFormat::for_model(config, &model);
+    let params = config.generation_params(&model);
+    let prompt = budgeted_prompt(
+        diff_info,
+        &intelligence,
+        &params,
+        format,
+        config,
+        &model,
+        debug,
+    );
+    if debug {
+        print_debug_info(diff_info, &intelligence, &prompt);
+    }
 
-                    return Err(anyhow::anyhow!(
-                        "openrouter api error ({}): {}",
-                        status,
-                        error_text
-                    ));
+    let provider = match config.active_provider().and_then(|p| build_provider(&p)) {
+        Ok(provider) => provider,
+        Err(e) => {
+            spinner.finish_and_clear();
+            return Err(e);
+        }
+    };
+
+    let requests: Vec<ChatRequest> = (0..count)
+        .map(|i| {
+            let base = params.temperature.unwrap_or(DEFAULT_TEMPERATURE);
+            let temperature = if count == 1 {
+                base
+            } else {
+                base + (MAX_CANDIDATE_TEMPERATURE.max(base) - base) * i as f32 / (count - 1) as f32
+            };
+            build_request(
+                &model,
+                &intelligence,
+                format,
+                &params,
+                prompt.clone(),
+                temperature,
+            )
+        })
+        .collect();
+    let responses = join_all(requests.iter().map(|r| provider.complete(r))).await;
+    spinner.finish_and_clear();
+
+    let mut candidates: Vec<CommitCandidate> = Vec::new();
+    let mut first_error = None;
+    let mut tally = UsageTally::default();
+    for (i, response) in responses.into_iter().enumerate() {
+        let raw_response = match response {
+            Ok(resp) => {
+                tally.add(&requests[i], &resp);
+                if debug {
+                    print_debug_reasoning(resp.reasoning.as_deref());
                 }
+                resp.content
             }
             Err(e) => {
-                if attempt < max_retries - 1 {
-                    eprintln!("Network error: {e}. Retrying in {retry_delay:?}...");
-                    sleep(retry_delay).await;
-                    retry_delay *= 2;
-                    continue;
-                } else {
-                    return Err(anyhow::anyhow!(
-                        "failed to connect to openrouter api after {max_retries} attempts: {e}"
-                    ));
+                if debug {
+                    println!("⚠️  candidate {} failed: {e}", i + 1);
                 }
+                first_error.get_or_insert(e);
+                continue;
             }
+        };
+
+        let candidate = review_candidate(&raw_response, format, &intelligence, debug);
+        if !candidates.iter().any(|c| c.message == candidate.message) {
+            candidates.push(candidate);
         }
     }
 
-    Err(anyhow::anyhow!(
-        "failed to complete api request after {max_retries} attempts"
-    ))
+    let usage = tally.record(config, &model, debug);
+
+    if candidates.is_empty() {
+        return Err(first_error
+            .unwrap_or_else(|| anyhow::anyhow!("no commit message candidates were generated")));
+    }
+
+    // valid first, then those agreeing with the analysed type, then the analysed scope
+    let expected_scope = intelligence
+        .scope_hint
+        .as_ref()
+        .map(|scope| format!("({scope})"));
+    candidates.sort_by_key(|c| {
+        let has_scope = expected_scope
+            .as_ref()
+            .is_some_and(|scope| c.message.lines().next().unwrap_or("").contains(scope));
+        (!c.is_valid(), !c.matches_type_hint, !has_scope)
+    });
+
+    Ok(CandidateSet { candidates, usage })
+}
+
+/// one model's answer in a side-by-side comparison
+#[derive(Debug)]
+pub struct ModelComparison {
+    pub model: String,
+    pub result: Result<CommitCandidate>,
+    pub latency: Duration,
+    pub usage: Option<UsageRecord>,
+}
+
+/// send the same prompt to several models at once and review each answer
+pub async fn compare_models(
+    diff_info: &DiffInfo,
+    debug: bool,
+    models: &[String],
+    config: &Config,
+) -> Result<Vec<ModelComparison>> {
+    let spinner = new_spinner("🧙 analysing commit changes...");
+    let intelligence = analyse_commit_intelligence(diff_info);
+
+    // every model sees the exact same prompt: tags work with any model, and the diff
+    // is cut to fit the smallest context window in the line-up
+    let format = OutputFormat::Tagged;
+    let budget = models
+        .iter()
+        .map(|model| {
+            let params = config.generation_params(model);
+            model_prompt_budget(&intelligence, &params, format, config, model)
+        })
+        .min()
+        .unwrap_or_default();
+    let prompt = construct_intelligent_prompt_with_budget(diff_info, &intelligence, budget, format);
+    if debug {
+        println!("🐛 debug: prompt budget: ~{budget} tokens (shared by all models)\n");
+        print_debug_info(diff_info, &intelligence, &prompt);
+    }
+
+    let provider = match config.active_provider().and_then(|p| build_provider(&p)) {
+        Ok(provider) => provider,
+        Err(e) => {
+            spinner.finish_and_clear();
+            return Err(e);
+        }
+    };
+    spinner.set_message(format!("🧙 comparing {} models...", models.len()));
+
+    let runs = join_all(models.iter().map(|model| {
+        let params = config.generation_params(model);
+        let request = build_request(
+            model,
+            &intelligence,
+            format,
+            &params,
+            prompt.clone(),
+            params.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+        );
+        let provider = provider.as_ref();
+        async move {
+            let started = Instant::now();
+            let response = match check_spend_cap(config, model) {
+                Ok(()) => provider.complete(&request).await,
+                Err(e) => Err(e),
+            };
+            (request, response, started.elapsed())
+        }
+    }))
+    .await;
+    spinner.finish_and_clear();
+
+    let comparisons = models
+        .iter()
+        .zip(runs)
+        .map(|(model, (request, response, latency))| {
+            let mut tally = UsageTally::default();
+            let result = response.map(|resp| {
+                tally.add(&request, &resp);
+                if debug {
+                    println!("🐛 debug: {model}:");
+                    print_debug_reasoning(resp.reasoning.as_deref());
+                }
+                review_candidate(&resp.content, format, &intelligence, debug)
+            });
+            ModelComparison {
+                model: model.clone(),
+                result,
+                latency,
+                usage: tally.record(config, model, debug),
+            }
+        })
+        .collect();
+
+    Ok(comparisons)
+}
+
+/// running token count across the requests behind one generation
+#[derive(Default)]
+struct UsageTally {
+    usage: TokenUsage,
+    requests: u32,
+    estimated: bool,
+}
+
+impl UsageTally {
+    fn add(&mut self, request: &ChatRequest, response: &ChatResponse) {
+        self.requests += 1;
+        match response.usage {
+            Some(usage) => self.usage += usage,
+            None => {
+                // streams stopped early and some local servers never report usage
+                self.estimated = true;
+                let prompt: usize = request
+                    .messages
+                    .iter()
+                    .map(|m| estimate_tokens(&m.content))
+                    .sum();
+                self.usage += TokenUsage {
+                    prompt_tokens: prompt as u32,
+                    completion_tokens: estimate_tokens(&response.content) as u32,
+                };
+            }
+        }
+    }
+
+    /// write the generation to the usage ledger, warning rather than failing on errors
+    fn record(self, config: &Config, model: &str, debug: bool) -> Option<UsageRecord> {
+        if self.requests == 0 {
+            return None;
+        }
+
+        let record = UsageRecord::new(config, model, self.usage, self.requests, self.estimated);
+        if let Err(e) = record_usage(&record) {
+            eprintln!("⚠️  warning: failed to record usage: {e}");
+        } else if debug {
+            println!(
+                "🐛 debug: recorded usage: {} prompt + {} completion tokens over {} request(s)\n",
+                record.prompt_tokens, record.completion_tokens, record.requests
+            );
+        }
+        Some(record)
+    }
+}
+
+/// clean up a raw response and record whether it passes validation
+fn review_candidate(
+    raw_response: &str,
+    format: OutputFormat,
+    intelligence: &CommitIntelligence,
+    debug: bool,
+) -> CommitCandidate {
+    let mut message = parse_commit(raw_response, format, debug);
+    let mut validation = validate_commit_message(&message);
+    if validation.is_err() {
+        if let Ok(fixed) = fix_commit_format(&message) {
+            if validate_commit_message(&fixed).is_ok() {
+                message = fixed;
+                validation = Ok(());
+            }
+        }
+    }
+
+    CommitCandidate {
+        matches_type_hint: commit_type(&message) == intelligence.commit_type_hint,
+        validation_error: validation.err().map(|e| e.to_string()),
+        message,
+    }
+}
+
+/// select model based on complexity or custom choice
+fn resolve_model(
+    intelligence: &CommitIntelligence,
+    debug: bool,
+    smart_model: bool,
+    custom_model: Option<String>,
+    config: &Config,
+) -> String {
+    let model = if let Some(custom) = custom_model {
+        custom
+    } else if smart_model {
+        select_model_for_complexity(intelligence, debug, config)
+    } else {
+        config.default_model()
+    };
+
+    if debug {
+        println!("🤖 selected model:\n{model}");
+        println!();
+    }
+
+    model
+}
+
+/// build the prompt, giving the diff whatever the model's context window has to spare
+fn budgeted_prompt(
+    diff_info: &DiffInfo,
+    intelligence: &CommitIntelligence,
+    params: &GenerationParams,
+    format: OutputFormat,
+    config: &Config,
+    model: &str,
+    debug: bool,
+) -> String {
+    let context_length = config.context_length(model) as usize;
+    let budget = model_prompt_budget(intelligence, params, format, config, model);
+
+    if debug {
+        println!(
+            "🐛 debug: prompt budget: ~{budget} tokens ({context_length} token context window)\n"
+        );
+    }
+
+    construct_intelligent_prompt_with_budget(diff_info, intelligence, budget, format)
+}
+
+/// tokens of diff a model can take once the system prompt and its answer are accounted for
+fn model_prompt_budget(
+    intelligence: &CommitIntelligence,
+    params: &GenerationParams,
+    format: OutputFormat,
+    config: &Config,
+    model: &str,
+) -> usize {
+    let context_length = config.context_length(model) as usize;
+    let system_tokens = estimate_tokens(&get_system_prompt(intelligence, format));
+    prompt_budget(context_length, max_output_tokens(params) as usize).saturating_sub(system_tokens)
+}
+
+/// answer tokens to ask for: the configured limit, or a default that leaves reasoning models room
+fn max_output_tokens(params: &GenerationParams) -> u32 {
+    params
+        .max_tokens
+        .unwrap_or(if params.reasoning_effort.is_some() {
+            REASONING_OUTPUT_TOKENS
+        } else {
+            MAX_OUTPUT_TOKENS
+        })
+}
+
+/// chat request asking for a conventional commit in the given format
+fn build_request(
+    model: &str,
+    intelligence: &CommitIntelligence,
+    format: OutputFormat,
+    params: &GenerationParams,
+    prompt: String,
+    temperature: f32,
+) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        messages: vec![
+            Message {
+                role: "system".to_string(),
+                content: get_system_prompt(intelligence, format),
+            },
+            Message {
+                role: "user".to_string(),
+                content: prompt,
+            },
+        ],
+        temperature: Some(temperature),
+        top_p: Some(params.top_p.unwrap_or(0.9)),
+        max_tokens: Some(max_output_tokens(params)),
+        // an empty list in the config switches stop sequences off
+        stop: match &params.stop {
+            Some(stop) => (!stop.is_empty()).then(|| stop.clone()),
+            None => format.stop(),
+        },
+        response_format: format.response_format(),
+        reasoning_effort: params.reasoning_effort,
+        extra: params.extra.clone(),
+    }
+}
+
+/// turn a raw completion into a commit message. json answers are rendered from their
+/// fields; anything else (or json that doesn't parse) goes through the tag parser
+fn parse_commit(raw_response: &str, format: OutputFormat, debug: bool) -> String {
+    let structured = match format {
+        OutputFormat::Json => parse_structured_commit(raw_response),
+        OutputFormat::Tagged => None,
+    };
+    if debug && format == OutputFormat::Json && structured.is_none() {
+        println!("⚠️  structured output didn't parse, falling back to the tag parser\n");
+    }
+
+    let message = match structured {
+        Some(commit) => commit.render(),
+        None => extract_commit_message(raw_response),
+    };
+    post_process_commit_message(&message)
+}
+
+/// the type of a conventional commit header, without scope or breaking marker
+fn commit_type(message: &str) -> String {
+    message
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .split('(')
+        .next()
+        .unwrap_or("")
+        .split('!')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+/// steadily ticking spinner used while waiting on the provider
+fn new_spinner(message: &str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.blue} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    spinner.set_message(message.to_string());
+    spinner.enable_steady_tick(Duration::from_millis(80));
+    spinner
+}
+
+/// show what a reasoning model thought before answering; it's never part of the commit
+fn print_debug_reasoning(reasoning: Option<&str>) {
+    let Some(reasoning) = reasoning else {
+        return;
+    };
+    println!("🐛 debug: model reasoning (not part of the commit):");
+    println!("═══════════════════════════════════════");
+    println!("{reasoning}");
+    println!("═══════════════════════════════════════");
+    println!();
 }
 
 /// print debug information
@@ -375,7 +758,7 @@ fn print_debug_info(diff_info: &DiffInfo, intelligence: &CommitIntelligence, pro
         } else {
             20
         };
-        let meaningful_diff = extract_meaningful_diff_lines(&file.diff_content, lines_to_include);
+        let meaningful_diff = sample_hunks(&file.hunks, lines_to_include);
         if !meaningful_diff.is_empty() {
             println!(
                 "     📝 diff sent to ai ({} lines):",
@@ -428,5 +811,9 @@ fn format_pattern_type(pattern_type: &PatternType) -> &'static str {
         PatternType::CiChange => "ci/cd",
         PatternType::Deprecation => "deprecation",
         PatternType::SecurityFix => "security fix",
+        PatternType::FileRelocation => "rename",
+        PatternType::FileRemoval => "removal",
+        PatternType::ModeChange => "file mode",
+        PatternType::SubmoduleUpdate => "submodule",
     }
 }
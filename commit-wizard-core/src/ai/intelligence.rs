@@ -1,8 +1,8 @@
 // commit intelligence analysis module
 
 use super::patterns::{Pattern, PatternType};
-use crate::git::{DiffInfo, ModifiedFile};
-use std::collections::{HashMap, HashSet};
+use crate::git::{DiffInfo, DiffLine, FileStatus, Hunk, ModifiedFile};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 // commit intelligence structures
 #[derive(Debug, Clone)]
@@ -19,6 +19,9 @@ pub struct CommitIntelligence {
 struct FileAnalysis {
     new_files: Vec<String>,
     modified_files: Vec<String>,
+    relocated_files: Vec<String>, // renamed or copied, described as "old -> new"
+    deleted_files: Vec<String>,
+    mode_changes: Vec<String>, // permission or type changes, e.g. chmod +x or a new symlink
     by_extension: HashMap<String, Vec<String>>,
     by_directory: HashMap<String, Vec<String>>,
 }
@@ -101,6 +104,67 @@ fn detect_universal_patterns(diff_info: &DiffInfo) -> Vec<Pattern> {
         });
     }
 
+    // moved, renamed or copied files
+    if !file_analysis.relocated_files.is_empty() {
+        let count = file_analysis.relocated_files.len();
+        patterns.push(Pattern {
+            pattern_type: PatternType::FileRelocation,
+            description: format!(
+                "{count} file{} moved or renamed: {}",
+                if count > 1 { "s" } else { "" },
+                summarize_files(&file_analysis.relocated_files)
+            ),
+            impact: 0.6,
+            files_affected: file_analysis.relocated_files.clone(),
+        });
+    }
+
+    // removed files
+    if !file_analysis.deleted_files.is_empty() {
+        let count = file_analysis.deleted_files.len();
+        patterns.push(Pattern {
+            pattern_type: PatternType::FileRemoval,
+            description: format!("{count} file{} removed", if count > 1 { "s" } else { "" }),
+            impact: 0.5,
+            files_affected: file_analysis.deleted_files.clone(),
+        });
+    }
+
+    // permission and file type changes
+    let mode_notes: Vec<String> = diff_info
+        .files
+        .iter()
+        .filter(|f| file_analysis.mode_changes.contains(&f.path))
+        .filter_map(|f| Some(format!("{} {}", f.path, f.status.describe()?)))
+        .collect();
+    if !mode_notes.is_empty() {
+        patterns.push(Pattern {
+            pattern_type: PatternType::ModeChange,
+            description: mode_notes.join("; "),
+            impact: 0.3,
+            files_affected: file_analysis.mode_changes.clone(),
+        });
+    }
+
+    // submodule pointer bumps
+    let submodules: Vec<&ModifiedFile> = diff_info
+        .files
+        .iter()
+        .filter(|f| f.submodule.is_some())
+        .collect();
+    if !submodules.is_empty() {
+        let notes: Vec<String> = submodules
+            .iter()
+            .filter_map(|f| Some(format!("{} ({})", f.path, f.submodule.as_ref()?.describe())))
+            .collect();
+        patterns.push(Pattern {
+            pattern_type: PatternType::SubmoduleUpdate,
+            description: format!("submodule pointer updated: {}", notes.join("; ")),
+            impact: 0.7,
+            files_affected: submodules.iter().map(|f| f.path.clone()).collect(),
+        });
+    }
+
     // cross-layer changes
     let layers = detect_layers(&file_analysis);
     if layers.len() >= 2 {
@@ -268,16 +332,30 @@ fn analyse_file_metadata(diff_info: &DiffInfo) -> FileAnalysis {
     let mut analysis = FileAnalysis {
         new_files: Vec::new(),
         modified_files: Vec::new(),
+        relocated_files: Vec::new(),
+        deleted_files: Vec::new(),
+        mode_changes: Vec::new(),
         by_extension: HashMap::new(),
         by_directory: HashMap::new(),
     };
 
     for file in &diff_info.files {
-        // detect new files
-        if file.removed_lines == 0 && file.added_lines > 10 {
-            analysis.new_files.push(file.path.clone());
-        } else {
-            analysis.modified_files.push(file.path.clone());
+        // sort files by what git says happened to them
+        match &file.status {
+            FileStatus::Added => analysis.new_files.push(file.path.clone()),
+            status => {
+                analysis.modified_files.push(file.path.clone());
+                match status {
+                    FileStatus::Renamed { from } | FileStatus::Copied { from } => analysis
+                        .relocated_files
+                        .push(format!("{from} -> {}", file.path)),
+                    FileStatus::Deleted => analysis.deleted_files.push(file.path.clone()),
+                    FileStatus::ModeChanged { .. } | FileStatus::TypeChanged { .. } => {
+                        analysis.mode_changes.push(file.path.clone())
+                    }
+                    _ => {}
+                }
+            }
         }
 
         // group by extension
@@ -307,44 +385,237 @@ fn detect_layers(analysis: &FileAnalysis) -> Vec<String> {
     let mut layers = HashSet::new();
 
     for dir_name in analysis.by_directory.keys() {
-        let dir_lower = dir_name.to_lowercase();
-        if dir_lower.contains("controller")
-            || dir_lower.contains("api")
-            || dir_lower.contains("endpoint")
-        {
-            layers.insert("api".to_string());
+        layers.extend(directory_layers(dir_name).into_iter().map(String::from));
+    }
+
+    layers.into_iter().collect()
+}
+
+/// the architectural layers a directory name suggests
+fn directory_layers(dir_name: &str) -> Vec<&'static str> {
+    let mut layers = Vec::new();
+    let dir_lower = dir_name.to_lowercase();
+    if dir_lower.contains("controller")
+        || dir_lower.contains("api")
+        || dir_lower.contains("endpoint")
+    {
+        layers.push("api");
+    }
+    if dir_lower.contains("service")
+        || dir_lower.contains("business")
+        || dir_lower.contains("domain")
+    {
+        layers.push("service");
+    }
+    if dir_lower.contains("model") || dir_lower.contains("entity") || dir_lower.contains("schema") {
+        layers.push("model");
+    }
+    if dir_lower.contains("view") || dir_lower.contains("component") || dir_lower.contains("ui") {
+        layers.push("ui");
+    }
+    if dir_lower.contains("test") || dir_lower.contains("spec") {
+        layers.push("test");
+    }
+    if dir_lower.contains("config") || dir_lower.contains("settings") {
+        layers.push("configuration");
+    }
+    layers
+}
+
+/// changes proposed as one atomic commit
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChangeGroup {
+    pub label: String,
+    pub files: Vec<String>,
+    // files split across groups, with the indices of the hunks that belong to this one;
+    // files not listed here go in whole
+    pub hunks: BTreeMap<String, Vec<usize>>,
+}
+
+impl ChangeGroup {
+    /// add a file, or some of its hunks, merging with what the group already holds of it
+    pub fn add(&mut self, path: &str, hunks: Option<Vec<usize>>) {
+        let whole = self.files.iter().any(|f| f == path) && !self.hunks.contains_key(path);
+        if !self.files.iter().any(|f| f == path) {
+            self.files.push(path.to_string());
         }
-        if dir_lower.contains("service")
-            || dir_lower.contains("business")
-            || dir_lower.contains("domain")
-        {
-            layers.insert("service".to_string());
+        match hunks {
+            Some(hunks) if !whole => {
+                let held = self.hunks.entry(path.to_string()).or_default();
+                held.extend(hunks);
+                held.sort_unstable();
+                held.dedup();
+            }
+            _ => {
+                self.hunks.remove(path);
+            }
         }
-        if dir_lower.contains("model")
-            || dir_lower.contains("entity")
-            || dir_lower.contains("schema")
-        {
-            layers.insert("model".to_string());
+    }
+
+    /// take a file, or this group's hunks of it, out of the group
+    pub fn remove(&mut self, path: &str) -> Option<Vec<usize>> {
+        self.files.retain(|f| f != path);
+        self.hunks.remove(path)
+    }
+}
+
+/// cluster changes into proposed atomic commits: files claimed by a tooling pattern (ci,
+/// dependencies, docs, configuration) go together, code is grouped by architectural layer,
+/// and whatever is left by directory. within code, hunks are grouped by what they do, so a
+/// file holding both a fix and a new feature is split between two commits
+pub fn cluster_changes(diff_info: &DiffInfo) -> Vec<ChangeGroup> {
+    let patterns = detect_universal_patterns(diff_info);
+    let analysis = analyse_file_metadata(diff_info);
+    // most specific first, so a Cargo.toml counts as a dependency rather than config
+    let claims = [
+        (PatternType::CiChange, "ci"),
+        (PatternType::DependencyUpdate, "dependencies"),
+        (PatternType::SubmoduleUpdate, "dependencies"),
+        (PatternType::DocumentationUpdate, "docs"),
+        (PatternType::ConfigurationDrift, "configuration"),
+    ];
+
+    // groups alongside the label they rank by, before any hunk kind is added
+    let mut groups: Vec<(String, ChangeGroup)> = Vec::new();
+    let mut place = |base: &str, kind: Option<&str>, path: &str, hunks: Option<Vec<usize>>| {
+        let label = match kind {
+            Some(kind) => format!("{base} {kind}"),
+            None => base.to_string(),
+        };
+        let at = match groups.iter().position(|(_, group)| group.label == label) {
+            Some(at) => at,
+            None => {
+                groups.push((
+                    base.to_string(),
+                    ChangeGroup {
+                        label,
+                        ..ChangeGroup::default()
+                    },
+                ));
+                groups.len() - 1
+            }
+        };
+        groups[at].1.add(path, hunks);
+    };
+
+    for file in &diff_info.files {
+        let claimed = claims.iter().find(|(pattern_type, _)| {
+            patterns
+                .iter()
+                .any(|p| p.pattern_type == *pattern_type && p.files_affected.contains(&file.path))
+        });
+        let directory = analysis
+            .by_directory
+            .iter()
+            .find(|(_, files)| files.contains(&file.path))
+            .map(|(dir, _)| dir.as_str());
+        let base = match (claimed, directory) {
+            (Some((_, kind)), _) => {
+                place(kind, None, &file.path, None);
+                continue;
+            }
+            (None, Some(dir)) => match directory_layers(dir).first() {
+                Some(layer) => format!("{layer} layer"),
+                None => format!("{dir}/"),
+            },
+            (None, None) => "top level".to_string(),
+        };
+
+        // hunks saying nothing about themselves go with the file's most common kind
+        let kinds: Vec<Option<&str>> = file.hunks.iter().map(hunk_kind).collect();
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for kind in kinds.iter().flatten() {
+            match counts.iter_mut().find(|(k, _)| k == kind) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((kind, 1)),
+            }
         }
-        if dir_lower.contains("view") || dir_lower.contains("component") || dir_lower.contains("ui")
-        {
-            layers.insert("ui".to_string());
+        let dominant = counts
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(kind, _)| *kind);
+        if counts.len() < 2 {
+            place(&base, dominant, &file.path, None);
+            continue;
+        }
+        let mut by_kind: Vec<(Option<&str>, Vec<usize>)> = Vec::new();
+        for (at, kind) in kinds.iter().enumerate() {
+            let kind = kind.or(dominant);
+            match by_kind.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, hunks)) => hunks.push(at),
+                None => by_kind.push((kind, vec![at])),
+            }
         }
-        if dir_lower.contains("test") || dir_lower.contains("spec") {
-            layers.insert("test".to_string());
+        for (kind, hunks) in by_kind {
+            place(&base, kind, &file.path, Some(hunks));
         }
-        if dir_lower.contains("config") || dir_lower.contains("settings") {
-            layers.insert("configuration".to_string());
+    }
+
+    // a copy goes in with the change to the file it was copied from
+    for file in &diff_info.files {
+        let FileStatus::Copied { from } = &file.status else {
+            continue;
+        };
+        if !diff_info.files.iter().any(|f| &f.path == from) {
+            continue;
+        }
+        for (_, group) in groups.iter_mut() {
+            group.remove(from);
+        }
+        if let Some((_, group)) = groups
+            .iter_mut()
+            .find(|(_, group)| group.files.contains(&file.path))
+        {
+            group.add(from, None);
         }
     }
+    groups.retain(|(_, group)| !group.files.is_empty());
+
+    // groundwork first, then code from the bottom layer up, then tests, docs and ci
+    let rank = |label: &str| match label {
+        "dependencies" => 0,
+        "configuration" | "configuration layer" => 1,
+        "model layer" => 2,
+        "service layer" => 3,
+        "api layer" => 4,
+        "ui layer" => 5,
+        "test layer" => 7,
+        "docs" => 8,
+        "ci" => 9,
+        _ => 6,
+    };
+    groups.sort_by_key(|(base, _)| rank(base));
+    groups.into_iter().map(|(_, group)| group).collect()
+}
 
-    layers.into_iter().collect()
+/// what a single hunk does, judged from its changed lines alone
+fn hunk_kind(hunk: &Hunk) -> Option<&'static str> {
+    let changed: Vec<String> = hunk
+        .lines
+        .iter()
+        .filter(|line| !matches!(line, DiffLine::Context(_)))
+        .map(DiffLine::render)
+        .collect();
+    let content = analyse_content(&changed.join("\n"));
+    let removes = hunk
+        .lines
+        .iter()
+        .any(|line| matches!(line, DiffLine::Removed(_)));
+    if content.new_functions > 0 || content.new_classes > 0 {
+        Some("features")
+    } else if content.has_bug_fix_indicators && removes {
+        Some("fixes")
+    } else if content.has_performance_indicators {
+        Some("performance")
+    } else {
+        None
+    }
 }
 
 /// analyse file content for universal patterns
 fn analyse_file_content_universal(file: &ModifiedFile) -> Vec<Pattern> {
     let mut patterns = Vec::new();
-    let content_analysis = analyse_content(&file.diff_content);
+    let content_analysis = analyse_content(&file.diff_content());
 
     // feature addition pattern
     if content_analysis.new_functions >= 3 || content_analysis.new_classes >= 1 {
@@ -543,7 +814,7 @@ fn detect_deprecation_patterns(diff_info: &DiffInfo) -> Vec<String> {
         .files
         .iter()
         .filter(|file| {
-            let content_lower = file.diff_content.to_lowercase();
+            let content_lower = file.diff_content().to_lowercase();
             content_lower.contains("@deprecated")
                 || content_lower.contains("deprecated")
                 || (file.removed_lines > 10 && content_lower.contains("export"))
@@ -558,7 +829,7 @@ fn detect_security_patterns(diff_info: &DiffInfo) -> Vec<String> {
         .files
         .iter()
         .filter(|file| {
-            let content_lower = file.diff_content.to_lowercase();
+            let content_lower = file.diff_content().to_lowercase();
             let path_lower = file.path.to_lowercase();
 
             // strong security indicators
@@ -653,6 +924,16 @@ pub fn determine_body_requirement(
         }
     }
 
+    // a submodule bump that brings in several commits deserves a list of them
+    if diff_info
+        .files
+        .iter()
+        .filter_map(|f| f.submodule.as_ref())
+        .any(|submodule| submodule.commit_count > 1)
+    {
+        return true;
+    }
+
     // require body for many files
     if diff_info.files.len() >= 5 {
         return true;
@@ -736,7 +1017,9 @@ pub fn suggest_commit_metadata(
             PatternType::BugFixPattern | PatternType::SecurityFix => {
                 *type_scores.entry("fix").or_insert(0.0) += pattern.impact;
             }
-            PatternType::RefactoringPattern => {
+            PatternType::RefactoringPattern
+            | PatternType::FileRelocation
+            | PatternType::FileRemoval => {
                 *type_scores.entry("refactor").or_insert(0.0) += pattern.impact;
             }
             PatternType::DocumentationUpdate => {
@@ -751,7 +1034,10 @@ pub fn suggest_commit_metadata(
             PatternType::CiChange => {
                 *type_scores.entry("ci").or_insert(0.0) += pattern.impact;
             }
-            PatternType::DependencyUpdate => {
+            PatternType::SubmoduleUpdate => {
+                *type_scores.entry("build").or_insert(0.0) += pattern.impact;
+            }
+            PatternType::DependencyUpdate | PatternType::ModeChange => {
                 *type_scores.entry("chore").or_insert(0.0) += pattern.impact;
             }
             PatternType::StyleNormalization => {
@@ -775,6 +1061,11 @@ pub fn suggest_commit_metadata(
 
 /// determine intelligent scope
 fn determine_intelligent_scope(diff_info: &DiffInfo) -> Option<String> {
+    // submodules are vendored dependencies
+    if diff_info.files.iter().all(|f| f.submodule.is_some()) {
+        return Some("deps".to_string());
+    }
+
     // detect subsystem from file paths
     let subsystem = detect_subsystem(diff_info);
     if subsystem != "general" {
@@ -3,13 +3,32 @@
 pub mod api;
 pub mod intelligence;
 pub mod models;
+pub mod offline;
 pub mod patterns;
 pub mod prompts;
+pub mod providers;
+pub mod reasoning;
+pub mod stream;
+pub mod structured;
+pub mod tokens;
 pub mod validation;
 
 // re-export key public items for convenient access
-pub use api::{generate_conventional_commit, generate_conventional_commit_with_model};
-pub use intelligence::{analyse_commit_intelligence, CommitIntelligence};
+pub use api::{
+    compare_models, generate_commit_candidates, generate_commit_outcome,
+    generate_conventional_commit, generate_conventional_commit_with_model, CandidateSet,
+    CommitCandidate, GenerationOutcome, ModelComparison,
+};
+pub use intelligence::{
+    analyse_commit_intelligence, cluster_changes, ChangeGroup, CommitIntelligence,
+};
 pub use models::{get_available_models, select_model_for_complexity};
+pub use offline::generate_offline_commit;
 pub use patterns::{Pattern, PatternType};
-pub use validation::validate_commit_message;
+pub use providers::{
+    build_provider, warrants_fallback, ChatRequest, ChatResponse, LlmProvider, StreamControl,
+    StreamEvent, TokenUsage,
+};
+pub use reasoning::{separate_reasoning, SeparatedOutput};
+pub use structured::{OutputFormat, StructuredCommit};
+pub use validation::{commit_message_violations, validate_commit_message};
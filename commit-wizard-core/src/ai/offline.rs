@@ -0,0 +1,209 @@
+// offline generation module - builds a commit message from local heuristics without any api call
+
+use super::intelligence::{analyse_commit_intelligence, CommitIntelligence};
+use super::patterns::PatternType;
+use super::validation::{post_process_commit_message, validate_commit_message};
+use crate::git::{extract_key_changes, DiffInfo, FileStatus};
+use anyhow::Result;
+
+const MAX_BULLETS: usize = 6;
+
+/// generate a validated conventional commit purely from diff analysis
+pub fn generate_offline_commit(diff_info: &DiffInfo) -> Result<String> {
+    let intelligence = analyse_commit_intelligence(diff_info);
+
+    let type_scope = match &intelligence.scope_hint {
+        Some(scope) if !scope.is_empty() => format!("{}({scope})", intelligence.commit_type_hint),
+        _ => intelligence.commit_type_hint.clone(),
+    };
+
+    let description = describe_changes(diff_info, &intelligence);
+    let mut message = post_process_commit_message(&format!("{type_scope}: {description}"));
+
+    // heuristics can produce something the validator rejects (e.g. overly long names)
+    if validate_commit_message(&message).is_err() {
+        message = format!("{type_scope}: {}", fallback_description(diff_info));
+        validate_commit_message(&message)?;
+    }
+
+    if intelligence.requires_body {
+        let bullets = build_bullets(diff_info, &intelligence);
+        if !bullets.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&bullets.join("\n"));
+        }
+    }
+
+    Ok(message)
+}
+
+/// describe the change in imperative mood based on the suggested type
+fn describe_changes(diff_info: &DiffInfo, intelligence: &CommitIntelligence) -> String {
+    let subject = change_subject(diff_info);
+    let functions = added_function_names(diff_info);
+    let key_changes: Vec<String> = diff_info
+        .files
+        .iter()
+        .map(|f| extract_key_changes(&f.diff_content()))
+        .collect();
+    let has_pattern = |pattern_type: PatternType| {
+        intelligence
+            .detected_patterns
+            .iter()
+            .any(|p| p.pattern_type == pattern_type)
+    };
+
+    match intelligence.commit_type_hint.as_str() {
+        "feat" => match functions.as_slice() {
+            [only] => format!("add {only} to {subject}"),
+            [first, rest @ ..] => format!(
+                "add {first} and {} other function{} to {subject}",
+                rest.len(),
+                if rest.len() == 1 { "" } else { "s" }
+            ),
+            [] if has_pattern(PatternType::NewFilePattern) => format!("add {subject}"),
+            [] => format!("extend {subject}"),
+        },
+        "fix" => {
+            if key_changes
+                .iter()
+                .any(|c| c.contains("improve error handling"))
+            {
+                format!("improve error handling in {subject}")
+            } else {
+                format!("resolve issue in {subject}")
+            }
+        }
+        "refactor" => match diff_info.files.as_slice() {
+            [file] => match &file.status {
+                FileStatus::Renamed { from } => format!("rename {from} to {}", file.path),
+                FileStatus::Deleted => format!("remove {subject}"),
+                _ => format!("restructure {subject}"),
+            },
+            _ if diff_info
+                .files
+                .iter()
+                .all(|f| matches!(f.status, FileStatus::Renamed { .. })) =>
+            {
+                format!("move {subject}")
+            }
+            _ => format!("restructure {subject}"),
+        },
+        "docs" => format!("update {subject} documentation"),
+        "test" => format!("update tests for {subject}"),
+        "perf" => format!("improve performance of {subject}"),
+        "ci" => format!("update {subject} pipeline"),
+        "style" => format!("normalise formatting in {subject}"),
+        "build" if has_pattern(PatternType::SubmoduleUpdate) => {
+            let bumped: Vec<_> = diff_info
+                .files
+                .iter()
+                .filter_map(|f| Some((f.path.as_str(), f.submodule.as_ref()?.new?)))
+                .collect();
+            match bumped.as_slice() {
+                [(path, new)] => format!(
+                    "bump {} to {}",
+                    path.rsplit('/').next().unwrap_or(path),
+                    &new.to_string()[..7]
+                ),
+                _ => format!("update {subject} submodules"),
+            }
+        }
+        "chore" if has_pattern(PatternType::DependencyUpdate) => {
+            format!("update dependencies in {subject}")
+        }
+        _ => format!("update {subject}"),
+    }
+}
+
+/// name the thing that changed: a file, a shared directory, or a file count
+fn change_subject(diff_info: &DiffInfo) -> String {
+    if let [file] = diff_info.files.as_slice() {
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        return match name.split_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+            _ => name.to_string(),
+        };
+    }
+
+    let parents: Vec<&str> = diff_info
+        .files
+        .iter()
+        .map(|f| f.path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(""))
+        .collect();
+    if let Some(first) = parents.first() {
+        if !first.is_empty() && parents.iter().all(|p| p == first) {
+            return first.rsplit('/').next().unwrap_or(first).to_string();
+        }
+    }
+
+    format!("{} files", diff_info.files.len())
+}
+
+/// names of functions added anywhere in the diff, in file order
+fn added_function_names(diff_info: &DiffInfo) -> Vec<String> {
+    let mut names = Vec::new();
+    for file in &diff_info.files {
+        for change in extract_key_changes(&file.diff_content()).split(", ") {
+            if let Some(name) = change.strip_prefix("add function ") {
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// safe description used when the heuristic one fails validation
+fn fallback_description(diff_info: &DiffInfo) -> String {
+    let count = diff_info.files.len();
+    format!("update {count} file{}", if count == 1 { "" } else { "s" })
+}
+
+/// body bullets from per-file key changes, topped up with pattern suggestions
+fn build_bullets(diff_info: &DiffInfo, intelligence: &CommitIntelligence) -> Vec<String> {
+    let mut bullets = Vec::new();
+
+    // the commits a submodule bump brings in say more than its pointer diff
+    for file in &diff_info.files {
+        let Some(submodule) = &file.submodule else {
+            continue;
+        };
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        for subject in &submodule.commits {
+            let verb = if submodule.rewound { "Drop" } else { "Pick up" };
+            bullets.push(format!("- {verb} \"{subject}\" from {name}"));
+        }
+    }
+
+    for file in diff_info
+        .files
+        .iter()
+        .filter(|f| !f.is_minified && f.submodule.is_none())
+    {
+        let key_changes = extract_key_changes(&file.diff_content());
+        if !key_changes.is_empty() {
+            bullets.push(format!("- {} in {}", capitalise(&key_changes), file.path));
+        }
+    }
+
+    for suggestion in &intelligence.suggested_bullets {
+        let bullet = format!("- {}", capitalise(suggestion));
+        if !bullets.contains(&bullet) {
+            bullets.push(bullet);
+        }
+    }
+
+    bullets.truncate(MAX_BULLETS);
+    bullets
+}
+
+/// bullets start with a capital letter, matching the generated style
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -28,4 +28,8 @@ pub enum PatternType {
     CiChange,
     Deprecation,
     SecurityFix,
+    FileRelocation,
+    FileRemoval,
+    ModeChange,
+    SubmoduleUpdate,
 }
@@ -2,12 +2,40 @@
 
 use super::intelligence::CommitIntelligence;
 use super::patterns::PatternType;
-use crate::git::DiffInfo;
-
-/// construct intelligent prompt using commit analysis
+use super::structured::OutputFormat;
+use super::tokens::{estimate_tokens, prompt_budget, DEFAULT_CONTEXT_TOKENS};
+use crate::git::{DiffInfo, DiffLine, Hunk};
+use crate::ignore::ContextRule;
+
+// diff budget at which files get the standard per-file line allowance
+const BASELINE_DIFF_TOKENS: usize = 24_000;
+// cap on how far larger context windows stretch the per-file allowance
+const MAX_DIFF_SCALE: usize = 8;
+const BASE_MAX_DIFF_FILES: usize = 15;
+// not worth starting another file with less room than this
+const MIN_FILE_DIFF_TOKENS: usize = 64;
+// added after the diff when any excerpt had to be cut or left out
+const SHORTENED_NOTE: &str = "\n(diff excerpts shortened to fit the model's context window)\n";
+
+/// construct intelligent prompt using commit analysis, sized for a typical context window
 pub fn construct_intelligent_prompt(
     diff_info: &DiffInfo,
     intelligence: &CommitIntelligence,
+) -> String {
+    construct_intelligent_prompt_with_budget(
+        diff_info,
+        intelligence,
+        prompt_budget(DEFAULT_CONTEXT_TOKENS, 400),
+        OutputFormat::Tagged,
+    )
+}
+
+/// construct intelligent prompt, filling the diff section up to `budget_tokens` in total
+pub fn construct_intelligent_prompt_with_budget(
+    diff_info: &DiffInfo,
+    intelligence: &CommitIntelligence,
+    budget_tokens: usize,
+    format: OutputFormat,
 ) -> String {
     let mut prompt = String::new();
 
@@ -85,7 +113,10 @@ pub fn construct_intelligent_prompt(
             ));
             break;
         }
-        prompt.push_str(&format!("- {}\n", file.path));
+        match file.status.describe() {
+            Some(status) => prompt.push_str(&format!("- {} ({status})\n", file.path)),
+            None => prompt.push_str(&format!("- {}\n", file.path)),
+        }
     }
 
     prompt.push_str("\n🎯 SCOPE DETERMINATION GUIDELINES:\n");
@@ -132,11 +163,49 @@ pub fn construct_intelligent_prompt(
         prompt.push('\n');
     }
 
+    // amending: the old message is a hint, the diff below is the whole change
+    if let Some(previous) = &diff_info.previous_message {
+        prompt
+            .push_str("✏️ REWRITING THE MESSAGE OF AN EXISTING COMMIT. ITS CURRENT MESSAGE IS:\n");
+        prompt.push_str(previous);
+        prompt.push_str(
+            "\n(keep what is still accurate, but describe everything in the changes below)\n\n",
+        );
+    }
+
     // actual code changes
     prompt.push_str("📁 ACTUAL CODE CHANGES:\n");
     prompt.push_str(&diff_info.summary);
     prompt.push('\n');
 
+    // a submodule's pointer diff is just two hashes, so show the commits it brings in
+    let submodules: Vec<_> = diff_info
+        .files
+        .iter()
+        .filter_map(|f| Some((f, f.submodule.as_ref()?)))
+        .collect();
+    if !submodules.is_empty() {
+        prompt.push_str("\n🔗 SUBMODULE UPDATES (treat as a dependency bump, e.g. build(deps)):\n");
+        for (file, submodule) in &submodules {
+            prompt.push_str(&format!("- {} ({})\n", file.path, submodule.describe()));
+            if !submodule.log_available {
+                prompt.push_str("  (the submodule's history isn't available locally)\n");
+            }
+            for subject in &submodule.commits {
+                prompt.push_str(&format!(
+                    "  {} {subject}\n",
+                    if submodule.rewound { "-" } else { "+" }
+                ));
+            }
+            if submodule.commit_count > submodule.commits.len() {
+                prompt.push_str(&format!(
+                    "  ... and {} more commits\n",
+                    submodule.commit_count - submodule.commits.len()
+                ));
+            }
+        }
+    }
+
     // include minified files summary first if any
     let minified_files: Vec<_> = diff_info.files.iter().filter(|f| f.is_minified).collect();
     if !minified_files.is_empty() {
@@ -159,16 +228,26 @@ pub fn construct_intelligent_prompt(
         prompt.push_str("(minified content omitted for clarity)\n\n");
     }
 
+    // everything after the diff is fixed, so build it first to know what's left for the diff
+    let tail = construct_prompt_tail(intelligence, &dominant_language, format);
+
     // include diff snippets
     if !diff_info.files.is_empty() {
         prompt.push_str("\n🔍 DIFF CONTENT (for context):\n");
 
+        // room is kept for the note saying excerpts were shortened, in case they are
+        let diff_budget = budget_tokens.saturating_sub(
+            estimate_tokens(&prompt) + estimate_tokens(&tail) + estimate_tokens(SHORTENED_NOTE),
+        );
+        // bigger context windows let each file show proportionally more of its diff
+        let scale = (diff_budget / BASELINE_DIFF_TOKENS).clamp(1, MAX_DIFF_SCALE);
+
         let important_files = get_important_files_for_diff(&diff_info.files);
-        let mut total_diff_lines = 0;
-        const MAX_TOTAL_DIFF_LINES: usize = 3000;
+        let mut used_tokens = 0;
+        let mut truncated = false;
 
         for (i, file) in important_files.iter().enumerate() {
-            if i >= 15 || total_diff_lines >= MAX_TOTAL_DIFF_LINES {
+            if i >= BASE_MAX_DIFF_FILES * scale {
                 break;
             }
 
@@ -177,26 +256,52 @@ pub fn construct_intelligent_prompt(
                 continue;
             }
 
-            prompt.push_str(&format!(
-                "\n--- {} (+{} -{}) ---\n",
-                file.path, file.added_lines, file.removed_lines
-            ));
-
-            let lines_to_include =
-                calculate_diff_lines_for_file(file, total_diff_lines, MAX_TOTAL_DIFF_LINES);
-            let meaningful_diff =
-                extract_meaningful_diff_lines(&file.diff_content, lines_to_include);
+            let header = format!(
+                "\n--- {}{} (+{} -{}) ---\n",
+                file.path,
+                file.status
+                    .describe()
+                    .map(|status| format!(", {status}"))
+                    .unwrap_or_default(),
+                file.added_lines,
+                file.removed_lines
+            );
+            let remaining = diff_budget.saturating_sub(used_tokens + estimate_tokens(&header));
+            if remaining < MIN_FILE_DIFF_TOKENS {
+                truncated = true;
+                break;
+            }
+            prompt.push_str(&header);
+            used_tokens += estimate_tokens(&header);
+
+            let mut lines_to_include = calculate_diff_lines_for_file(file, scale);
+            let mut meaningful_diff = sample_hunks(&file.hunks, lines_to_include);
+
+            // shrink the excerpt proportionally until it fits what's left of the budget
+            // counted with the newline that follows it
+            let mut diff_tokens = estimate_tokens(&meaningful_diff) + 1;
+            while diff_tokens > remaining && lines_to_include > 0 {
+                truncated = true;
+                lines_to_include =
+                    (lines_to_include * remaining / diff_tokens).min(lines_to_include - 1);
+                meaningful_diff = sample_hunks(&file.hunks, lines_to_include);
+                diff_tokens = estimate_tokens(&meaningful_diff) + 1;
+            }
 
-            if !meaningful_diff.is_empty() {
-                prompt.push_str(&meaningful_diff);
-                prompt.push('\n');
-                total_diff_lines += meaningful_diff.lines().count();
-            } else {
-                prompt.push_str(&format!(
-                    "Large diff with {} additions, {} deletions\n",
+            if meaningful_diff.is_empty() {
+                meaningful_diff = format!(
+                    "Large diff with {} additions, {} deletions",
                     file.added_lines, file.removed_lines
-                ));
+                );
+                diff_tokens = estimate_tokens(&meaningful_diff) + 1;
             }
+            prompt.push_str(&meaningful_diff);
+            prompt.push('\n');
+            used_tokens += diff_tokens;
+        }
+
+        if truncated {
+            prompt.push_str(SHORTENED_NOTE);
         }
 
         let skipped_files = diff_info.files.len() - important_files.len();
@@ -208,8 +313,20 @@ pub fn construct_intelligent_prompt(
     }
     prompt.push('\n');
 
+    prompt.push_str(&tail);
+    prompt
+}
+
+/// examples and instructions that close every prompt
+fn construct_prompt_tail(
+    intelligence: &CommitIntelligence,
+    dominant_language: &str,
+    format: OutputFormat,
+) -> String {
+    let mut prompt = String::new();
+
     // provide examples
-    add_language_tailored_examples(&mut prompt, intelligence, &dominant_language);
+    add_language_tailored_examples(&mut prompt, intelligence, dominant_language);
 
     // clear instructions
     prompt.push_str("🎯 INSTRUCTIONS:\n");
@@ -239,25 +356,76 @@ pub fn construct_intelligent_prompt(
     }
     prompt.push('\n');
 
-    // require deterministic, tagged output for robust parsing
-    prompt.push_str("output must be wrapped exactly once in <commit>...</commit> tags with no extra commentary.\n");
+    // require deterministic, tagged or structured output for robust parsing
+    prompt.push_str(format.prompt_instruction());
     prompt.push_str("generate the commit message now.\n");
 
     prompt
 }
 
 /// get system prompt based on intelligence
-pub fn get_system_prompt(intelligence: &CommitIntelligence) -> &'static str {
-    if intelligence.complexity_score > 3.0 {
-        "you are an expert software engineer writing precise, detailed commit messages. only output the commit inside <commit>...</commit> tags, no extra commentary."
+pub fn get_system_prompt(intelligence: &CommitIntelligence, format: OutputFormat) -> String {
+    let persona = if intelligence.complexity_score > 3.0 {
+        "you are an expert software engineer writing precise, detailed commit messages."
     } else if intelligence.requires_body {
-        "you are a senior developer creating clear commit messages. only output the commit inside <commit>...</commit> tags, no extra commentary."
+        "you are a senior developer creating clear commit messages."
+    } else {
+        "you are a developer writing concise commit messages."
+    };
+    format!("{persona} {}", format.system_instruction())
+}
+
+/// pick up to `max_lines` diff lines spread fairly over every hunk, so the end of a long
+/// diff is represented as well as its start. each hunk shown keeps its @@ header
+pub fn sample_hunks(hunks: &[Hunk], max_lines: usize) -> String {
+    if hunks.is_empty() || max_lines == 0 {
+        return String::new();
+    }
+
+    // a header plus at least one line per hunk; with too many hunks, take evenly spaced ones
+    let shown: Vec<&Hunk> = if hunks.len() * 2 <= max_lines {
+        hunks.iter().collect()
     } else {
-        "you are a developer writing concise commit messages. only output the commit inside <commit>...</commit> tags, no extra commentary."
+        let count = (max_lines / 2).max(1);
+        (0..count)
+            .map(|i| &hunks[i * hunks.len() / count])
+            .collect()
+    };
+
+    // deal the remaining lines out one at a time, so one huge hunk can't starve the rest
+    let mut quotas = vec![0; shown.len()];
+    let mut left = max_lines.saturating_sub(shown.len());
+    while left > 0 {
+        let mut dealt = false;
+        for (quota, hunk) in quotas.iter_mut().zip(&shown) {
+            if left > 0 && *quota < hunk.lines.len() {
+                *quota += 1;
+                left -= 1;
+                dealt = true;
+            }
+        }
+        if !dealt {
+            break;
+        }
+    }
+
+    let mut selected = Vec::new();
+    for (hunk, quota) in shown.iter().zip(quotas) {
+        selected.push(hunk.header_line());
+        selected.extend(pick_hunk_lines(hunk, quota));
+    }
+    let hidden = hunks.len() - shown.len();
+    if hidden > 0 {
+        selected.push(format!(
+            "... {hidden} more hunk{} not shown",
+            if hidden == 1 { "" } else { "s" }
+        ));
     }
+    selected.join("\n")
 }
 
-/// extract meaningful diff lines
+/// extract meaningful diff lines from patch text, added and important lines first
+#[deprecated(note = "diffs are modelled as hunks now; use sample_hunks on ModifiedFile::hunks")]
 pub fn extract_meaningful_diff_lines(diff_content: &str, max_lines: usize) -> String {
     let lines: Vec<&str> = diff_content.lines().collect();
     let mut selected_lines = Vec::new();
@@ -292,6 +460,24 @@ pub fn extract_meaningful_diff_lines(diff_content: &str, max_lines: usize) -> St
     selected_lines.join("\n")
 }
 
+/// the `quota` most useful lines of a hunk, in their original order
+fn pick_hunk_lines(hunk: &Hunk, quota: usize) -> Vec<String> {
+    let lines: Vec<String> = hunk.lines.iter().map(DiffLine::render).collect();
+
+    // additions first, then other important changes, then anything non-blank
+    let added = |i: &usize| matches!(hunk.lines[*i], DiffLine::Added(_));
+    let important = |i: &usize| is_important_line(&lines[*i]);
+    let mut picked: Vec<usize> = (0..lines.len())
+        .filter(|i| added(i) && important(i))
+        .chain((0..lines.len()).filter(|i| !added(i) && important(i)))
+        .chain((0..lines.len()).filter(|i| !important(i) && !lines[*i].trim().is_empty()))
+        .take(quota)
+        .collect();
+
+    picked.sort_unstable();
+    picked.into_iter().map(|i| lines[i].clone()).collect()
+}
+
 /// check if a line is important for ai context
 fn is_important_line(line: &str) -> bool {
     // skip empty lines and pure formatting
@@ -348,6 +534,10 @@ pub fn format_pattern_type(pattern_type: &PatternType) -> &'static str {
         PatternType::CiChange => "ci/cd",
         PatternType::Deprecation => "deprecation",
         PatternType::SecurityFix => "security fix",
+        PatternType::FileRelocation => "rename",
+        PatternType::FileRemoval => "removal",
+        PatternType::ModeChange => "file mode",
+        PatternType::SubmoduleUpdate => "submodule",
     }
 }
 
@@ -472,7 +662,11 @@ fn get_important_files_for_diff(
     // filter out auto-generated files
     sorted_files
         .into_iter()
-        .filter(|f| !is_auto_generated_or_boring_file(&f.path))
+        .filter(|f| match f.context {
+            ContextRule::Default => !is_auto_generated_or_boring_file(&f.path),
+            ContextRule::Excluded(_) => false,
+            ContextRule::Included => true,
+        })
         .collect()
 }
 
@@ -541,21 +735,16 @@ fn get_file_priority(path: &str) -> u8 {
 }
 
 /// calculate diff lines for file
-fn calculate_diff_lines_for_file(
-    file: &crate::git::ModifiedFile,
-    current_total: usize,
-    max_total: usize,
-) -> usize {
-    let remaining = max_total.saturating_sub(current_total);
+fn calculate_diff_lines_for_file(file: &crate::git::ModifiedFile, scale: usize) -> usize {
     let file_changes = file.added_lines + file.removed_lines;
 
-    // dynamic allocation based on file importance and remaining space
+    // dynamic allocation based on file size, stretched for larger context windows
     if file_changes < 50 {
-        file_changes.min(remaining)
+        file_changes
     } else if file_changes < 200 {
-        (file_changes / 2).min(remaining).min(100)
+        (file_changes / 2 * scale).min(file_changes)
     } else {
-        50.min(remaining)
+        (50 * scale).min(file_changes)
     }
 }
 
@@ -681,3 +870,173 @@ fn add_single_line_examples(
 
     prompt.push_str("```\n\n");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    /// a hunk of `count` added lines, starting at `start`
+    fn hunk(start: u32, count: usize) -> Hunk {
+        Hunk {
+            old_start: start,
+            new_start: start,
+            new_lines: count as u32,
+            lines: (0..count)
+                .map(|i| DiffLine::Added(format!("let line_{start}_{i} = {i};")))
+                .collect(),
+            ..Hunk::default()
+        }
+    }
+
+    /// `files` new rust files of `lines` lines each, staged in a scratch repository
+    fn staged_files(files: usize, lines: usize) -> DiffInfo {
+        let (dir, repo) = test_support::scratch_repo();
+        let paths: Vec<String> = (0..files)
+            .map(|file| format!("src/file_{file}.rs"))
+            .collect();
+        for (file, path) in paths.iter().enumerate() {
+            let content: String = (0..lines)
+                .map(|i| format!("let value_{i} = compute({i}, \"padding for file {file}\");\n"))
+                .collect();
+            test_support::write(&dir, path, &content);
+        }
+        test_support::stage(&repo, &paths.iter().map(String::as_str).collect::<Vec<_>>());
+        crate::git::get_diff_info(test_support::path(&dir), usize::MAX, 100, false).unwrap()
+    }
+
+    fn prompt(diff_info: &DiffInfo, budget_tokens: usize) -> String {
+        let intelligence = crate::ai::analyse_commit_intelligence(diff_info);
+        construct_intelligent_prompt_with_budget(
+            diff_info,
+            &intelligence,
+            budget_tokens,
+            OutputFormat::Tagged,
+        )
+    }
+
+    fn diff_lines(prompt: &str) -> usize {
+        prompt.lines().filter(|l| l.starts_with("+let ")).count()
+    }
+
+    #[test]
+    fn a_large_diff_stays_within_the_budget() {
+        let diff_info = staged_files(3, 2_000);
+        // the prompt without any diff is under 1k tokens, 50 lines of each file about 2k more
+        for budget in [1_200, 2_000, 2_500] {
+            let prompt = prompt(&diff_info, budget);
+            assert!(
+                estimate_tokens(&prompt) <= budget,
+                "{} tokens for a budget of {budget}",
+                estimate_tokens(&prompt)
+            );
+            assert!(prompt.contains(SHORTENED_NOTE));
+            assert!(diff_lines(&prompt) > 0);
+        }
+    }
+
+    #[test]
+    fn bigger_budgets_show_more_of_each_file_up_to_the_scale_cap() {
+        let diff_info = staged_files(1, 1_000);
+        // up to twice the baseline, a large file shows its usual 50 lines, @@ header included
+        let baseline = prompt(&diff_info, 30_000);
+        assert_eq!(diff_lines(&baseline), 50 - 1);
+        assert!(!baseline.contains(SHORTENED_NOTE));
+        // a huge window stretches that at most MAX_DIFF_SCALE times
+        let huge = prompt(&diff_info, 10_000_000);
+        assert_eq!(diff_lines(&huge), 50 * MAX_DIFF_SCALE - 1);
+        assert!(!huge.contains(SHORTENED_NOTE));
+    }
+
+    #[test]
+    fn files_without_room_for_a_useful_excerpt_are_left_out() {
+        let diff_info = staged_files(2, 100);
+        let nothing = prompt(&diff_info, 0);
+        assert!(nothing.contains(SHORTENED_NOTE));
+        assert!(!nothing.contains("--- src/file_"));
+
+        // room for a header, but not MIN_FILE_DIFF_TOKENS of excerpt after it
+        let budget = estimate_tokens(&nothing) + MIN_FILE_DIFF_TOKENS / 2;
+        let cramped = prompt(&diff_info, budget);
+        assert!(cramped.contains(SHORTENED_NOTE));
+        assert!(!cramped.contains("--- src/file_"));
+        assert_eq!(diff_lines(&cramped), 0);
+    }
+
+    #[test]
+    fn a_huge_hunk_does_not_starve_the_others() {
+        let hunks = [hunk(1, 100), hunk(200, 3)];
+        let sample = sample_hunks(&hunks, 10);
+        let lines: Vec<&str> = sample.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "@@ -1,0 +1,100 @@");
+        // the small hunk is shown whole, the big one gets the rest
+        assert_eq!(
+            &lines[6..],
+            [
+                "@@ -200,0 +200,3 @@",
+                "+let line_200_0 = 0;",
+                "+let line_200_1 = 1;",
+                "+let line_200_2 = 2;",
+            ]
+        );
+        assert_eq!(lines[5], "+let line_1_4 = 4;");
+    }
+
+    #[test]
+    fn too_many_hunks_are_sampled_evenly_with_a_note() {
+        let hunks: Vec<Hunk> = (0..10).map(|i| hunk(i * 100 + 1, 2)).collect();
+        let sample = sample_hunks(&hunks, 6);
+        let headers: Vec<&str> = sample.lines().filter(|l| l.starts_with("@@")).collect();
+        assert_eq!(
+            headers,
+            [
+                "@@ -1,0 +1,2 @@",
+                "@@ -301,0 +301,2 @@",
+                "@@ -601,0 +601,2 @@"
+            ]
+        );
+        assert!(sample.ends_with("... 7 more hunks not shown"), "{sample}");
+
+        let sample = sample_hunks(&hunks[..3], 4);
+        assert!(sample.ends_with("... 1 more hunk not shown"), "{sample}");
+    }
+
+    #[test]
+    fn nothing_is_sampled_without_hunks_or_room() {
+        assert_eq!(sample_hunks(&[], 10), "");
+        assert_eq!(sample_hunks(&[hunk(1, 3)], 0), "");
+    }
+
+    #[test]
+    fn hunk_lines_favour_additions_and_keep_their_order() {
+        let hunk = Hunk {
+            old_start: 1,
+            old_lines: 4,
+            new_start: 1,
+            new_lines: 4,
+            lines: vec![
+                DiffLine::Context("fn main() {".to_string()),
+                DiffLine::Removed("    old_call();".to_string()),
+                DiffLine::Added("    new_call();".to_string()),
+                DiffLine::Added("}".to_string()),
+                DiffLine::Added("    another_call();".to_string()),
+            ],
+            ..Hunk::default()
+        };
+        assert_eq!(
+            pick_hunk_lines(&hunk, 2),
+            ["+    new_call();", "+    another_call();"]
+        );
+        // then the removal, still in its original place
+        assert_eq!(
+            pick_hunk_lines(&hunk, 3),
+            [
+                "-    old_call();",
+                "+    new_call();",
+                "+    another_call();"
+            ]
+        );
+        assert_eq!(pick_hunk_lines(&hunk, 10).len(), 5);
+    }
+}
@@ -0,0 +1,1108 @@
+// provider abstraction module - routes chat completions to the configured llm backend
+
+use super::reasoning::separate_reasoning;
+use crate::error::WizardError;
+use crate::{AvailableModel, GenerationParams, ProviderConfig, ProviderKind, ReasoningEffort};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// provider-neutral chat completion request
+#[derive(Serialize, Clone, Debug)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>, // json schema for structured output
+    #[serde(skip)]
+    pub reasoning_effort: Option<ReasoningEffort>, // each backend spells this differently
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>, // provider-specific params from the config
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+/// provider-neutral chat completion response
+#[derive(Debug, Clone, Default)]
+pub struct ChatResponse {
+    pub content: String,           // the answer alone, with any reasoning taken out
+    pub reasoning: Option<String>, // what a reasoning model thought before answering
+    pub usage: Option<TokenUsage>, // none when the backend didn't report it
+}
+
+impl ChatResponse {
+    /// separate a finished completion's reasoning from its answer; a model that only
+    /// thought (usually out of tokens) counts as an empty response
+    fn separate(
+        provider: &str,
+        content: &str,
+        reasoning: Option<String>,
+        usage: Option<TokenUsage>,
+    ) -> Result<Self> {
+        let output = separate_reasoning(content, reasoning);
+        if output.answer.is_empty() {
+            return Err(WizardError::EmptyResponse {
+                provider: provider.to_string(),
+            }
+            .into());
+        }
+
+        Ok(Self {
+            content: output.answer,
+            reasoning: output.reasoning,
+            usage,
+        })
+    }
+}
+
+/// tokens billed for a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// a fragment of a streamed completion
+#[derive(Debug, Clone, Copy)]
+pub enum StreamEvent<'a> {
+    /// thinking tokens from reasoning models - shown, never committed
+    Reasoning(&'a str),
+    /// answer tokens
+    Content(&'a str),
+}
+
+/// returned by stream handlers to keep reading or hang up early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamControl {
+    Continue,
+    Stop,
+}
+
+/// callback receiving stream events as they arrive
+pub type StreamHandler<'a> = dyn FnMut(StreamEvent<'_>) -> StreamControl + Send + 'a;
+
+/// a backend capable of turning a chat request into a completion
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// short lowercase name used in messages and errors
+    fn name(&self) -> &str;
+
+    /// send a single chat completion request
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse>;
+
+    /// send a chat completion request, reporting tokens as they arrive.
+    /// backends without streaming support report the whole answer at once
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        on_event: &mut StreamHandler<'_>,
+    ) -> Result<ChatResponse> {
+        let response = self.complete(request).await?;
+        on_event(StreamEvent::Content(&response.content));
+        Ok(response)
+    }
+
+    /// list the models this backend can serve
+    async fn list_models(&self) -> Result<Vec<AvailableModel>>;
+}
+
+/// whether an error is worth retrying on the next model in the fallback chain
+pub fn warrants_fallback(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<WizardError>()
+            .is_some_and(WizardError::warrants_fallback)
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(reqwest::Error::is_timeout)
+    })
+}
+
+// hosted apis answer quickly; local models on modest hardware need far longer
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// build the provider described by a profile, resolving its api key from the environment
+pub fn build_provider(config: &ProviderConfig) -> Result<Box<dyn LlmProvider>> {
+    let base_url = config.base_url().trim_end_matches('/').to_string();
+    let api_key = match config.api_key_env() {
+        Some(key_env) => {
+            let key = env::var(&key_env)
+                .ok()
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty());
+            if key.is_none() && config.requires_api_key() {
+                return Err(WizardError::Auth(format!(
+                    "{key_env} environment variable is not set"
+                ))
+                .into());
+            }
+            key
+        }
+        None => None,
+    };
+
+    let provider: Box<dyn LlmProvider> = match config.provider {
+        ProviderKind::Openrouter => Box::new(OpenAiCompatibleProvider {
+            label: "openrouter".to_string(),
+            base_url,
+            api_key,
+            timeout: REMOTE_TIMEOUT,
+            openrouter: true,
+        }),
+        ProviderKind::OpenaiCompatible => Box::new(OpenAiCompatibleProvider {
+            label: config.display_name(),
+            base_url,
+            api_key,
+            timeout: REMOTE_TIMEOUT,
+            openrouter: false,
+        }),
+        ProviderKind::Anthropic => Box::new(AnthropicProvider {
+            base_url,
+            api_key: api_key.unwrap_or_default(),
+        }),
+        ProviderKind::Ollama | ProviderKind::LlamaCpp => {
+            ensure_loopback(&base_url)?;
+            Box::new(LocalProvider {
+                kind: config.provider,
+                root_url: base_url.clone(),
+                chat: OpenAiCompatibleProvider {
+                    label: config.display_name(),
+                    base_url: format!("{base_url}/v1"),
+                    api_key,
+                    timeout: LOCAL_TIMEOUT,
+                    openrouter: false,
+                },
+            })
+        }
+    };
+
+    Ok(provider)
+}
+
+/// refuse to send code anywhere but this machine when a local provider is configured
+fn ensure_loopback(base_url: &str) -> Result<()> {
+    let host = base_url
+        .split("://")
+        .nth(1)
+        .unwrap_or(base_url)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    // strip the port, keeping bracketed ipv6 hosts intact
+    let host = if host.starts_with('[') {
+        host.split(']')
+            .next()
+            .unwrap_or(host)
+            .trim_start_matches('[')
+    } else {
+        host.split(':').next().unwrap_or(host)
+    };
+
+    let is_loopback = host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false);
+
+    if is_loopback {
+        Ok(())
+    } else {
+        Err(WizardError::Config(format!(
+            "local providers must point at localhost, but base_url is '{base_url}'"
+        ))
+        .into())
+    }
+}
+
+// openai-compatible wire structures (used by openrouter, openai, deepseek, etc.)
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+}
+
+impl From<OpenAiUsage> for TokenUsage {
+    fn from(usage: OpenAiUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    message: OpenAiResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    reasoning: Option<String>,
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+// the neutral request plus reasoning and streaming fields
+#[derive(Serialize)]
+struct OpenAiRequest<'a> {
+    #[serde(flatten)]
+    request: &'a ChatRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<OpenRouterReasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAiStreamOptions>,
+}
+
+// openrouter nests the effort in an object; everyone else takes openai's flat field
+#[derive(Serialize)]
+struct OpenRouterReasoning {
+    effort: ReasoningEffort,
+}
+
+#[derive(Serialize)]
+struct OpenAiStreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiStreamChoice>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+    #[serde(default)]
+    error: Option<StreamError>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiStreamChoice {
+    #[serde(default)]
+    delta: OpenAiDelta,
+}
+
+// openrouter calls it `reasoning`, deepseek and llama.cpp call it `reasoning_content`
+#[derive(Deserialize, Default)]
+struct OpenAiDelta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    reasoning: Option<String>,
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StreamError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiModelList {
+    data: Vec<OpenAiModel>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiModel {
+    id: String,
+    // openrouter and some gateways report this; plain openai does not
+    #[serde(default)]
+    context_length: Option<u32>,
+}
+
+/// openrouter and any endpoint speaking the openai chat completions protocol
+pub struct OpenAiCompatibleProvider {
+    label: String,
+    base_url: String,
+    api_key: Option<String>,
+    timeout: Duration,
+    openrouter: bool,
+}
+
+impl OpenAiCompatibleProvider {
+    fn wire<'a>(&self, request: &'a ChatRequest, stream: bool) -> OpenAiRequest<'a> {
+        let effort = request.reasoning_effort;
+        OpenAiRequest {
+            request,
+            reasoning: effort
+                .filter(|_| self.openrouter)
+                .map(|effort| OpenRouterReasoning { effort }),
+            reasoning_effort: effort.filter(|_| !self.openrouter),
+            stream,
+            // asks for a final chunk carrying token usage
+            stream_options: stream.then_some(OpenAiStreamOptions {
+                include_usage: true,
+            }),
+        }
+    }
+
+    fn authorise(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.header("Authorization", format!("Bearer {key}")),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let wire = self.wire(request, false);
+        let response = send_with_retries(&self.label, &request.model, self.timeout, |client| {
+            self.authorise(client.post(&url))
+                .header("Content-Type", "application/json")
+                .json(&wire)
+        })
+        .await?;
+
+        let body = response
+            .json::<OpenAiResponse>()
+            .await
+            .with_context(|| format!("failed to parse {} api response", self.label))?;
+
+        let (content, reasoning) = match body.choices.into_iter().next() {
+            Some(OpenAiChoice { message }) => (
+                message.content.unwrap_or_default(),
+                message.reasoning.or(message.reasoning_content),
+            ),
+            None => (String::new(), None),
+        };
+        ChatResponse::separate(
+            &self.label,
+            &content,
+            reasoning,
+            body.usage.map(TokenUsage::from),
+        )
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        on_event: &mut StreamHandler<'_>,
+    ) -> Result<ChatResponse> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let wire = self.wire(request, true);
+        let response = send_with_retries(&self.label, &request.model, self.timeout, |client| {
+            self.authorise(client.post(&url))
+                .header("Content-Type", "application/json")
+                .json(&wire)
+        })
+        .await?;
+
+        let mut events = SseReader::new(response);
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut usage = None;
+        while let Some(data) = events.next_data().await? {
+            if data == "[DONE]" {
+                break;
+            }
+            let chunk: OpenAiStreamChunk = serde_json::from_str(&data)
+                .with_context(|| format!("failed to parse {} stream chunk", self.label))?;
+            if let Some(error) = chunk.error {
+                return Err(WizardError::Api(format!(
+                    "{} api error during stream: {}",
+                    self.label, error.message
+                ))
+                .into());
+            }
+            // usage arrives on its own in the final chunk, so an early stop never sees it
+            if let Some(reported) = chunk.usage {
+                usage = Some(reported.into());
+            }
+
+            let Some(choice) = chunk.choices.into_iter().next() else {
+                continue;
+            };
+            let delta = choice.delta;
+            if let Some(thinking) = delta.reasoning.or(delta.reasoning_content) {
+                reasoning.push_str(&thinking);
+                if !thinking.is_empty()
+                    && on_event(StreamEvent::Reasoning(&thinking)) == StreamControl::Stop
+                {
+                    break;
+                }
+            }
+            if let Some(text) = delta.content {
+                content.push_str(&text);
+                if !text.is_empty() && on_event(StreamEvent::Content(&text)) == StreamControl::Stop
+                {
+                    break;
+                }
+            }
+        }
+
+        ChatResponse::separate(&self.label, &content, Some(reasoning), usage)
+    }
+
+    async fn list_models(&self) -> Result<Vec<AvailableModel>> {
+        let url = format!("{}/models", self.base_url);
+        let response = send_with_retries(&self.label, "", self.timeout, |client| {
+            self.authorise(client.get(&url))
+        })
+        .await?;
+
+        let body = response
+            .json::<OpenAiModelList>()
+            .await
+            .with_context(|| format!("failed to parse {} models response", self.label))?;
+
+        Ok(sorted_models(
+            body.data.into_iter().map(|m| (m.id, m.context_length)),
+        ))
+    }
+}
+
+// ollama's native model listing
+#[derive(Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+/// ollama or llama.cpp server on this machine - chat goes through their openai-compatible endpoint
+pub struct LocalProvider {
+    kind: ProviderKind,
+    root_url: String,
+    chat: OpenAiCompatibleProvider,
+}
+
+#[async_trait]
+impl LlmProvider for LocalProvider {
+    fn name(&self) -> &str {
+        self.chat.name()
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.chat.complete(request).await
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        on_event: &mut StreamHandler<'_>,
+    ) -> Result<ChatResponse> {
+        self.chat.stream(request, on_event).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<AvailableModel>> {
+        if self.kind != ProviderKind::Ollama {
+            return self.chat.list_models().await;
+        }
+
+        let url = format!("{}/api/tags", self.root_url);
+        let response =
+            send_with_retries(self.name(), "", REMOTE_TIMEOUT, |client| client.get(&url))
+                .await
+                .context("is ollama running? start it with: ollama serve")?;
+
+        let body = response
+            .json::<OllamaTags>()
+            .await
+            .context("failed to parse ollama model list")?;
+
+        Ok(sorted_models(
+            body.models.into_iter().map(|m| (m.name, None)),
+        ))
+    }
+}
+
+/// turn raw model ids and their context windows into sorted picker entries
+fn sorted_models(ids: impl Iterator<Item = (String, Option<u32>)>) -> Vec<AvailableModel> {
+    let mut models: Vec<AvailableModel> = ids
+        .map(|(id, context_length)| AvailableModel {
+            description: id.clone(),
+            name: id,
+            context_length,
+            pricing: None,
+            structured_output: None,
+            params: GenerationParams::default(),
+        })
+        .collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    models
+}
+
+// anthropic messages api wire structures
+#[derive(Serialize)]
+struct AnthropicRequest<'a> {
+    model: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<&'a Message>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<&'a Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<AnthropicThinking>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(flatten)]
+    extra: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+// extended thinking, paid for out of max_tokens
+#[derive(Serialize)]
+struct AnthropicThinking {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    budget_tokens: u32,
+}
+
+impl AnthropicThinking {
+    fn for_effort(effort: ReasoningEffort) -> Self {
+        Self {
+            kind: "enabled",
+            budget_tokens: match effort {
+                ReasoningEffort::Low => 1024, // the smallest budget anthropic accepts
+                ReasoningEffort::Medium => 4096,
+                ReasoningEffort::High => 16384,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContentBlock>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize, Default)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<AnthropicUsage> for TokenUsage {
+    fn from(usage: AnthropicUsage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicContentBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    thinking: Option<String>,
+}
+
+// only the stream events we act on; pings and block bookkeeping are skipped
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicStreamEvent {
+    // input tokens are reported up front, output tokens with the closing delta
+    MessageStart {
+        message: AnthropicStreamMessage,
+    },
+    MessageDelta {
+        usage: AnthropicUsage,
+    },
+    ContentBlockDelta {
+        delta: AnthropicDelta,
+    },
+    MessageStop,
+    Error {
+        error: StreamError,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicStreamMessage {
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicDelta {
+    TextDelta {
+        text: String,
+    },
+    ThinkingDelta {
+        thinking: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// anthropic's native messages api
+pub struct AnthropicProvider {
+    base_url: String,
+    api_key: String,
+}
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let response = self.send(request, false).await?;
+
+        let body = response
+            .json::<AnthropicResponse>()
+            .await
+            .context("failed to parse anthropic api response")?;
+
+        let blocks = |kind: &str| {
+            body.content
+                .iter()
+                .filter(|block| block.block_type == kind)
+                .filter_map(|block| block.text.as_deref().or(block.thinking.as_deref()))
+                .collect::<Vec<_>>()
+                .join("")
+        };
+        ChatResponse::separate(
+            "anthropic",
+            &blocks("text"),
+            Some(blocks("thinking")),
+            body.usage.map(TokenUsage::from),
+        )
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatRequest,
+        on_event: &mut StreamHandler<'_>,
+    ) -> Result<ChatResponse> {
+        let response = self.send(request, true).await?;
+
+        let mut events = SseReader::new(response);
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut usage: Option<TokenUsage> = None;
+        while let Some(data) = events.next_data().await? {
+            let event: AnthropicStreamEvent =
+                serde_json::from_str(&data).context("failed to parse anthropic stream event")?;
+            let control = match event {
+                AnthropicStreamEvent::MessageStart { message } => {
+                    usage = Some(message.usage.into());
+                    StreamControl::Continue
+                }
+                AnthropicStreamEvent::MessageDelta { usage: delta } => {
+                    if let Some(usage) = usage.as_mut() {
+                        usage.completion_tokens = delta.output_tokens;
+                    }
+                    StreamControl::Continue
+                }
+                AnthropicStreamEvent::ContentBlockDelta {
+                    delta: AnthropicDelta::TextDelta { text },
+                } => {
+                    content.push_str(&text);
+                    on_event(StreamEvent::Content(&text))
+                }
+                AnthropicStreamEvent::ContentBlockDelta {
+                    delta: AnthropicDelta::ThinkingDelta { thinking },
+                } => {
+                    reasoning.push_str(&thinking);
+                    on_event(StreamEvent::Reasoning(&thinking))
+                }
+                AnthropicStreamEvent::Error { error } => {
+                    return Err(WizardError::Api(format!(
+                        "anthropic api error during stream: {}",
+                        error.message
+                    ))
+                    .into());
+                }
+                AnthropicStreamEvent::MessageStop => break,
+                _ => StreamControl::Continue,
+            };
+            if control == StreamControl::Stop {
+                break;
+            }
+        }
+
+        ChatResponse::separate("anthropic", &content, Some(reasoning), usage)
+    }
+
+    async fn list_models(&self) -> Result<Vec<AvailableModel>> {
+        let url = format!("{}/models?limit=1000", self.base_url);
+        let response = send_with_retries("anthropic", "", REMOTE_TIMEOUT, |client| {
+            client
+                .get(&url)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+        })
+        .await?;
+
+        let body = response
+            .json::<OpenAiModelList>()
+            .await
+            .context("failed to parse anthropic models response")?;
+
+        Ok(sorted_models(
+            body.data.into_iter().map(|m| (m.id, m.context_length)),
+        ))
+    }
+}
+
+impl AnthropicProvider {
+    /// translate the neutral request into a messages api call
+    async fn send(&self, request: &ChatRequest, stream: bool) -> Result<reqwest::Response> {
+        // anthropic takes the system prompt separately from the conversation
+        let system = request
+            .messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let thinking = request.reasoning_effort.map(AnthropicThinking::for_effort);
+        // the thinking budget comes out of max_tokens, so make sure the answer still fits
+        let max_tokens = request.max_tokens.unwrap_or(1024).max(
+            thinking
+                .as_ref()
+                .map_or(0, |thinking| thinking.budget_tokens + 1024),
+        );
+        let wire = AnthropicRequest {
+            model: &request.model,
+            system: if system.is_empty() {
+                None
+            } else {
+                Some(system)
+            },
+            messages: request
+                .messages
+                .iter()
+                .filter(|m| m.role != "system")
+                .collect(),
+            max_tokens,
+            // thinking rules out custom sampling, and anthropic rejects requests that set
+            // both temperature and top_p
+            temperature: request.temperature.filter(|_| thinking.is_none()),
+            top_p: if request.temperature.is_some() || thinking.is_some() {
+                None
+            } else {
+                request.top_p
+            },
+            stop_sequences: request.stop.as_ref(),
+            thinking,
+            stream,
+            extra: &request.extra,
+        };
+
+        let url = format!("{}/messages", self.base_url);
+        send_with_retries("anthropic", &request.model, REMOTE_TIMEOUT, |client| {
+            client
+                .post(&url)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .header("Content-Type", "application/json")
+                .json(&wire)
+        })
+        .await
+    }
+}
+
+/// incremental server-sent events reader yielding each `data:` payload
+struct SseReader {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl SseReader {
+    fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// next data payload, or none once the server closes the stream
+    async fn next_data(&mut self) -> Result<Option<String>> {
+        loop {
+            // split on whole lines only so multi-byte characters never straddle chunks
+            if let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(data) = line.trim_end().strip_prefix("data:") {
+                    return Ok(Some(data.trim_start().to_string()));
+                }
+                continue;
+            }
+
+            match self.response.chunk().await.context("stream interrupted")? {
+                Some(bytes) => self.buffer.extend_from_slice(&bytes),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// send a request with retries on network errors, server errors and rate limiting.
+/// `timeout` is the backend's default, which the `[http]` settings can override
+async fn send_with_retries<F>(
+    label: &str,
+    model: &str,
+    timeout: Duration,
+    build: F,
+) -> Result<reqwest::Response>
+where
+    F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+{
+    let settings = crate::http::settings();
+    let client = crate::http::client().map_err(|e| WizardError::Config(format!("{e:#}")))?;
+    let timeout = settings.timeout_or(timeout);
+    let max_attempts = settings.max_attempts();
+    let mut retry_delay = Duration::from_secs(1);
+
+    for attempt in 0..max_attempts {
+        match crate::http::send(build(&client).timeout(timeout)).await {
+            Ok(resp) => {
+                if resp.status().is_success() {
+                    return Ok(resp);
+                }
+
+                let status = resp.status();
+                let retry_after = crate::http::retry_after(&resp);
+                let error_text = resp
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "unknown error".to_string());
+
+                if status.is_server_error() || status == 429 {
+                    // a server asking for a longer pause than we're willing to sit through
+                    // is as good as a refusal
+                    let too_long =
+                        retry_after.is_some_and(|wait| wait > settings.max_retry_after());
+                    if attempt < max_attempts - 1 && !too_long {
+                        match retry_after {
+                            Some(wait) => {
+                                eprintln!(
+                                    "Retryable error ({status}): {error_text}. Server asked to retry in {wait:?}..."
+                                );
+                                pause(wait).await;
+                            }
+                            None => {
+                                eprintln!(
+                                    "Retryable error ({status}): {error_text}. Retrying in {retry_delay:?}..."
+                                );
+                                backoff(&mut retry_delay).await;
+                            }
+                        }
+                        continue;
+                    }
+                    let provider = label.to_string();
+                    let body = match retry_after {
+                        Some(wait) => format!("{error_text} (retry after {}s)", wait.as_secs()),
+                        None => error_text,
+                    };
+                    return Err(if status == 429 {
+                        WizardError::RateLimited { provider, body }
+                    } else {
+                        WizardError::ServiceUnavailable {
+                            provider,
+                            status: status.as_u16(),
+                            body,
+                        }
+                    }
+                    .into());
+                } else if status == 401 || status == 403 {
+                    return Err(WizardError::Auth(format!(
+                        "{label} rejected the api key ({status}): {error_text}"
+                    ))
+                    .into());
+                } else if !model.is_empty() && is_unknown_model(status, &error_text, model) {
+                    return Err(WizardError::InvalidModel {
+                        model: model.to_string(),
+                    }
+                    .into());
+                }
+
+                return Err(WizardError::Api(format!(
+                    "{label} api error ({status}): {error_text}"
+                ))
+                .into());
+            }
+            // missing fixtures and bad settings won't fix themselves; only retry the network
+            Err(e) if e.downcast_ref::<reqwest::Error>().is_none() => return Err(e),
+            Err(e) => {
+                if attempt < max_attempts - 1 {
+                    eprintln!("Network error: {e}. Retrying in {retry_delay:?}...");
+                    backoff(&mut retry_delay).await;
+                    continue;
+                } else if e
+                    .downcast_ref::<reqwest::Error>()
+                    .is_some_and(reqwest::Error::is_timeout)
+                {
+                    return Err(WizardError::Timeout {
+                        provider: label.to_string(),
+                    }
+                    .into());
+                } else {
+                    return Err(WizardError::Network(format!(
+                        "failed to connect to {label} api after {max_attempts} attempts: {e}"
+                    ))
+                    .into());
+                }
+            }
+        }
+    }
+
+    Err(WizardError::Network(format!(
+        "failed to complete api request after {max_attempts} attempts"
+    ))
+    .into())
+}
+
+/// whether an error response says the requested model doesn't exist, going by the
+/// provider's structured error rather than its wording, which can mention "model" for
+/// unrelated reasons (context length, parameters). a bare 404 isn't enough, since a
+/// mistyped base url or api path answers with one too
+fn is_unknown_model(status: reqwest::StatusCode, body: &str, model: &str) -> bool {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        return false;
+    };
+    let error = &json["error"];
+    // openai-style `code`, or a `type` naming the model specifically
+    let named_unknown = ["code", "type"].iter().any(|field| {
+        matches!(
+            error[field].as_str(),
+            Some("model_not_found" | "invalid_model")
+        )
+    });
+    // openai points `param` at the offending field
+    let model_param = error["param"].as_str() == Some("model");
+    // anthropic, ollama and openrouter answer with a 404 whose message names the model
+    let message = error["message"]
+        .as_str()
+        .or(error.as_str())
+        .unwrap_or_default();
+    let short_name = model.rsplit('/').next().unwrap_or(model);
+    let names_model = status == 404 && !short_name.is_empty() && message.contains(short_name);
+    named_unknown || model_param || names_model
+}
+
+/// wait before the next attempt, doubling the delay each time
+async fn backoff(delay: &mut Duration) {
+    pause(*delay).await;
+    *delay *= 2;
+}
+
+/// replayed fixtures answer instantly, so there's nothing to wait for
+async fn pause(wait: Duration) {
+    if !crate::http::is_replaying() {
+        sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn unknown_model_comes_from_the_structured_error() {
+        let openai = r#"{"error":{"message":"The model `gpt-9` does not exist","type":"invalid_request_error","param":null,"code":"model_not_found"}}"#;
+        let anthropic =
+            r#"{"type":"error","error":{"type":"not_found_error","message":"model: claude-x"}}"#;
+        let ollama = r#"{"error":"model \"llama9\" not found, try pulling it first"}"#;
+        let param = r#"{"error":{"message":"invalid value","type":"invalid_request_error","param":"model"}}"#;
+        assert!(is_unknown_model(StatusCode::NOT_FOUND, openai, "gpt-9"));
+        assert!(is_unknown_model(
+            StatusCode::NOT_FOUND,
+            anthropic,
+            "anthropic/claude-x"
+        ));
+        assert!(is_unknown_model(StatusCode::NOT_FOUND, ollama, "llama9"));
+        assert!(is_unknown_model(StatusCode::BAD_REQUEST, param, "gpt-9"));
+    }
+
+    #[test]
+    fn other_errors_mentioning_the_model_are_not_unknown_models() {
+        let context = r#"{"error":{"message":"This model's maximum context length is 8192 tokens","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#;
+        let parameter = r#"{"error":{"message":"temperature is not supported with gpt-9","type":"invalid_request_error","param":"temperature"}}"#;
+        assert!(!is_unknown_model(StatusCode::BAD_REQUEST, context, "gpt-9"));
+        assert!(!is_unknown_model(
+            StatusCode::BAD_REQUEST,
+            parameter,
+            "gpt-9"
+        ));
+        assert!(!is_unknown_model(
+            StatusCode::NOT_FOUND,
+            "<html>not found</html>",
+            "gpt-9"
+        ));
+    }
+
+    #[test]
+    fn a_wrong_url_is_not_an_unknown_model() {
+        // what a mistyped base url or api path gets back
+        let anthropic =
+            r#"{"type":"error","error":{"type":"not_found_error","message":"Not Found"}}"#;
+        let openai = r#"{"error":{"message":"Invalid URL (POST /v1/chat/completion)","type":"invalid_request_error","param":null,"code":null}}"#;
+        let generic = r#"{"error":"not found"}"#;
+        for body in [anthropic, openai, generic] {
+            assert!(
+                !is_unknown_model(StatusCode::NOT_FOUND, body, "gpt-9"),
+                "{body}"
+            );
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// reasoning module - keeps a reasoning model's thinking apart from its answer
+
+// tag pairs models wrap inline reasoning in
+const THINK_TAGS: [(&str, &str); 3] = [
+    ("<think>", "</think>"),
+    ("<thinking>", "</thinking>"),
+    ("<reasoning>", "</reasoning>"),
+];
+
+/// a completion split into what the model thought and what it answered
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeparatedOutput {
+    pub answer: String,
+    pub reasoning: Option<String>,
+}
+
+/// pull inline reasoning out of a completion, combining it with any reasoning the api
+/// reported separately. the answer never contains text from inside a thinking block
+pub fn separate_reasoning(content: &str, reported: Option<String>) -> SeparatedOutput {
+    let mut thoughts: Vec<String> = reported
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .into_iter()
+        .collect();
+    let mut answer = String::new();
+    let mut rest = content;
+
+    // some chat templates put the opening tag in the prompt, so the completion starts
+    // mid-thought and only the closing tag shows up
+    if let Some((close_at, close)) = orphaned_close(rest) {
+        thoughts.push(rest[..close_at].trim().to_string());
+        rest = &rest[close_at + close.len()..];
+    }
+
+    while let Some((open_at, open, close)) = next_open(rest) {
+        answer.push_str(&rest[..open_at]);
+        let inner = &rest[open_at + open.len()..];
+        match inner.find(close) {
+            Some(close_at) => {
+                thoughts.push(inner[..close_at].trim().to_string());
+                rest = &inner[close_at + close.len()..];
+            }
+            None => {
+                // cut off while still thinking: there's no answer after this
+                thoughts.push(inner.trim().to_string());
+                rest = "";
+            }
+        }
+    }
+    answer.push_str(rest);
+
+    thoughts.retain(|text| !text.is_empty());
+    SeparatedOutput {
+        answer: answer.trim().to_string(),
+        reasoning: (!thoughts.is_empty()).then(|| thoughts.join("\n\n")),
+    }
+}
+
+/// the earliest opening tag in `text`, with its position and closing tag
+fn next_open(text: &str) -> Option<(usize, &'static str, &'static str)> {
+    THINK_TAGS
+        .iter()
+        .filter_map(|&(open, close)| text.find(open).map(|at| (at, open, close)))
+        .min_by_key(|&(at, ..)| at)
+}
+
+/// a closing tag that comes before any opening tag
+fn orphaned_close(text: &str) -> Option<(usize, &'static str)> {
+    let first_open = next_open(text).map_or(text.len(), |(at, ..)| at);
+    THINK_TAGS
+        .iter()
+        .filter_map(|&(_, close)| text.find(close).map(|at| (at, close)))
+        .filter(|&(at, _)| at < first_open)
+        .min_by_key(|&(at, _)| at)
+}
+
+/// the text of a thinking block that is still open at the end of `text`, while streaming
+pub fn open_thought(text: &str) -> Option<&str> {
+    let (open_at, open, close) = THINK_TAGS
+        .iter()
+        .filter_map(|&(open, close)| text.rfind(open).map(|at| (at, open, close)))
+        .max_by_key(|&(at, ..)| at)?;
+    let thought = &text[open_at + open.len()..];
+    (!thought.contains(close)).then_some(thought)
+}
+
+/// where the answer can start: just past the last closing thinking tag seen so far
+pub fn answer_offset(text: &str) -> usize {
+    THINK_TAGS
+        .iter()
+        .filter_map(|&(_, close)| text.rfind(close).map(|at| at + close.len()))
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn separated(answer: &str, reasoning: Option<&str>) -> SeparatedOutput {
+        SeparatedOutput {
+            answer: answer.to_string(),
+            reasoning: reasoning.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn plain_answers_pass_through() {
+        assert_eq!(
+            separate_reasoning("  feat: add login\n", None),
+            separated("feat: add login", None)
+        );
+    }
+
+    #[test]
+    fn inline_blocks_are_taken_out_of_the_answer() {
+        assert_eq!(
+            separate_reasoning("<think>\nit adds a route\n</think>\nfeat: add login", None),
+            separated("feat: add login", Some("it adds a route"))
+        );
+        assert_eq!(
+            separate_reasoning("<thinking></thinking>fix: typo", None),
+            separated("fix: typo", None)
+        );
+    }
+
+    #[test]
+    fn an_orphaned_close_tag_ends_a_thought_begun_in_the_prompt() {
+        assert_eq!(
+            separate_reasoning(
+                "the diff renames a field</think>\n\nrefactor: rename id",
+                None
+            ),
+            separated("refactor: rename id", Some("the diff renames a field"))
+        );
+        // an orphaned close followed by a complete block
+        assert_eq!(
+            separate_reasoning("one</reasoning>fix: a<think>two</think>", None),
+            separated("fix: a", Some("one\n\ntwo"))
+        );
+    }
+
+    #[test]
+    fn an_unterminated_block_leaves_no_answer_behind_it() {
+        assert_eq!(
+            separate_reasoning("<think>still weighing feat against fix", None),
+            separated("", Some("still weighing feat against fix"))
+        );
+        assert_eq!(
+            separate_reasoning("fix: a\n<reasoning>but maybe", None),
+            separated("fix: a", Some("but maybe"))
+        );
+    }
+
+    #[test]
+    fn mixed_tag_kinds_are_each_closed_by_their_own_tag() {
+        assert_eq!(
+            separate_reasoning(
+                "<think>first</think>a <reasoning>second</reasoning>b <thinking>third</thinking>c",
+                None
+            ),
+            separated("a b c", Some("first\n\nsecond\n\nthird"))
+        );
+        // a different kind's close tag inside a block is just text
+        assert_eq!(
+            separate_reasoning("<think>x </reasoning> y</think>fix: z", None),
+            separated("fix: z", Some("x </reasoning> y"))
+        );
+    }
+
+    #[test]
+    fn reasoning_reported_by_the_api_comes_first() {
+        assert_eq!(
+            separate_reasoning("feat: add login", Some("  from the api\n".to_string())),
+            separated("feat: add login", Some("from the api"))
+        );
+        assert_eq!(
+            separate_reasoning("<think>inline</think>feat: a", Some("reported".to_string())),
+            separated("feat: a", Some("reported\n\ninline"))
+        );
+        assert_eq!(
+            separate_reasoning("feat: a", Some("   ".to_string())),
+            separated("feat: a", None)
+        );
+    }
+
+    #[test]
+    fn open_thought_follows_the_block_still_being_streamed() {
+        assert_eq!(open_thought("<think>partial"), Some("partial"));
+        assert_eq!(open_thought("<thinking>"), Some(""));
+        assert_eq!(open_thought("<think>done</think>"), None);
+        assert_eq!(
+            open_thought("<think>a</think>b<reasoning>next"),
+            Some("next")
+        );
+        assert_eq!(open_thought("<commit>feat: a"), None);
+    }
+
+    #[test]
+    fn the_answer_starts_after_the_last_close_tag() {
+        assert_eq!(answer_offset("<commit>feat"), 0);
+        let text = "<think>a</think><reasoning>b</reasoning><commit>";
+        assert_eq!(&text[answer_offset(text)..], "<commit>");
+        let text = "prompted thought</think>\n<commit>";
+        assert_eq!(&text[answer_offset(text)..], "\n<commit>");
+    }
+}
@@ -0,0 +1,186 @@
+// streaming module - renders a live preview of the commit message while the model writes it
+
+use anyhow::Result;
+use console::style;
+use indicatif::ProgressBar;
+use std::io::Write;
+
+use super::providers::{ChatRequest, ChatResponse, LlmProvider, StreamControl, StreamEvent};
+use super::reasoning::{answer_offset, open_thought};
+
+const OPEN_TAG: &str = "<commit>";
+const CLOSE_TAG: &str = "</commit>";
+
+// how much of the latest reasoning to show next to the spinner
+const THINKING_TAIL_CHARS: usize = 60;
+
+/// stream a completion, echoing the text inside `<commit>` tags as it arrives.
+/// the spinner shows the thinking phase and is cleared once the message starts.
+/// the stream is read to the end, since a closing `</think>` can still turn what
+/// looked like the message into reasoning
+pub async fn stream_with_preview(
+    provider: &dyn LlmProvider,
+    request: &ChatRequest,
+    spinner: &ProgressBar,
+) -> Result<ChatResponse> {
+    let mut preview = LivePreview::new(spinner);
+    let result = provider
+        .stream(request, &mut |event| preview.handle(event))
+        .await;
+    preview.finish();
+
+    // the model may have kept talking after the tag; keep only the message
+    let mut response = result?;
+    if let Some(end) = response.content.find(CLOSE_TAG) {
+        response.content.truncate(end + CLOSE_TAG.len());
+    }
+    Ok(response)
+}
+
+struct LivePreview<'a> {
+    spinner: &'a ProgressBar,
+    content: String,
+    thinking: String,
+    // byte offset into `content` where the message starts, once the open tag is seen
+    start: Option<usize>,
+    printed: usize,
+    done: bool,
+}
+
+impl<'a> LivePreview<'a> {
+    fn new(spinner: &'a ProgressBar) -> Self {
+        Self {
+            spinner,
+            content: String::new(),
+            thinking: String::new(),
+            start: None,
+            printed: 0,
+            done: false,
+        }
+    }
+
+    fn handle(&mut self, event: StreamEvent<'_>) -> StreamControl {
+        match event {
+            StreamEvent::Reasoning(text) => {
+                self.thinking.push_str(text);
+                if self.start.is_none() {
+                    self.spinner
+                        .set_message(format!("💭 thinking... {}", thinking_tail(&self.thinking)));
+                }
+                StreamControl::Continue
+            }
+            StreamEvent::Content(text) => {
+                self.content.push_str(text);
+                self.render()
+            }
+        }
+    }
+
+    /// print any newly completed message text, up to the close tag
+    fn render(&mut self) -> StreamControl {
+        // some local models think inline inside <think> tags rather than in a separate field
+        if let Some(thought) = open_thought(&self.content) {
+            if self.start.is_none() {
+                self.spinner
+                    .set_message(format!("💭 thinking... {}", thinking_tail(thought)));
+            }
+            return StreamControl::Continue;
+        }
+
+        let answer_from = answer_offset(&self.content);
+        if self.start.is_some_and(|start| start < answer_from) {
+            // the chat template swallowed the opening tag, so the model was still thinking
+            println!(
+                "\n{}",
+                style("💭 that was the model thinking - waiting for the message...").dim()
+            );
+            self.start = None;
+            self.done = false;
+        }
+        if self.done {
+            return StreamControl::Continue;
+        }
+
+        if self.start.is_none() {
+            match self.content[answer_from..].find(OPEN_TAG) {
+                Some(pos) => {
+                    self.spinner.finish_and_clear();
+                    println!("{}", style("✍️  writing commit message:").dim());
+                    let start = answer_from + pos + OPEN_TAG.len();
+                    self.start = Some(start);
+                    self.printed = start;
+                }
+                None => {
+                    self.spinner.set_message("✍️  writing commit message...");
+                    return StreamControl::Continue;
+                }
+            }
+        }
+
+        let (end, done) = match self.content[self.printed..].find(CLOSE_TAG) {
+            Some(pos) => (self.printed + pos, true),
+            // hold back anything that could be the start of the close tag
+            None => (
+                self.content.len() - partial_tag_suffix(&self.content),
+                false,
+            ),
+        };
+
+        let fresh = &self.content[self.printed..end];
+        // skip the newline that usually follows the open tag
+        let fresh = if Some(self.printed) == self.start {
+            fresh.trim_start()
+        } else {
+            fresh
+        };
+        if !fresh.is_empty() {
+            print!("{}", style(fresh).yellow().dim());
+            let _ = std::io::stdout().flush();
+        }
+        self.printed = end;
+
+        if done {
+            self.done = true;
+            println!("\n");
+        }
+        StreamControl::Continue
+    }
+
+    fn finish(&mut self) {
+        // flush whatever was held back if the stream ended without a close tag
+        if self.start.is_some() && !self.done {
+            let rest = self.content[self.printed..].trim_end();
+            if !rest.is_empty() {
+                print!("{}", style(rest).yellow().dim());
+            }
+            println!("\n");
+        }
+    }
+}
+
+/// length of the longest suffix of `text` that is a proper prefix of the close tag
+fn partial_tag_suffix(text: &str) -> usize {
+    (1..CLOSE_TAG.len())
+        .rev()
+        .find(|&len| text.ends_with(&CLOSE_TAG[..len]))
+        .unwrap_or(0)
+}
+
+/// last few characters of the reasoning, flattened onto one line
+fn thinking_tail(thinking: &str) -> String {
+    // only look at the recent end so long reasoning doesn't get rescanned on every token
+    let recent = thinking
+        .char_indices()
+        .rev()
+        .nth(THINKING_TAIL_CHARS * 4)
+        .map(|(i, _)| &thinking[i..])
+        .unwrap_or(thinking);
+    let flat: String = recent.split_whitespace().collect::<Vec<_>>().join(" ");
+    let count = flat.chars().count();
+    if count <= THINKING_TAIL_CHARS {
+        flat
+    } else {
+        let tail: String = flat.chars().skip(count - THINKING_TAIL_CHARS).collect();
+        format!("…{tail}")
+    }
+}
@@ -0,0 +1,260 @@
+// structured output module - asks capable models for the commit as json and renders it
+
+use crate::{Config, ProviderKind};
+use serde::Deserialize;
+use serde_json::json;
+
+const COMMIT_TYPES: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+/// how the model is asked to shape its answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// free text wrapped in `<commit>` tags - works with any model
+    #[default]
+    Tagged,
+    /// a json object constrained by a schema through `response_format`
+    Json,
+}
+
+impl OutputFormat {
+    /// json for models known to honour a response schema, tags for everything else
+    pub fn for_model(config: &Config, model: &str) -> Self {
+        // anthropic's messages api has no response_format
+        let provider = config.active_provider().unwrap_or_default();
+        if provider.provider != ProviderKind::Anthropic && config.supports_structured_output(model)
+        {
+            Self::Json
+        } else {
+            Self::Tagged
+        }
+    }
+
+    /// closing line of the system prompt
+    pub fn system_instruction(self) -> &'static str {
+        match self {
+            Self::Tagged => "only output the commit inside <commit>...</commit> tags, no extra commentary.",
+            Self::Json => "only output the commit as a json object matching the given schema, no extra commentary.",
+        }
+    }
+
+    /// how the answer should be delivered, stated at the end of the prompt
+    pub fn prompt_instruction(self) -> &'static str {
+        match self {
+            Self::Tagged => "output must be wrapped exactly once in <commit>...</commit> tags with no extra commentary.\n",
+            Self::Json => "output a single json object with the fields type, scope (null when there is none), description, body (one string per bullet point, empty for single-line commits), breaking and footers.\n",
+        }
+    }
+
+    /// `response_format` for the request, if any
+    pub fn response_format(self) -> Option<serde_json::Value> {
+        match self {
+            Self::Tagged => None,
+            Self::Json => Some(json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "conventional_commit",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": {
+                            "type": { "type": "string", "enum": COMMIT_TYPES },
+                            "scope": { "type": ["string", "null"] },
+                            "description": { "type": "string" },
+                            "body": { "type": "array", "items": { "type": "string" } },
+                            "breaking": { "type": "boolean" },
+                            "footers": { "type": "array", "items": { "type": "string" } }
+                        },
+                        "required": ["type", "scope", "description", "body", "breaking", "footers"],
+                        "additionalProperties": false
+                    }
+                }
+            })),
+        }
+    }
+
+    /// stop sequences that end the answer early
+    pub fn stop(self) -> Option<Vec<String>> {
+        match self {
+            Self::Tagged => Some(vec!["</commit>".to_string()]),
+            Self::Json => None,
+        }
+    }
+}
+
+/// a conventional commit as returned by a model in json mode
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StructuredCommit {
+    #[serde(rename = "type")]
+    pub commit_type: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    pub description: String,
+    #[serde(default)]
+    pub body: Vec<String>,
+    #[serde(default)]
+    pub breaking: bool,
+    #[serde(default)]
+    pub footers: Vec<String>,
+}
+
+impl StructuredCommit {
+    /// render as a commit message: header, bullet body, then footers
+    pub fn render(&self) -> String {
+        let scope = self
+            .scope
+            .as_deref()
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| format!("({scope})"))
+            .unwrap_or_default();
+        let mut message = format!(
+            "{}{scope}{}: {}",
+            self.commit_type.trim(),
+            if self.breaking { "!" } else { "" },
+            self.description.trim().trim_end_matches('.')
+        );
+
+        let bullets: Vec<String> = self
+            .body
+            .iter()
+            .map(|line| line.trim().trim_start_matches(['-', '*']).trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("- {line}"))
+            .collect();
+        if !bullets.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&bullets.join("\n"));
+        }
+
+        let footers: Vec<&str> = self
+            .footers
+            .iter()
+            .map(|footer| footer.trim())
+            .filter(|footer| !footer.is_empty())
+            .collect();
+        if !footers.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&footers.join("\n"));
+        }
+
+        message
+    }
+}
+
+/// find and parse the json commit in an answer, tolerating code fences or chatter around it
+pub fn parse_structured_commit(answer: &str) -> Option<StructuredCommit> {
+    let start = answer.find('{')?;
+    let end = answer.rfind('}')?;
+    if end < start {
+        return None;
+    }
+
+    serde_json::from_str::<StructuredCommit>(&answer[start..=end])
+        .ok()
+        .filter(|commit| !commit.commit_type.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(scope: Option<&str>, breaking: bool) -> StructuredCommit {
+        StructuredCommit {
+            commit_type: "feat".to_string(),
+            scope: scope.map(str::to_string),
+            description: "add retry backoff".to_string(),
+            body: Vec::new(),
+            breaking,
+            footers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_a_bare_answer_and_fills_in_defaults() {
+        let parsed = parse_structured_commit(r#"{"type":"fix","description":"handle empty diff"}"#)
+            .expect("valid commit");
+        assert_eq!(parsed.commit_type, "fix");
+        assert_eq!(parsed.description, "handle empty diff");
+        assert_eq!(parsed.scope, None);
+        assert!(parsed.body.is_empty() && parsed.footers.is_empty() && !parsed.breaking);
+    }
+
+    #[test]
+    fn parses_through_code_fences_and_chatter() {
+        let answer = "sure, here it is:\n```json\n{\"type\": \"docs\", \"scope\": \"readme\", \"description\": \"explain {braces}\"}\n```\nhope that helps";
+        let parsed = parse_structured_commit(answer).expect("fenced commit");
+        assert_eq!(parsed.scope.as_deref(), Some("readme"));
+        assert_eq!(parsed.description, "explain {braces}");
+    }
+
+    #[test]
+    fn rejects_answers_that_are_not_a_usable_commit() {
+        assert_eq!(parse_structured_commit("<commit>feat: tags</commit>"), None);
+        assert_eq!(parse_structured_commit("} backwards {"), None);
+        assert_eq!(
+            parse_structured_commit(r#"{"type": "feat", "description": }"#),
+            None
+        );
+        assert_eq!(
+            parse_structured_commit(r#"{"description": "no type"}"#),
+            None
+        );
+        assert_eq!(
+            parse_structured_commit(r#"{"type": "  ", "description": "blank type"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn renders_the_header_with_scope_and_breaking_marker() {
+        assert_eq!(commit(None, false).render(), "feat: add retry backoff");
+        assert_eq!(
+            commit(Some(" http "), false).render(),
+            "feat(http): add retry backoff"
+        );
+        assert_eq!(
+            commit(Some("  "), true).render(),
+            "feat!: add retry backoff"
+        );
+        assert_eq!(
+            commit(Some("http"), true).render(),
+            "feat(http)!: add retry backoff"
+        );
+    }
+
+    #[test]
+    fn renders_a_tidied_description_body_and_footers() {
+        let rendered = StructuredCommit {
+            commit_type: " refactor ".to_string(),
+            description: " split the parser. ".to_string(),
+            body: vec![
+                "- move lexing out".to_string(),
+                "* keep the public api".to_string(),
+                "   ".to_string(),
+                "drop the old helper".to_string(),
+            ],
+            footers: vec!["Refs: #12".to_string(), " ".to_string()],
+            ..commit(None, false)
+        }
+        .render();
+        assert_eq!(
+            rendered,
+            "refactor: split the parser\n\n- move lexing out\n- keep the public api\n- drop the old helper\n\nRefs: #12"
+        );
+    }
+
+    #[test]
+    fn footers_follow_the_header_when_there_is_no_body() {
+        let rendered = StructuredCommit {
+            footers: vec!["BREAKING CHANGE: retries are on by default".to_string()],
+            ..commit(None, true)
+        }
+        .render();
+        assert_eq!(
+            rendered,
+            "feat!: add retry backoff\n\nBREAKING CHANGE: retries are on by default"
+        );
+    }
+}
@@ -0,0 +1,43 @@
+// token estimation module - rough token counts for fitting prompts into a model's context window
+
+/// context window assumed when nothing better is known
+pub const DEFAULT_CONTEXT_TOKENS: usize = 32_768;
+
+/// estimate how many tokens a model will see for this text.
+/// bpe tokenisers average roughly four characters per token for code and latin text,
+/// while other scripts (and emoji) tend to cost at least a token per character
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    ascii.div_ceil(4) + other
+}
+
+/// tokens available for input once the response and a safety margin are reserved
+pub fn prompt_budget(context_length: usize, max_output_tokens: usize) -> usize {
+    // the estimate is only approximate, so keep a tenth of the window spare
+    let margin = context_length / 10;
+    context_length.saturating_sub(max_output_tokens + margin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_budget_reserves_the_response_and_a_tenth_of_the_window() {
+        assert_eq!(prompt_budget(32_768, 1_000), 32_768 - 1_000 - 3_276);
+        assert_eq!(prompt_budget(1_000, 2_000), 0);
+    }
+
+    #[test]
+    fn tokens_are_estimated_per_four_ascii_characters() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(estimate_tokens("ab→"), 2);
+    }
+}
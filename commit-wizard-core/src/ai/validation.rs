@@ -1,5 +1,6 @@
 // validation and message processing module
 
+use crate::error::{ValidationRule, WizardError};
 use anyhow::Result;
 
 /// extract commit message from ai response
@@ -264,12 +265,12 @@ pub fn fix_commit_format(msg: &str) -> Result<String> {
         Ok(()) => Ok(normalized),
         Err(e) => {
             // if it's still invalid, try one more fix for the specific error
-            if e.to_string().contains("invalid scope") && e.to_string().contains("ai, napi") {
+            if e.rule() == Some(ValidationRule::InvalidScope) && msg.contains(", ") {
                 // this is our specific case - scope has spaces after commas
                 let fixed = normalize_commit_format(msg);
                 Ok(fixed)
             } else {
-                Err(e)
+                Err(e.into())
             }
         }
     }
@@ -311,10 +312,24 @@ pub fn post_process_commit_message(msg: &str) -> String {
 }
 
 /// validate that the generated commit message follows conventional commits format
-pub fn validate_commit_message(msg: &str) -> Result<()> {
+pub fn validate_commit_message(msg: &str) -> Result<(), WizardError> {
+    match commit_message_violations(msg).into_iter().next() {
+        Some(violation) => Err(violation),
+        None => Ok(()),
+    }
+}
+
+/// every conventional commits rule a message breaks, in the order they're checked.
+/// a header that can't be parsed stops the checks that depend on it
+pub fn commit_message_violations(msg: &str) -> Vec<WizardError> {
+    let mut violations = Vec::new();
     let lines: Vec<&str> = msg.lines().collect();
     if lines.is_empty() {
-        return Err(anyhow::anyhow!("commit message is empty"));
+        violations.push(WizardError::validation(
+            ValidationRule::EmptyMessage,
+            "commit message is empty",
+        ));
+        return violations;
     }
 
     let first_line = lines[0];
@@ -324,25 +339,33 @@ pub fn validate_commit_message(msg: &str) -> Result<()> {
         "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore",
         "revert",
     ];
+    let check_type = |type_part: &str, violations: &mut Vec<WizardError>| {
+        if !valid_types.contains(&type_part) {
+            violations.push(WizardError::validation(
+                ValidationRule::InvalidType,
+                format!(
+                    "invalid type '{}', must be one of: {}",
+                    type_part,
+                    valid_types.join(", ")
+                ),
+            ));
+        }
+    };
 
     let has_scope = first_line.contains('(') && first_line.contains(')');
     if has_scope {
         // format: type(scope): description or type(scope)!: description
         let parts: Vec<&str> = first_line.splitn(2, '(').collect();
         if parts.len() != 2 {
-            return Err(anyhow::anyhow!(
-                "invalid format: missing opening parenthesis"
+            violations.push(WizardError::validation(
+                ValidationRule::InvalidFormat,
+                "invalid format: missing opening parenthesis",
             ));
+            return violations;
         }
 
         let type_part = parts[0].trim_end_matches('!'); // handle type! syntax
-        if !valid_types.contains(&type_part) {
-            return Err(anyhow::anyhow!(
-                "invalid type '{}', must be one of: {}",
-                type_part,
-                valid_types.join(", ")
-            ));
-        }
+        check_type(type_part, &mut violations);
 
         let rest = parts[1];
         // handle both "): " and ")!: " patterns
@@ -353,7 +376,8 @@ pub fn validate_commit_message(msg: &str) -> Result<()> {
         };
 
         if scope_desc.len() != 2 {
-            return Err(anyhow::anyhow!("invalid format: expected 'type(scope): description' or 'type(scope)!: description'"));
+            violations.push(WizardError::validation(ValidationRule::InvalidFormat, "invalid format: expected 'type(scope): description' or 'type(scope)!: description'"));
+            return violations;
         }
 
         let scope = scope_desc[0];
@@ -363,14 +387,12 @@ pub fn validate_commit_message(msg: &str) -> Result<()> {
                     c.is_alphanumeric() || c == '-' || c == '_' || c == ',' || c == '.' || c == '/'
                 }))
         {
-            return Err(anyhow::anyhow!(
-                "invalid scope '{}', must be a noun (alphanumeric, hyphens, underscores, commas, dots, or forward slashes only)",
-                scope
-            ));
+            violations.push(WizardError::validation(ValidationRule::InvalidScope, format!("invalid scope '{}', must be a noun (alphanumeric, hyphens, underscores, commas, dots, or forward slashes only)",
+                scope)));
         }
 
         let description = scope_desc[1];
-        validate_description(description)?;
+        description_violations(description, &mut violations);
     } else {
         // format: type: description or type!: description
         let parts: Vec<&str> = if first_line.contains("!: ") {
@@ -380,46 +402,52 @@ pub fn validate_commit_message(msg: &str) -> Result<()> {
         };
 
         if parts.len() != 2 {
-            return Err(anyhow::anyhow!("invalid format: expected 'type: description', 'type!: description', or 'type(scope): description'"));
+            violations.push(WizardError::validation(ValidationRule::InvalidFormat, "invalid format: expected 'type: description', 'type!: description', or 'type(scope): description'"));
+            return violations;
         }
 
         let type_part = parts[0].trim_end_matches('!'); // handle type! syntax
-        if !valid_types.contains(&type_part) {
-            return Err(anyhow::anyhow!(
-                "invalid type '{}', must be one of: {}",
-                type_part,
-                valid_types.join(", ")
-            ));
-        }
+        check_type(type_part, &mut violations);
 
         let description = parts[1];
-        validate_description(description)?;
+        description_violations(description, &mut violations);
     }
 
-    Ok(())
+    violations
 }
 
-/// validate the description part of the commit message
-fn validate_description(description: &str) -> Result<()> {
+/// check the description part of the commit message
+fn description_violations(description: &str, violations: &mut Vec<WizardError>) {
     if description.is_empty() {
-        return Err(anyhow::anyhow!("description cannot be empty"));
+        violations.push(WizardError::validation(
+            ValidationRule::EmptyDescription,
+            "description cannot be empty",
+        ));
+        return;
     }
 
     if description.len() > 72 {
-        return Err(anyhow::anyhow!(
-            "description too long ({} chars), must be ≤72 characters",
-            description.len()
+        violations.push(WizardError::validation(
+            ValidationRule::DescriptionTooLong,
+            format!(
+                "description too long ({} chars), must be ≤72 characters",
+                description.len()
+            ),
         ));
     }
 
     if description.ends_with('.') {
-        return Err(anyhow::anyhow!("description should not end with a period"));
+        violations.push(WizardError::validation(
+            ValidationRule::TrailingPeriod,
+            "description should not end with a period",
+        ));
     }
 
     let first_char = description.chars().next().unwrap_or(' ');
     if first_char.is_uppercase() {
-        return Err(anyhow::anyhow!(
-            "description should start with lowercase letter"
+        violations.push(WizardError::validation(
+            ValidationRule::CapitalisedDescription,
+            "description should start with lowercase letter",
         ));
     }
 
@@ -457,10 +485,13 @@ fn validate_description(description: &str) -> Result<()> {
 
     // allow up to 2 vague words before failing
     if vague_count > 2 {
-        return Err(anyhow::anyhow!(
-            "description too vague - contains {} vague words ({}), try to be more specific",
-            vague_count,
-            found_vague_words.join(", ")
+        violations.push(WizardError::validation(
+            ValidationRule::VagueDescription,
+            format!(
+                "description too vague - contains {} vague words ({}), try to be more specific",
+                vague_count,
+                found_vague_words.join(", ")
+            ),
         ));
     }
 
@@ -483,14 +514,13 @@ fn validate_description(description: &str) -> Result<()> {
                 "modifying",
             ];
             if non_imperative.contains(first_word) {
-                return Err(anyhow::anyhow!(
-                    "description should use imperative mood (e.g., 'add' not 'added' or 'adding')"
+                violations.push(WizardError::validation(
+                    ValidationRule::NotImperative,
+                    "description should use imperative mood (e.g., 'add' not 'added' or 'adding')",
                 ));
             }
         }
     }
-
-    Ok(())
 }
 
 /// intelligently shorten a commit description to fit within 72 characters
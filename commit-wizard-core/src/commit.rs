@@ -0,0 +1,775 @@
+// commit module - writes commits with git2, running hooks and signing the way `git commit` does
+
+use crate::error::WizardError;
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use git2::{Commit, Oid, Repository, Signature, Time};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// `[commit]` defaults, overridden per run by the matching flags
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign: Option<bool>, // unset follows git's commit.gpgsign
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub signoff: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_verify: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protected_branches: Option<Vec<String>>, // reword won't touch commits already on these upstream branches
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub abort_on_secrets: bool, // refuse to commit when the staged changes look like they hold credentials
+}
+
+impl CommitConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// upstream branches whose history is never rewritten, main and master unless configured
+    pub fn protected_branches(&self) -> Vec<String> {
+        self.protected_branches
+            .clone()
+            .unwrap_or_else(|| vec!["main".to_string(), "master".to_string()])
+    }
+}
+
+/// how a single commit should be written
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    pub sign: Option<bool>, // none follows commit.gpgsign
+    pub no_verify: bool,    // skip the pre-commit and commit-msg hooks
+    pub signoff: bool,
+    pub author: Option<String>, // "name <email>"
+    pub date: Option<String>,   // author date
+    pub amend: bool,            // replace HEAD instead of adding a child
+}
+
+/// the commit that was written
+#[derive(Debug, Clone)]
+pub struct CommitOutcome {
+    pub id: Oid,
+    pub branch: Option<String>, // none on a detached HEAD
+    pub summary: String,
+}
+
+impl CommitOutcome {
+    /// `[main 1a2b3c4] subject`, as git prints it
+    pub fn describe(&self) -> String {
+        let id = self.id.to_string();
+        format!(
+            "[{} {}] {}",
+            self.branch.as_deref().unwrap_or("detached HEAD"),
+            &id[..7],
+            self.summary
+        )
+    }
+}
+
+/// commit the index with `message` in the repository containing `repo_path`
+pub fn create_commit(
+    repo_path: &str,
+    message: &str,
+    options: &CommitOptions,
+) -> Result<CommitOutcome> {
+    let repo = Repository::discover(repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?;
+
+    if !options.no_verify {
+        run_hook(&repo, "pre-commit", &[])?;
+    }
+
+    // hooks may rewrite the message, so it goes through the same file git uses
+    let mut message = message.trim_end().to_string();
+    if options.signoff {
+        message = add_signoff(&message, &repo.signature().map_err(identity_error)?);
+    }
+    let message_file = repo.path().join("COMMIT_EDITMSG");
+    fs::write(&message_file, format!("{message}\n"))
+        .with_context(|| format!("failed to write {}", message_file.display()))?;
+    // the message is always supplied, so this is a "message" source even when amending,
+    // as with git commit --amend -m
+    run_hook(
+        &repo,
+        "prepare-commit-msg",
+        &[message_file.as_os_str(), "message".as_ref()],
+    )?;
+    if !options.no_verify {
+        run_hook(&repo, "commit-msg", &[message_file.as_os_str()])?;
+    }
+    let message = read_message(&message_file, Cleanup::from_config(&repo))?;
+
+    // read the index after the hooks, which may have staged more changes
+    let mut index = repo.index().map_err(git_error)?;
+    index.read(false).map_err(git_error)?;
+    if index.has_conflicts() {
+        return Err(WizardError::Git(
+            "the index has unresolved conflicts; resolve them before committing".to_string(),
+        )
+        .into());
+    }
+    let tree = repo
+        .find_tree(index.write_tree().map_err(git_error)?)
+        .map_err(git_error)?;
+
+    let head = head_commit(&repo)?;
+    let parents: Vec<Commit> = match (&head, options.amend) {
+        (Some(head), true) => head.parents().collect(),
+        (Some(head), false) => vec![head.clone()],
+        (None, true) => {
+            return Err(WizardError::Git("there is no commit to amend yet".to_string()).into());
+        }
+        (None, false) => Vec::new(),
+    };
+    if !options.amend && parents.first().map(|p| p.tree_id()) == Some(tree.id()) {
+        return Err(WizardError::Git(
+            "nothing to commit: stage your changes with git add first".to_string(),
+        )
+        .into());
+    }
+
+    let committer = repo.signature().map_err(identity_error)?;
+    let author = author_signature(options, &committer, head.as_ref().filter(|_| options.amend))?;
+    let parent_refs: Vec<&Commit> = parents.iter().collect();
+
+    let id = write_commit(
+        &repo,
+        &author,
+        &committer,
+        &message,
+        &tree,
+        &parent_refs,
+        options.sign,
+    )?;
+
+    let summary = message.lines().next().unwrap_or_default().to_string();
+    let action = match (options.amend, parents.is_empty()) {
+        (true, _) => "commit (amend)",
+        (false, true) => "commit (initial)",
+        (false, false) => "commit",
+    };
+    let branch = move_head(&repo, id, &format!("{action}: {summary}"))?;
+
+    // like git, a failing post-commit hook doesn't undo the commit
+    if let Err(e) = run_hook(&repo, "post-commit", &[]) {
+        eprintln!("⚠️  {e:#}");
+    }
+
+    Ok(CommitOutcome {
+        id,
+        branch,
+        summary,
+    })
+}
+
+/// write a commit object without moving any refs, signing it if asked to or if
+/// commit.gpgsign is set
+pub(crate) fn write_commit(
+    repo: &Repository,
+    author: &Signature<'_>,
+    committer: &Signature<'_>,
+    message: &str,
+    tree: &git2::Tree<'_>,
+    parents: &[&Commit<'_>],
+    sign: Option<bool>,
+) -> Result<Oid> {
+    let git_config = repo.config().map_err(git_error)?;
+    let sign = sign.unwrap_or_else(|| git_config.get_bool("commit.gpgsign").unwrap_or(false));
+    if !sign {
+        return Ok(repo
+            .commit(None, author, committer, message, tree, parents)
+            .map_err(git_error)?);
+    }
+
+    let buffer = repo
+        .commit_create_buffer(author, committer, message, tree, parents)
+        .map_err(git_error)?;
+    let buffer = std::str::from_utf8(&buffer).context("commit buffer is not valid utf-8")?;
+    let signature = sign_buffer(repo, &git_config, committer, buffer)?;
+    Ok(repo
+        .commit_signed(buffer, &signature, None)
+        .map_err(git_error)?)
+}
+
+pub(crate) fn git_error(error: git2::Error) -> WizardError {
+    WizardError::Git(error.message().to_string())
+}
+
+pub(crate) fn identity_error(error: git2::Error) -> WizardError {
+    WizardError::Git(format!(
+        "git doesn't know who you are ({}). set it with: git config --global user.name \"your name\" && git config --global user.email you@example.com",
+        error.message()
+    ))
+}
+
+fn head_commit(repo: &Repository) -> Result<Option<Commit<'_>>> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit().map_err(git_error)?)),
+        // a fresh repository has no HEAD commit yet
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(git_error(e).into()),
+    }
+}
+
+/// point the current branch (or a detached HEAD) at the new commit. returns the branch name
+pub(crate) fn move_head(repo: &Repository, id: Oid, reflog: &str) -> Result<Option<String>> {
+    let head = repo.find_reference("HEAD").map_err(git_error)?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, id, true, reflog)
+                .map_err(git_error)?;
+            Ok(Some(
+                branch
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(branch)
+                    .to_string(),
+            ))
+        }
+        None => {
+            repo.set_head_detached(id).map_err(git_error)?;
+            Ok(None)
+        }
+    }
+}
+
+/// the author: --author if given, else the amended commit's author, else the committer,
+/// with --date applied on top
+fn author_signature(
+    options: &CommitOptions,
+    committer: &Signature<'_>,
+    amended: Option<&Commit<'_>>,
+) -> Result<Signature<'static>> {
+    let (name, email, time) = match (&options.author, amended) {
+        (Some(author), _) => {
+            let (name, email) = parse_author(author)?;
+            (name, email, committer.when())
+        }
+        (None, Some(commit)) => {
+            let author = commit.author();
+            (
+                author.name().unwrap_or_default().to_string(),
+                author.email().unwrap_or_default().to_string(),
+                author.when(),
+            )
+        }
+        (None, None) => (
+            committer.name().unwrap_or_default().to_string(),
+            committer.email().unwrap_or_default().to_string(),
+            committer.when(),
+        ),
+    };
+    let time = match &options.date {
+        Some(date) => parse_date(date)?,
+        None => time,
+    };
+
+    Signature::new(&name, &email, &time)
+        .map_err(|e| WizardError::Config(format!("invalid author: {}", e.message())).into())
+}
+
+/// "name <email>"
+fn parse_author(author: &str) -> Result<(String, String)> {
+    let invalid = || {
+        WizardError::Config(format!(
+            "invalid --author '{author}' (expected \"name <email>\")"
+        ))
+    };
+    let (name, rest) = author.split_once('<').ok_or_else(invalid)?;
+    let email = rest.strip_suffix('>').ok_or_else(invalid)?;
+    if name.trim().is_empty() || email.trim().is_empty() {
+        return Err(invalid().into());
+    }
+    Ok((name.trim().to_string(), email.trim().to_string()))
+}
+
+/// unix timestamps (optionally `@`-prefixed), rfc 2822, iso 8601 and plain
+/// `yyyy-mm-dd[ hh:mm[:ss]]` in local time
+fn parse_date(date: &str) -> Result<Time> {
+    let date = date.trim();
+    let parsed: Option<DateTime<FixedOffset>> = date
+        .strip_prefix('@')
+        .unwrap_or(date)
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|utc| utc.fixed_offset())
+        .or_else(|| DateTime::parse_from_rfc2822(date).ok())
+        .or_else(|| DateTime::parse_from_rfc3339(date).ok())
+        .or_else(|| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z").ok())
+        .or_else(|| {
+            ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|day| day.and_hms_opt(0, 0, 0))
+                })
+                .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+                .map(|local| local.fixed_offset())
+        });
+
+    let parsed = parsed.ok_or_else(|| {
+        WizardError::Config(format!(
+            "invalid --date '{date}' (try 2024-05-01 14:30, an rfc 2822 or iso 8601 date, or a unix timestamp)"
+        ))
+    })?;
+    Ok(Time::new(
+        parsed.timestamp(),
+        parsed.offset().local_minus_utc() / 60,
+    ))
+}
+
+/// append a Signed-off-by trailer unless the last trailer already is this one
+fn add_signoff(message: &str, who: &Signature<'_>) -> String {
+    let trailer = format!(
+        "Signed-off-by: {} <{}>",
+        who.name().unwrap_or_default(),
+        who.email().unwrap_or_default()
+    );
+    if message.lines().last() == Some(trailer.as_str()) {
+        return message.to_string();
+    }
+    // join an existing trailer block rather than starting a new paragraph
+    let in_trailers = message.lines().last().is_some_and(|line| {
+        line.starts_with("Signed-off-by:") || line.starts_with("Co-authored-by:")
+    });
+    let separator = if in_trailers { "\n" } else { "\n\n" };
+    format!("{message}{separator}{trailer}")
+}
+
+/// `core.commentChar`, '#' when it isn't set, or `None` for `auto`, where git picks a
+/// character the message doesn't start a line with
+pub(crate) fn comment_char(repo: &Repository) -> Option<char> {
+    match repo.config().and_then(|c| c.get_string("core.commentChar")) {
+        Ok(value) if value == "auto" => None,
+        Ok(value) => Some(value.chars().next().unwrap_or('#')),
+        Err(_) => Some('#'),
+    }
+}
+
+/// how git tidies a message before committing, from `commit.cleanup`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cleanup {
+    Strip(char), // whitespace, plus lines starting with the comment character
+    Whitespace,  // trailing whitespace and surplus blank lines only
+    Verbatim,
+}
+
+impl Cleanup {
+    /// the mode git uses for a message that isn't edited, as with `git commit -m`: `default`
+    /// and `scissors` only strip comments when git opened the editor itself
+    fn from_config(repo: &Repository) -> Self {
+        let config = repo.config().ok();
+        let get = |name: &str| config.as_ref().and_then(|c| c.get_string(name).ok());
+        match get("commit.cleanup").as_deref() {
+            // `auto` picks a character the message doesn't use, and '#' is git's first choice
+            Some("strip") => Self::Strip(comment_char(repo).unwrap_or('#')),
+            Some("verbatim") => Self::Verbatim,
+            _ => Self::Whitespace,
+        }
+    }
+
+    fn apply(self, content: &str) -> String {
+        if self == Self::Verbatim {
+            return content.to_string();
+        }
+        let mut lines: Vec<&str> = Vec::new();
+        for line in content.lines() {
+            if matches!(self, Self::Strip(comment) if line.starts_with(comment)) {
+                continue;
+            }
+            let line = line.trim_end();
+            // collapse runs of blank lines, and drop leading ones
+            if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+                continue;
+            }
+            lines.push(line);
+        }
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+}
+
+/// the message as the hooks left it, cleaned up the way `commit.cleanup` says
+fn read_message(path: &Path, cleanup: Cleanup) -> Result<String> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let message = cleanup.apply(&content);
+    if message.trim().is_empty() {
+        return Err(
+            WizardError::Git("aborting commit due to empty commit message".to_string()).into(),
+        );
+    }
+    Ok(message)
+}
+
+/// run a hook if the repository has an executable one, failing with its output if it rejects
+fn run_hook(repo: &Repository, name: &str, args: &[&std::ffi::OsStr]) -> Result<()> {
+    let Some(hook) = hook_path(repo, name) else {
+        return Ok(());
+    };
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+
+    // hooks are usually shell scripts, which windows can't run directly
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("sh");
+        command.arg(&hook);
+        command
+    } else {
+        Command::new(&hook)
+    };
+    let output = command
+        .args(args)
+        .current_dir(workdir)
+        .env("GIT_DIR", repo.path())
+        .env("GIT_EDITOR", ":")
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run the {name} hook ({})", hook.display()))?;
+
+    if !output.status.success() {
+        let mut details = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !stdout.trim().is_empty() {
+            details = format!("{}\n{details}", stdout.trim()).trim().to_string();
+        }
+        return Err(WizardError::Git(format!(
+            "the {name} hook rejected the commit{}{details}",
+            if details.is_empty() { "" } else { ":\n" }
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// the git directory shared by every worktree, where hooks live. a linked worktree's own
+/// directory names it in a `commondir` file, which git2 doesn't expose
+pub(crate) fn common_dir(repo: &Repository) -> PathBuf {
+    let git_dir = repo.path();
+    if !repo.is_worktree() {
+        return git_dir.to_path_buf();
+    }
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => {
+            let common = git_dir.join(common.trim());
+            common.canonicalize().unwrap_or(common)
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// the hook's path if it exists and can be run, honouring core.hooksPath
+fn hook_path(repo: &Repository, name: &str) -> Option<PathBuf> {
+    let dir = repo
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("core.hooksPath").ok())
+        .map(|dir| match repo.workdir() {
+            Some(workdir) if dir.is_relative() => workdir.join(dir),
+            _ => dir,
+        })
+        .unwrap_or_else(|| common_dir(repo).join("hooks"));
+    let hook = dir.join(name);
+    let metadata = fs::metadata(&hook).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // git skips hooks that aren't executable, like the shipped *.sample ones
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return None;
+        }
+    }
+    Some(hook)
+}
+
+/// sign a commit buffer with whatever gpg.format asks for
+fn sign_buffer(
+    repo: &Repository,
+    git_config: &git2::Config,
+    committer: &Signature<'_>,
+    buffer: &str,
+) -> Result<String> {
+    let format = git_config
+        .get_string("gpg.format")
+        .unwrap_or_else(|_| "openpgp".to_string());
+    let key = git_config
+        .get_string("user.signingkey")
+        .ok()
+        .filter(|key| !key.trim().is_empty());
+
+    match format.as_str() {
+        "ssh" => {
+            let key = key.ok_or_else(|| {
+                WizardError::Config(
+                    "gpg.format is ssh but user.signingkey isn't set. point it at your ssh key: git config user.signingkey ~/.ssh/id_ed25519.pub".to_string(),
+                )
+            })?;
+            let program = git_config
+                .get_string("gpg.ssh.program")
+                .unwrap_or_else(|_| "ssh-keygen".to_string());
+            // a literal key has to be handed to ssh-keygen as a file
+            let (key_file, temporary) = match key.strip_prefix("key::") {
+                Some(literal) => {
+                    let path = repo.path().join("COMMIT_WIZARD_SIGNING_KEY.pub");
+                    fs::write(&path, literal)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    (path, true)
+                }
+                None => (expand_home(&key), false),
+            };
+            let result = run_signer(
+                "ssh",
+                Command::new(&program)
+                    .args(["-Y", "sign", "-n", "git", "-f"])
+                    .arg(&key_file),
+                buffer,
+            );
+            if temporary {
+                let _ = fs::remove_file(&key_file);
+            }
+            result
+        }
+        "x509" | "openpgp" => {
+            let (section, default_program) = if format == "x509" {
+                ("gpg.x509.program", "gpgsm")
+            } else {
+                ("gpg.openpgp.program", "gpg")
+            };
+            let program = git_config
+                .get_string(section)
+                .or_else(|_| git_config.get_string("gpg.program"))
+                .unwrap_or_else(|_| default_program.to_string());
+            // without a configured key, gpg picks one matching the committer
+            let key = key.unwrap_or_else(|| {
+                format!(
+                    "{} <{}>",
+                    committer.name().unwrap_or_default(),
+                    committer.email().unwrap_or_default()
+                )
+            });
+            run_signer(
+                "gpg",
+                Command::new(&program).args(["--status-fd=2", "-bsau", &key]),
+                buffer,
+            )
+        }
+        other => Err(WizardError::Config(format!(
+            "unsupported gpg.format '{other}' (expected openpgp, x509 or ssh)"
+        ))
+        .into()),
+    }
+}
+
+/// feed the buffer to a signing program and return the signature it prints
+fn run_signer(kind: &str, command: &mut Command, buffer: &str) -> Result<String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            WizardError::Config(format!(
+                "failed to run {program} to sign the commit ({e}). install it, or commit without signing (--no-gpg-sign)"
+            ))
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(buffer.as_bytes())
+            .with_context(|| format!("failed to send the commit to {program}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to wait for {program}"))?;
+
+    let signature = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() || signature.trim().is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // gpg's status lines are noise; keep what a person would read
+        let details: Vec<&str> = stderr
+            .lines()
+            .filter(|line| !line.starts_with("[GNUPG:]"))
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        return Err(WizardError::Git(format!(
+            "{kind} failed to sign the commit{}. check user.signingkey, or commit without signing (--no-gpg-sign)",
+            if details.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", details.join(" "))
+            }
+        ))
+        .into());
+    }
+    Ok(signature)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, git};
+    use tempfile::TempDir;
+
+    /// a repository with an identity and one staged file, ready to commit
+    fn scratch_repo() -> (TempDir, Repository) {
+        let (dir, repo) = test_support::scratch_repo();
+        test_support::write(&dir, "notes.md", "notes\n");
+        test_support::stage(&repo, &["notes.md"]);
+        (dir, repo)
+    }
+
+    fn commit(dir: &TempDir, message: &str) -> String {
+        let options = CommitOptions {
+            sign: Some(false),
+            ..CommitOptions::default()
+        };
+        let outcome =
+            create_commit(dir.path().to_str().unwrap(), message, &options).expect("commit");
+        let repo = Repository::open(dir.path()).expect("open repository");
+        let commit = repo.find_commit(outcome.id).expect("find commit");
+        commit.message().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn hash_lines_in_the_message_are_kept() {
+        let (dir, _repo) = scratch_repo();
+        let message = "docs: add release notes\n\n#123 is fixed by this\n# heading\n";
+        assert_eq!(
+            commit(&dir, message),
+            "docs: add release notes\n\n#123 is fixed by this\n# heading"
+        );
+    }
+
+    #[test]
+    fn strip_cleanup_removes_comment_lines() {
+        let (dir, repo) = scratch_repo();
+        let mut config = repo.config().expect("config");
+        config
+            .set_str("commit.cleanup", "strip")
+            .expect("commit.cleanup");
+        let message = "docs: add release notes\n\n# a comment\nbody line\n";
+        assert_eq!(
+            commit(&dir, message),
+            "docs: add release notes\n\nbody line"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prepare_commit_msg_is_told_the_message_was_given_when_amending() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, _repo) = scratch_repo();
+        commit(&dir, "docs: add notes");
+        let hooks = dir.path().join(".git/hooks");
+        fs::create_dir_all(&hooks).expect("create hooks dir");
+        let hook = hooks.join("prepare-commit-msg");
+        fs::write(
+            &hook,
+            "#!/bin/sh\necho \"$# $2\" > \"$GIT_DIR/hook-args\"\n",
+        )
+        .expect("write hook");
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).expect("chmod hook");
+
+        let options = CommitOptions {
+            amend: true,
+            sign: Some(false),
+            ..CommitOptions::default()
+        };
+        create_commit(dir.path().to_str().unwrap(), "docs: add notes", &options).expect("amend");
+        let args = fs::read_to_string(dir.path().join(".git/hook-args")).expect("hook ran");
+        assert_eq!(args.trim(), "2 message");
+    }
+
+    #[test]
+    fn commits_in_a_linked_worktree_move_its_own_branch() {
+        let (dir, repo) = scratch_repo();
+        commit(&dir, "docs: add notes");
+        let main_branch = repo.head().unwrap().name().unwrap().to_string();
+        let main_tip = repo.head().unwrap().target().unwrap();
+
+        let linked = tempfile::tempdir().expect("temp dir");
+        let linked_path = linked.path().join("feature");
+        git(
+            dir.path(),
+            &[
+                "worktree",
+                "add",
+                "-b",
+                "feature",
+                linked_path.to_str().unwrap(),
+            ],
+        );
+        fs::write(linked_path.join("notes.md"), "more notes\n").expect("write file");
+        git(&linked_path, &["add", "notes.md"]);
+
+        let options = CommitOptions {
+            sign: Some(false),
+            ..CommitOptions::default()
+        };
+        let outcome = create_commit(
+            linked_path.to_str().unwrap(),
+            "docs: extend notes",
+            &options,
+        )
+        .expect("commit");
+
+        let feature = repo
+            .find_branch("feature", git2::BranchType::Local)
+            .unwrap();
+        assert_eq!(feature.get().target(), Some(outcome.id));
+        assert_eq!(repo.refname_to_id(&main_branch).unwrap(), main_tip);
+        assert_eq!(repo.head().unwrap().target(), Some(main_tip));
+    }
+
+    #[test]
+    fn sparse_checkout_keeps_its_skip_worktree_bits() {
+        let (dir, _repo) = scratch_repo();
+        for path in ["app/main.rs", "vendor/lib.rs"] {
+            test_support::write(&dir, path, "fn main() {}\n");
+        }
+        git(dir.path(), &["add", "-A"]);
+        commit(&dir, "chore: initial layout");
+        git(dir.path(), &["sparse-checkout", "set", "app"]);
+        assert!(!dir.path().join("vendor/lib.rs").exists());
+
+        fs::write(dir.path().join("app/main.rs"), "fn main() { run() }\n").expect("write file");
+        git(dir.path(), &["add", "app/main.rs"]);
+        let diff_info =
+            crate::git::get_diff_info(dir.path().to_str().unwrap(), 100 * 1024, 10, false)
+                .expect("diff info");
+        let paths: Vec<&str> = diff_info.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["app/main.rs"]);
+        commit(&dir, "fix: call run from main");
+
+        let repo = Repository::open(dir.path()).expect("open repository");
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(head.get_path(Path::new("vendor/lib.rs")).is_ok());
+        let index = repo.index().expect("index");
+        let vendored = index.get_path(Path::new("vendor/lib.rs"), 0).unwrap();
+        assert!(
+            git2::IndexEntryExtendedFlag::from_bits_truncate(vendored.flags_extended)
+                .is_skip_worktree()
+        );
+        assert!(!dir.path().join("vendor/lib.rs").exists());
+    }
+}
@@ -0,0 +1,222 @@
+// error module - typed failures so the cli and bindings can tell them apart without reading messages
+
+use std::fmt;
+
+/// the conventional commit rule a message broke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationRule {
+    EmptyMessage,
+    InvalidFormat,
+    InvalidType,
+    InvalidScope,
+    EmptyDescription,
+    DescriptionTooLong,
+    TrailingPeriod,
+    CapitalisedDescription,
+    VagueDescription,
+    NotImperative,
+}
+
+impl ValidationRule {
+    /// stable machine-readable name
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::EmptyMessage => "empty_message",
+            Self::InvalidFormat => "invalid_format",
+            Self::InvalidType => "invalid_type",
+            Self::InvalidScope => "invalid_scope",
+            Self::EmptyDescription => "empty_description",
+            Self::DescriptionTooLong => "description_too_long",
+            Self::TrailingPeriod => "trailing_period",
+            Self::CapitalisedDescription => "capitalised_description",
+            Self::VagueDescription => "vague_description",
+            Self::NotImperative => "not_imperative",
+        }
+    }
+}
+
+/// everything commit wizard can fail with that a caller might want to handle differently
+#[derive(Debug)]
+pub enum WizardError {
+    /// config file, profile or setting problem
+    Config(String),
+    /// api key missing, empty or rejected
+    Auth(String),
+    /// the api couldn't be reached
+    Network(String),
+    /// the api answered with an error not covered below
+    Api(String),
+    Timeout {
+        provider: String,
+    },
+    /// server errors that outlasted the retries
+    ServiceUnavailable {
+        provider: String,
+        status: u16,
+        body: String,
+    },
+    RateLimited {
+        provider: String,
+        body: String,
+    },
+    EmptyResponse {
+        provider: String,
+    },
+    InvalidModel {
+        model: String,
+    },
+    Validation {
+        rule: ValidationRule,
+        message: String,
+        attempts: u32, // generations that ended in this failure; more than one means retrying didn't help
+    },
+    SpendCapReached {
+        model: String,
+        spent: f64,
+        cap: f64,
+    },
+    Git(String),
+    NoChanges,
+    SecretsFound {
+        count: usize,
+    },
+    Cancelled,
+}
+
+impl WizardError {
+    pub fn validation(rule: ValidationRule, message: impl Into<String>) -> Self {
+        Self::Validation {
+            rule,
+            message: message.into(),
+            attempts: 1,
+        }
+    }
+
+    /// note that a validation failure survived this many generations
+    pub fn after_attempts(self, count: u32) -> Self {
+        match self {
+            Self::Validation { rule, message, .. } => Self::Validation {
+                rule,
+                message,
+                attempts: count,
+            },
+            other => other,
+        }
+    }
+
+    /// stable machine-readable name, as handed to the js binding
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "config",
+            Self::Auth(_) => "auth",
+            Self::Network(_) => "network",
+            Self::Api(_) => "api",
+            Self::Timeout { .. } => "timeout",
+            Self::ServiceUnavailable { .. } => "service_unavailable",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::EmptyResponse { .. } => "empty_response",
+            Self::InvalidModel { .. } => "invalid_model",
+            Self::Validation { .. } => "validation",
+            Self::SpendCapReached { .. } => "spend_cap_reached",
+            Self::Git(_) => "git",
+            Self::NoChanges => "no_changes",
+            Self::SecretsFound { .. } => "secrets_found",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// process exit code for the cli; 1 is left for failures that aren't classified
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 3,
+            Self::Auth(_) => 4,
+            Self::Network(_) => 5,
+            Self::Api(_) => 6,
+            Self::Timeout { .. } => 7,
+            Self::ServiceUnavailable { .. } => 8,
+            Self::RateLimited { .. } => 9,
+            Self::EmptyResponse { .. } => 10,
+            Self::InvalidModel { .. } => 11,
+            Self::Validation { .. } => 12,
+            Self::SpendCapReached { .. } => 13,
+            Self::Git(_) => 14,
+            Self::NoChanges => 15,
+            Self::SecretsFound { .. } => 16,
+            Self::Cancelled => 130,
+        }
+    }
+
+    /// the rule a validation error broke
+    pub fn rule(&self) -> Option<ValidationRule> {
+        match self {
+            Self::Validation { rule, .. } => Some(*rule),
+            _ => None,
+        }
+    }
+
+    /// failures tied to the model or the moment rather than the request, so another model may
+    /// succeed. a validation failure only counts once the retries have been used up on it
+    pub fn warrants_fallback(&self) -> bool {
+        match self {
+            Self::Validation { attempts, .. } => *attempts > 1,
+            _ => matches!(
+                self,
+                Self::Timeout { .. }
+                    | Self::ServiceUnavailable { .. }
+                    | Self::RateLimited { .. }
+                    | Self::EmptyResponse { .. }
+                    | Self::InvalidModel { .. }
+            ),
+        }
+    }
+
+    /// the outermost wizard error behind an `anyhow` error, looking through any context
+    pub fn find(error: &anyhow::Error) -> Option<&WizardError> {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<WizardError>())
+    }
+}
+
+impl fmt::Display for WizardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(message)
+            | Self::Auth(message)
+            | Self::Network(message)
+            | Self::Api(message)
+            | Self::Git(message) => f.write_str(message),
+            Self::Timeout { provider } => write!(f, "{provider} api request timed out"),
+            Self::ServiceUnavailable {
+                provider,
+                status,
+                body,
+            } => write!(f, "{provider} api error ({status}): {body}"),
+            Self::RateLimited { provider, body } => {
+                write!(f, "{provider} api rate limit exceeded (429): {body}")
+            }
+            Self::EmptyResponse { provider } => {
+                write!(f, "{provider} api returned an empty response")
+            }
+            Self::InvalidModel { model } => write!(
+                f,
+                "invalid model '{model}'. use the model settings menu to select a different model"
+            ),
+            Self::Validation { message, .. } => f.write_str(message),
+            Self::SpendCapReached { model, spent, cap } => write!(
+                f,
+                "monthly spend cap reached (${spent:.2} of ${cap:.2}). premium model '{model}' is blocked until next month; pick a free model or raise monthly_spend_cap"
+            ),
+            Self::NoChanges => f.write_str("no changes detected in the repository"),
+            Self::SecretsFound { count } => write!(
+                f,
+                "{count} possible secret{} in the staged changes; unstage {} or commit without --abort-on-secrets",
+                if *count == 1 { "" } else { "s" },
+                if *count == 1 { "it" } else { "them" }
+            ),
+            Self::Cancelled => f.write_str("cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for WizardError {}
@@ -1,16 +1,259 @@
+use crate::error::WizardError;
+use crate::ignore::{ContextRule, ContextRules};
+use crate::secrets::{redact_text, scan_and_redact, SecretFinding};
 use anyhow::{Context, Result};
 use encoding_rs::Encoding;
-use git2::{DiffFormat, DiffOptions, Repository};
+use git2::{
+    Delta, DiffFindOptions, DiffOptions, FileMode, IndexEntryExtendedFlag, Oid, Repository, Sort,
+};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 
 /// information about a modified file in the git diff
+#[derive(Clone)]
 pub struct ModifiedFile {
     pub path: String,
     pub added_lines: usize,
     pub removed_lines: usize,
-    pub diff_content: String,
+    pub hunks: Vec<Hunk>,
     pub file_type: FileType,
     pub change_hints: Vec<ChangeHint>,
     pub is_minified: bool,
+    pub status: FileStatus,
+    pub submodule: Option<SubmoduleChange>, // set when the path is a submodule whose commit moved
+    pub context: ContextRule, // whether .gitattributes or .commitwizardignore withhold the content
+    pub secrets: Vec<SecretFinding>, // likely credentials on added lines, already redacted in hunks
+}
+
+/// how many of a submodule's commits are listed for one pointer change
+const MAX_SUBMODULE_COMMITS: usize = 20;
+
+/// a submodule pointer change, with the submodule's own history between the two commits
+#[derive(Debug, Clone, Default)]
+pub struct SubmoduleChange {
+    pub old: Option<Oid>, // none when the submodule was just added
+    pub new: Option<Oid>, // none when it was removed
+    pub commit_count: usize,
+    pub commits: Vec<String>, // subjects of the commits brought in, newest first
+    pub rewound: bool,        // the new commit is an ancestor of the old one
+    pub log_available: bool,  // false when the submodule isn't checked out or lacks a commit
+}
+
+impl SubmoduleChange {
+    /// a short note for summaries, e.g. "submodule 1a2b3c4..5d6e7f8, 3 commits"
+    pub fn describe(&self) -> String {
+        let short = |id: Option<Oid>| id.map(|id| id.to_string()[..7].to_string());
+        let range = match (short(self.old), short(self.new)) {
+            (Some(old), Some(new)) => format!("submodule {old}..{new}"),
+            (None, Some(new)) => format!("submodule added at {new}"),
+            (Some(old), None) => format!("submodule removed, was at {old}"),
+            (None, None) => "submodule".to_string(),
+        };
+        if !self.log_available {
+            return range;
+        }
+        format!(
+            "{range}, {} commit{}{}",
+            self.commit_count,
+            if self.commit_count == 1 { "" } else { "s" },
+            if self.rewound { " rolled back" } else { "" }
+        )
+    }
+}
+
+impl ModifiedFile {
+    /// the whole diff as patch text, or a note for minified files
+    pub fn diff_content(&self) -> String {
+        if self.is_minified {
+            return format!(
+                "(minified content omitted - {} additions, {} deletions)",
+                self.added_lines, self.removed_lines
+            );
+        }
+        let mut content = String::new();
+        for hunk in &self.hunks {
+            content.push_str(&hunk.header_line());
+            content.push('\n');
+            for line in &hunk.lines {
+                content.push_str(&line.render());
+                content.push('\n');
+            }
+        }
+        content
+    }
+
+    /// this file with only the hunks at `picked`, counted and hinted afresh
+    pub fn with_hunks(&self, picked: &[usize]) -> ModifiedFile {
+        let mut file = self.clone();
+        file.hunks = picked
+            .iter()
+            .filter_map(|&at| self.hunks.get(at).cloned())
+            .collect();
+        let lines = file.hunks.iter().flat_map(|hunk| &hunk.lines);
+        file.added_lines = lines
+            .clone()
+            .filter(|line| matches!(line, DiffLine::Added(_)))
+            .count();
+        file.removed_lines = lines
+            .filter(|line| matches!(line, DiffLine::Removed(_)))
+            .count();
+        file.secrets.retain(|secret| {
+            file.hunks.iter().any(|hunk| {
+                (hunk.new_start..hunk.new_start + hunk.new_lines).contains(&secret.line)
+            })
+        });
+        file.change_hints = analyse_change_hints(&file.diff_content(), false);
+        file
+    }
+}
+
+/// one contiguous block of changes within a file
+#[derive(Debug, Clone, Default)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub function: Option<String>, // enclosing function or section, from the @@ line
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    fn from_git(hunk: &git2::DiffHunk) -> Self {
+        // "@@ -1,4 +1,5 @@ fn main() {" - git's context heuristic puts the function after the second @@
+        let header = decode_line_content(hunk.header());
+        let function = header
+            .splitn(3, "@@")
+            .nth(2)
+            .map(str::trim)
+            .filter(|function| !function.is_empty())
+            .map(str::to_string);
+        Self {
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            function,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn header_line(&self) -> String {
+        let mut header = format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        );
+        if let Some(function) = &self.function {
+            header.push(' ');
+            header.push_str(function);
+        }
+        header
+    }
+}
+
+/// a line of a hunk, without its trailing newline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Added(String),
+    Removed(String),
+    Context(String),
+}
+
+impl DiffLine {
+    /// the line as it appears in a patch, prefixed with +, - or a space
+    pub fn render(&self) -> String {
+        match self {
+            Self::Added(text) => format!("+{text}"),
+            Self::Removed(text) => format!("-{text}"),
+            Self::Context(text) => format!(" {text}"),
+        }
+    }
+}
+
+/// what happened to a file, as git sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed {
+        from: String,
+    },
+    Copied {
+        from: String,
+    },
+    /// permissions changed, e.g. chmod +x, possibly along with the content
+    ModeChanged {
+        from: FileMode,
+        to: FileMode,
+    },
+    /// the kind of entry changed, e.g. a file replaced by a symlink
+    TypeChanged {
+        from: FileMode,
+        to: FileMode,
+    },
+}
+
+impl FileStatus {
+    fn from_delta(delta: &git2::DiffDelta) -> Self {
+        let old_path = || {
+            delta
+                .old_file()
+                .path()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let (from, to) = (delta.old_file().mode(), delta.new_file().mode());
+        match delta.status() {
+            Delta::Added | Delta::Untracked => Self::Added,
+            Delta::Deleted => Self::Deleted,
+            Delta::Renamed => Self::Renamed { from: old_path() },
+            Delta::Copied => Self::Copied { from: old_path() },
+            Delta::Typechange => Self::TypeChanged { from, to },
+            _ if from != to => Self::ModeChanged { from, to },
+            _ => Self::Modified,
+        }
+    }
+
+    /// the path the content came from, for a rename or copy
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            Self::Renamed { from } | Self::Copied { from } => Some(from),
+            _ => None,
+        }
+    }
+
+    /// a short note for summaries and prompts; none for a plain modification
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Self::Added => Some("new file".to_string()),
+            Self::Modified => None,
+            Self::Deleted => Some("deleted".to_string()),
+            Self::Renamed { from } => Some(format!("renamed from {from}")),
+            Self::Copied { from } => Some(format!("copied from {from}")),
+            Self::ModeChanged { from, to } => Some(format!(
+                "mode changed from {} to {}",
+                mode_name(*from),
+                mode_name(*to)
+            )),
+            Self::TypeChanged { from, to } => Some(format!(
+                "changed from {} to {}",
+                mode_name(*from),
+                mode_name(*to)
+            )),
+        }
+    }
+}
+
+fn mode_name(mode: FileMode) -> &'static str {
+    match mode {
+        FileMode::Blob | FileMode::BlobGroupWritable => "regular file",
+        FileMode::BlobExecutable => "executable",
+        FileMode::Link => "symlink",
+        FileMode::Commit => "submodule",
+        FileMode::Tree => "directory",
+        FileMode::Unreadable => "unreadable",
+    }
 }
 
 /// categorize files by their purpose
@@ -46,6 +289,24 @@ pub enum ChangeHint {
 pub struct DiffInfo {
     pub files: Vec<ModifiedFile>,
     pub summary: String,
+    pub previous_message: Option<String>, // message of the commit being amended or reworded
+}
+
+impl DiffInfo {
+    /// the part of this diff touching `paths`, e.g. one group of a split. files listed in
+    /// `hunks` keep only the hunks at those indices
+    pub fn subset(&self, paths: &[String], hunks: &BTreeMap<String, Vec<usize>>) -> DiffInfo {
+        let files = self
+            .files
+            .iter()
+            .filter(|file| paths.contains(&file.path))
+            .map(|file| match hunks.get(&file.path) {
+                Some(picked) => file.with_hunks(picked),
+                None => file.clone(),
+            })
+            .collect();
+        summarise(files, self.previous_message.clone())
+    }
 }
 
 /// get diff information from a git repository
@@ -61,6 +322,7 @@ pub fn get_diff_info(
     // create diff options
     let mut diff_opts = DiffOptions::new();
     diff_opts.show_binary(false);
+    diff_opts.include_typechange(true);
     diff_opts.include_untracked(true);
     diff_opts.recurse_untracked_dirs(true);
 
@@ -77,8 +339,15 @@ pub fn get_diff_info(
 
         if let Ok(head) = repo.head() {
             if let Ok(tree) = head.peel_to_tree() {
-                let diff = repo.diff_tree_to_index(Some(&tree), None, Some(&mut diff_opts))?;
-                process_diff(&diff, &mut files, max_file_size, max_files, verbose)?;
+                let mut diff = repo.diff_tree_to_index(Some(&tree), None, Some(&mut diff_opts))?;
+                process_diff(
+                    &repo,
+                    &mut diff,
+                    &mut files,
+                    max_file_size,
+                    max_files,
+                    verbose,
+                )?;
             }
         }
     } else if verbose {
@@ -88,6 +357,7 @@ pub fn get_diff_info(
     // if the repository doesn't have commits yet, get all staged files
     if !has_head {
         // for new repos, we need to get all staged files in the index
+        let rules = ContextRules::load(&repo);
         if let Ok(index) = repo.index() {
             for entry in index.iter() {
                 let path_str = std::str::from_utf8(&entry.path).unwrap_or_default();
@@ -119,25 +389,41 @@ pub fn get_diff_info(
                         }
 
                         // add file to the list
-                        let is_minified = is_minified_file(path_str, Some(&content));
-                        let diff_content = if is_minified {
-                            format!("(minified content omitted - {} lines)", line_count)
-                        } else {
-                            format!("+{content}")
-                        };
+                        let context = rules.rule_for(path_str);
+                        let withheld = matches!(context, ContextRule::Excluded(_));
+                        let is_minified = context == ContextRule::Default
+                            && is_minified_file(path_str, Some(&content));
+                        let mut hunks = vec![Hunk {
+                            new_start: 1,
+                            new_lines: line_count as u32,
+                            lines: content
+                                .lines()
+                                .map(|line| DiffLine::Added(line.to_string()))
+                                .collect(),
+                            ..Hunk::default()
+                        }];
+                        // scanned even when withheld, since the secret is still committed
+                        let secrets = scan_and_redact(&mut hunks);
+                        if is_minified || withheld {
+                            hunks = Vec::new();
+                        }
 
                         files.push(ModifiedFile {
                             path: path_str.to_string(),
                             added_lines: line_count,
                             removed_lines: 0,
-                            diff_content,
+                            hunks,
                             file_type: classify_file_type(path_str),
-                            change_hints: if is_minified {
+                            change_hints: if is_minified || withheld {
                                 Vec::new()
                             } else {
                                 analyse_change_hints(&content, true)
                             },
                             is_minified,
+                            status: FileStatus::Added,
+                            submodule: None,
+                            context,
+                            secrets,
                         });
 
                         // limit number of files
@@ -156,17 +442,123 @@ pub fn get_diff_info(
             println!("no staged changes found, checking unstaged changes (working directory vs index)...");
         }
 
-        if let Ok(diff) = repo.diff_index_to_workdir(None, Some(&mut diff_opts)) {
-            process_diff(&diff, &mut files, max_file_size, max_files, verbose)?;
+        if let Ok(mut diff) = repo.diff_index_to_workdir(None, Some(&mut diff_opts)) {
+            process_diff(
+                &repo,
+                &mut diff,
+                &mut files,
+                max_file_size,
+                max_files,
+                verbose,
+            )?;
+
+            // libgit2 doesn't honour sparse checkouts: paths outside the cone are absent from
+            // the working tree on purpose, not deleted
+            let sparse = sparse_paths(&repo);
+            files.retain(|file| {
+                !(file.status == FileStatus::Deleted && sparse.contains(&file.path))
+            });
         }
     } else if verbose {
         println!("staged changes found, skipping unstaged changes...");
     }
 
     if files.is_empty() {
-        return Err(anyhow::anyhow!("no changes detected in the repository"));
+        return Err(WizardError::NoChanges.into());
+    }
+
+    Ok(summarise(files, None))
+}
+
+/// get diff information for amending HEAD: everything HEAD changed plus anything staged
+/// since, with HEAD's message kept as context for the new one
+pub fn get_amend_diff_info(
+    repo_path: &str,
+    max_file_size: usize,
+    max_files: usize,
+    verbose: bool,
+) -> Result<DiffInfo> {
+    let repo = Repository::discover(repo_path).context("failed to open git repository")?;
+    let head = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => commit,
+        Err(_) => {
+            return Err(WizardError::Git("there is no commit to amend yet".to_string()).into())
+        }
+    };
+
+    // the index already holds HEAD's tree plus newly staged changes, so comparing it with
+    // HEAD's parent covers both
+    let parent_tree = parent_tree(&head)?;
+    if verbose {
+        match parent_tree {
+            Some(_) => println!("analysing HEAD~1..HEAD plus staged changes..."),
+            None => println!("amending the root commit, analysing all of its files..."),
+        }
+    }
+
+    let mut diff_opts = DiffOptions::new();
+    diff_opts.show_binary(false);
+    diff_opts.include_typechange(true);
+    let mut diff = repo.diff_tree_to_index(parent_tree.as_ref(), None, Some(&mut diff_opts))?;
+    commit_diff_info(&repo, &mut diff, &head, max_file_size, max_files, verbose)
+}
+
+/// get diff information for one commit in history: its own changes against its first
+/// parent, with its message kept as context for the new one
+pub fn get_commit_diff_info(
+    repo_path: &str,
+    commit_id: git2::Oid,
+    max_file_size: usize,
+    max_files: usize,
+    verbose: bool,
+) -> Result<DiffInfo> {
+    let repo = Repository::discover(repo_path).context("failed to open git repository")?;
+    let commit = repo
+        .find_commit(commit_id)
+        .map_err(|e| WizardError::Git(format!("can't read commit {commit_id}: {e}")))?;
+
+    let mut diff_opts = DiffOptions::new();
+    diff_opts.show_binary(false);
+    diff_opts.include_typechange(true);
+    let mut diff = repo.diff_tree_to_tree(
+        parent_tree(&commit)?.as_ref(),
+        Some(&commit.tree()?),
+        Some(&mut diff_opts),
+    )?;
+    commit_diff_info(&repo, &mut diff, &commit, max_file_size, max_files, verbose)
+}
+
+/// a commit's first parent tree. a root commit has none, so every file counts as added
+fn parent_tree<'r>(commit: &git2::Commit<'r>) -> Result<Option<git2::Tree<'r>>> {
+    match commit.parent(0) {
+        Ok(parent) => Ok(Some(parent.tree()?)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn commit_diff_info(
+    repo: &Repository,
+    diff: &mut git2::Diff,
+    commit: &git2::Commit,
+    max_file_size: usize,
+    max_files: usize,
+    verbose: bool,
+) -> Result<DiffInfo> {
+    let mut files = Vec::new();
+    process_diff(repo, diff, &mut files, max_file_size, max_files, verbose)?;
+    if files.is_empty() {
+        return Err(WizardError::NoChanges.into());
     }
 
+    let previous_message = commit
+        .message()
+        .map(|message| redact_text(message.trim()))
+        .filter(|message| !message.is_empty());
+    Ok(summarise(files, previous_message))
+}
+
+/// wrap analysed files up with a human-readable summary of the change
+fn summarise(files: Vec<ModifiedFile>, previous_message: Option<String>) -> DiffInfo {
     // build a summary of the changes
     let file_count = files.len();
     let total_additions: usize = files.iter().map(|f| f.added_lines).sum();
@@ -185,24 +577,29 @@ pub fn get_diff_info(
     // generate detailed file breakdown for summary
     summary.push_str("\n\nfile breakdown:\n");
     for file in &files {
-        let change_type = if file.removed_lines == 0 && file.added_lines > 5 {
-            " (new file)"
-        } else if file.added_lines > file.removed_lines * 2 {
-            " (major additions)"
-        } else if file.removed_lines > file.added_lines * 2 {
-            " (major deletions)"
-        } else {
-            " (modified)"
+        let change_type = match (&file.submodule, file.status.describe()) {
+            (Some(submodule), _) => format!(" ({})", submodule.describe()),
+            (None, Some(status)) => format!(" ({status})"),
+            (None, None) if file.added_lines > file.removed_lines * 2 => {
+                " (major additions)".to_string()
+            }
+            (None, None) if file.removed_lines > file.added_lines * 2 => {
+                " (major deletions)".to_string()
+            }
+            (None, None) => " (modified)".to_string(),
         };
 
         summary.push_str(&format!(
             "  {} (+{}, -{}){}",
             file.path, file.added_lines, file.removed_lines, change_type
         ));
+        if let ContextRule::Excluded(rule) = &file.context {
+            summary.push_str(&format!(" [content withheld: {rule}]"));
+        }
 
         // add specific code changes if available
-        if !file.diff_content.is_empty() {
-            let key_changes = extract_key_changes(&file.diff_content);
+        if !file.hunks.is_empty() {
+            let key_changes = extract_key_changes(&file.diff_content());
             if !key_changes.is_empty() {
                 summary.push_str(&format!("\n    key changes: {key_changes}"));
             }
@@ -210,11 +607,15 @@ pub fn get_diff_info(
         summary.push('\n');
     }
 
-    Ok(DiffInfo { files, summary })
+    DiffInfo {
+        files,
+        summary,
+        previous_message,
+    }
 }
 
 /// extract key changes from diff content to provide meaningful context
-fn extract_key_changes(diff_content: &str) -> String {
+pub fn extract_key_changes(diff_content: &str) -> String {
     let mut changes = Vec::new();
     let added_lines: Vec<&str> = diff_content
         .lines()
@@ -319,6 +720,7 @@ pub fn has_staged_changes(repo_path: &str) -> Result<bool> {
         if let Ok(tree) = head.peel_to_tree() {
             let mut diff_opts = DiffOptions::new();
             diff_opts.show_binary(false);
+            diff_opts.include_typechange(true);
 
             if let Ok(diff) = repo.diff_tree_to_index(Some(&tree), None, Some(&mut diff_opts)) {
                 // if diff has any deltas, there are staged changes
@@ -357,6 +759,7 @@ pub fn get_staged_files(repo_path: &str) -> Result<Vec<String>> {
         if let Ok(tree) = head.peel_to_tree() {
             let mut diff_opts = DiffOptions::new();
             diff_opts.show_binary(false);
+            diff_opts.include_typechange(true);
 
             if let Ok(diff) = repo.diff_tree_to_index(Some(&tree), None, Some(&mut diff_opts)) {
                 diff.foreach(
@@ -377,27 +780,43 @@ pub fn get_staged_files(repo_path: &str) -> Result<Vec<String>> {
     Ok(staged_files)
 }
 
-/// process a diff to extract file information (optimised with batching)
+/// process a diff into files with structured hunks, in a single pass
 fn process_diff(
-    diff: &git2::Diff,
+    repo: &Repository,
+    diff: &mut git2::Diff,
     files: &mut Vec<ModifiedFile>,
     max_file_size: usize,
     max_files: usize,
     verbose: bool,
 ) -> Result<()> {
-    // first pass: collect valid file paths (simplified approach)
-    let mut valid_paths = Vec::new();
+    // pair deletions with additions so moves, renames and copies show up as such
+    let mut find_opts = DiffFindOptions::new();
+    find_opts.renames(true).copies(true).for_untracked(true);
+    diff.find_similar(Some(&mut find_opts))?;
+
+    let rules = ContextRules::load(repo);
+
+    // a single pass: each file is followed by its hunks and their lines, so everything
+    // lands on the most recently accepted file. files skipped below leave `current` empty
+    let first_new = files.len();
+    let state = RefCell::new((std::mem::take(files), None::<usize>));
 
     // handle early termination gracefully when max_files is reached
     let result = diff.foreach(
         &mut |delta, _| {
+            let mut state = state.borrow_mut();
+            let (files, current) = &mut *state;
+            *current = None;
             let path = match delta.new_file().path() {
                 Some(path) => path.to_string_lossy().to_string(),
                 None => return true,
             };
 
-            // skip binary files
-            if delta.new_file().is_binary() || delta.old_file().is_binary() {
+            // skip binary files, unless an attribute made them binary: those are still counted
+            let context = rules.rule_for(&path);
+            if (delta.new_file().is_binary() || delta.old_file().is_binary())
+                && !matches!(context, ContextRule::Excluded(_))
+            {
                 if verbose {
                     println!("skipping binary file: {path}");
                 }
@@ -418,17 +837,67 @@ fn process_diff(
             }
 
             // stop if we've reached max_files
-            if valid_paths.len() >= max_files {
+            if files.len() - first_new >= max_files {
                 return false; // stop processing
             }
 
-            valid_paths.push(path);
+            *current = Some(files.len());
+            files.push(ModifiedFile {
+                file_type: classify_file_type(&path),
+                context,
+                path,
+                added_lines: 0,
+                removed_lines: 0,
+                hunks: Vec::new(),
+                change_hints: Vec::new(),
+                is_minified: false, // will be determined once the lines are in
+                status: FileStatus::from_delta(&delta),
+                submodule: submodule_pointers(&delta).map(|(old, new)| SubmoduleChange {
+                    old,
+                    new,
+                    ..SubmoduleChange::default()
+                }),
+                secrets: Vec::new(), // found once the lines are in
+            });
             true
         },
         None,
-        None,
-        None,
+        Some(&mut |_, hunk| {
+            let mut state = state.borrow_mut();
+            let (files, current) = &mut *state;
+            if let Some(file) = current.map(|at| &mut files[at]) {
+                file.hunks.push(Hunk::from_git(&hunk));
+            }
+            true
+        }),
+        Some(&mut |_, _, line| {
+            let mut state = state.borrow_mut();
+            let (files, current) = &mut *state;
+            let Some(file) = current.map(|at| &mut files[at]) else {
+                return true;
+            };
+            let text = decode_line_content(line.content())
+                .trim_end_matches(['\n', '\r'])
+                .to_string();
+            let line = match line.origin() {
+                '+' => {
+                    file.added_lines += 1;
+                    DiffLine::Added(text)
+                }
+                '-' => {
+                    file.removed_lines += 1;
+                    DiffLine::Removed(text)
+                }
+                ' ' => DiffLine::Context(text),
+                _ => return true, // "no newline at end of file" markers
+            };
+            if let Some(hunk) = file.hunks.last_mut() {
+                hunk.lines.push(line);
+            }
+            true
+        }),
     );
+    *files = state.into_inner().0;
 
     // handle git2 error code -7 (GIT_EUSER) as normal early termination
     match result {
@@ -442,67 +911,108 @@ fn process_diff(
         Err(e) => return Err(e.into()),
     }
 
-    // second pass: initialise file entries for all valid paths
-    for path in &valid_paths {
-        files.push(ModifiedFile {
-            path: path.clone(),
-            added_lines: 0,
-            removed_lines: 0,
-            diff_content: String::new(),
-            file_type: classify_file_type(path),
-            change_hints: Vec::new(),
-            is_minified: false, // will be determined when collecting diff content
-        });
+    // look up what a moved submodule pointer brought in
+    for file in files[first_new..].iter_mut() {
+        if let Some(change) = &mut file.submodule {
+            resolve_submodule_log(repo, &file.path, change);
+        }
     }
 
-    // third pass: collect diff content efficiently
-    diff.print(DiffFormat::Patch, |delta, _, line| {
-        let path = match delta.new_file().path() {
-            Some(path) => path.to_string_lossy().to_string(),
-            None => return true,
-        };
+    // drop content the model shouldn't see: secrets, withheld files and minified ones. every
+    // file is scanned first, since a withheld file's secrets are committed all the same
+    for file in files[first_new..].iter_mut() {
+        file.secrets = scan_and_redact(&mut file.hunks);
+        let content = file.diff_content();
+        // an explicit rule overrides the minified file heuristics either way
+        file.is_minified =
+            file.context == ContextRule::Default && is_minified_file(&file.path, Some(&content));
+
+        if file.is_minified || matches!(file.context, ContextRule::Excluded(_)) {
+            file.hunks = Vec::new(); // counted, but summarised instead of shown
+            file.change_hints = Vec::new();
+        } else {
+            // only analyse change hints for files whose content is kept
+            file.change_hints = analyse_change_hints(&content, false);
+        }
+    }
 
-        // find the file entry (we know it exists from the second pass)
-        if let Some(file_entry) = files.iter_mut().find(|f| f.path == path) {
-            // track added and removed lines
-            match line.origin() {
-                '+' => file_entry.added_lines += 1,
-                '-' => file_entry.removed_lines += 1,
-                _ => {}
-            }
+    Ok(())
+}
 
-            // append line to diff content (up to a reasonable size)
-            if file_entry.diff_content.len() < 5000 {
-                file_entry.diff_content.push(line.origin());
-                file_entry
-                    .diff_content
-                    .push_str(&decode_line_content(line.content()));
-            }
-        }
+/// index paths marked skip-worktree, i.e. outside a sparse checkout
+pub(crate) fn sparse_paths(repo: &Repository) -> HashSet<String> {
+    let Ok(index) = repo.index() else {
+        return HashSet::new();
+    };
+    index
+        .iter()
+        .filter(|entry| {
+            IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree()
+        })
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect()
+}
 
-        true
-    })?;
+/// the old and new commits of a submodule entry, or none if this isn't a pointer change
+fn submodule_pointers(delta: &git2::DiffDelta) -> Option<(Option<Oid>, Option<Oid>)> {
+    let pointer = |file: git2::DiffFile| {
+        (file.mode() == FileMode::Commit && !file.id().is_zero()).then(|| file.id())
+    };
+    let (old, new) = (pointer(delta.old_file()), pointer(delta.new_file()));
+    // a dirty submodule working tree shows up as a change with the same commit
+    match (old, new) {
+        (None, None) => None,
+        (Some(old), Some(new)) if old == new => None,
+        pointers => Some(pointers),
+    }
+}
 
-    // detect minified files and adjust their content
-    for file in files.iter_mut() {
-        // check if file is minified based on path and content
-        file.is_minified = is_minified_file(&file.path, Some(&file.diff_content));
+/// fill in the submodule's commit log between the two pointers. it's left unavailable
+/// when the submodule isn't checked out or hasn't fetched both commits
+fn resolve_submodule_log(repo: &Repository, path: &str, change: &mut SubmoduleChange) {
+    let Some(submodule) = repo
+        .workdir()
+        .and_then(|workdir| Repository::open(workdir.join(path)).ok())
+    else {
+        return;
+    };
+    // only a moved pointer has a log worth summarising; an added submodule is all history
+    let (Some(old), Some(new)) = (change.old, change.new) else {
+        return;
+    };
+    if submodule.find_commit(old).is_err() || submodule.find_commit(new).is_err() {
+        return;
+    }
 
-        // if minified, replace diff content with summary
-        if file.is_minified {
-            let summary = format!(
-                "(minified content omitted - {} additions, {} deletions)",
-                file.added_lines, file.removed_lines
-            );
-            file.diff_content = summary;
-            file.change_hints = Vec::new(); // no hints for minified files
-        } else {
-            // only analyse change hints for non-minified files
-            file.change_hints = analyse_change_hints(&file.diff_content, false);
-        }
+    // moving back lists the commits being dropped instead
+    change.rewound = submodule.graph_descendant_of(old, new).unwrap_or(false);
+    let (tip, base) = if change.rewound {
+        (old, new)
+    } else {
+        (new, old)
+    };
+    let Ok(mut walk) = submodule.revwalk() else {
+        return;
+    };
+    let walked = walk
+        .set_sorting(Sort::TOPOLOGICAL)
+        .and_then(|_| walk.push(tip))
+        .and_then(|_| walk.hide(base));
+    if walked.is_err() {
+        return;
     }
 
-    Ok(())
+    for id in walk.flatten() {
+        change.commit_count += 1;
+        if change.commits.len() < MAX_SUBMODULE_COMMITS {
+            if let Ok(commit) = submodule.find_commit(id) {
+                change
+                    .commits
+                    .push(commit.summary().unwrap_or_default().to_string());
+            }
+        }
+    }
+    change.log_available = true;
 }
 
 /// classify file type based on path and extension
@@ -0,0 +1,375 @@
+// http module - sends api requests, optionally recording them as fixtures or replaying them offline
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// overrides the api base url of every provider profile
+pub const API_BASE_ENV: &str = "COMMIT_WIZARD_API_BASE";
+/// `live` (default), `record` or `replay`
+pub const HTTP_MODE_ENV: &str = "COMMIT_WIZARD_HTTP_MODE";
+/// directory holding recorded request/response fixtures
+pub const FIXTURES_ENV: &str = "COMMIT_WIZARD_FIXTURES";
+
+/// how api requests reach the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMode {
+    Live,
+    Record, // send for real and save each exchange as a fixture
+    Replay, // answer from saved fixtures, never touching the network
+}
+
+impl HttpMode {
+    pub fn from_env() -> Result<Self> {
+        match env::var(HTTP_MODE_ENV) {
+            Err(_) => Ok(Self::Live),
+            Ok(mode) => match mode.trim().to_lowercase().as_str() {
+                "" | "live" => Ok(Self::Live),
+                "record" => Ok(Self::Record),
+                "replay" => Ok(Self::Replay),
+                other => Err(anyhow::anyhow!(
+                    "invalid {HTTP_MODE_ENV} '{other}' (expected live, record or replay)"
+                )),
+            },
+        }
+    }
+}
+
+/// whether responses come from fixtures, so no api key or network is needed
+pub fn is_replaying() -> bool {
+    matches!(HttpMode::from_env(), Ok(HttpMode::Replay))
+}
+
+/// base url forced through the environment, e.g. to point at a local stand-in server
+pub fn api_base_override() -> Option<String> {
+    env::var(API_BASE_ENV)
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// `[http]` settings shared by every api request
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>, // without it, HTTPS_PROXY and friends are honoured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>, // comma-separated hosts that bypass `proxy`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_certs: Vec<PathBuf>, // extra pem root certificates, e.g. a tls-intercepting proxy's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>, // whole request; defaults to 30s for hosted apis, 300s for local ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retry_after_secs: Option<u64>, // longest `Retry-After` worth waiting for
+}
+
+impl HttpConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs.unwrap_or(10))
+    }
+
+    /// the configured request timeout, or the caller's default for its kind of backend
+    pub fn timeout_or(&self, default: Duration) -> Duration {
+        self.timeout_secs.map_or(default, Duration::from_secs)
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(3).max(1)
+    }
+
+    pub fn max_retry_after(&self) -> Duration {
+        Duration::from_secs(self.max_retry_after_secs.unwrap_or(60))
+    }
+
+    fn build_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder().connect_timeout(self.connect_timeout());
+
+        if let Some(url) = self.proxy.as_deref().filter(|url| !url.trim().is_empty()) {
+            let proxy = reqwest::Proxy::all(url.trim())
+                .with_context(|| format!("invalid proxy url '{url}'"))?
+                .no_proxy(
+                    self.no_proxy
+                        .as_deref()
+                        .and_then(reqwest::NoProxy::from_string),
+                );
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.ca_certs {
+            let pem = fs::read(path)
+                .with_context(|| format!("failed to read ca certificate {}", path.display()))?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("invalid pem certificate in {}", path.display()))?;
+            if certs.is_empty() {
+                anyhow::bail!("no pem certificates found in {}", path.display());
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        builder.build().context("failed to build http client")
+    }
+}
+
+// the client is rebuilt only when the settings change, so connections are pooled across requests
+struct SharedClient {
+    settings: HttpConfig,
+    client: Option<reqwest::Client>,
+}
+
+fn shared() -> &'static Mutex<SharedClient> {
+    static SHARED: OnceLock<Mutex<SharedClient>> = OnceLock::new();
+    SHARED.get_or_init(|| {
+        Mutex::new(SharedClient {
+            settings: HttpConfig::default(),
+            client: None,
+        })
+    })
+}
+
+/// use these settings for every request from now on
+pub fn configure(settings: &HttpConfig) {
+    let mut shared = shared()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if shared.settings != *settings {
+        shared.settings = settings.clone();
+        shared.client = None;
+    }
+}
+
+/// the settings requests are currently sent with
+pub fn settings() -> HttpConfig {
+    shared()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .settings
+        .clone()
+}
+
+/// the shared client, built on first use
+pub fn client() -> Result<reqwest::Client> {
+    let mut shared = shared()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = &shared.client {
+        return Ok(client.clone());
+    }
+    let client = shared.settings.build_client()?;
+    shared.client = Some(client.clone());
+    Ok(client)
+}
+
+/// how long a 429 or 503 asks us to wait, from `Retry-After` as seconds or an http date
+pub fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// one saved request/response exchange
+#[derive(Serialize, Deserialize, Debug)]
+struct Fixture {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    body: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_after: Option<String>,
+    body: serde_json::Value,
+}
+
+/// send a request according to the current http mode.
+/// network failures surface as `reqwest::Error` so callers can retry them; fixture
+/// problems are plain errors that retrying won't fix
+pub async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let mode = HttpMode::from_env()?;
+    if mode == HttpMode::Live {
+        return Ok(request.send().await?);
+    }
+
+    let (client, request) = request.build_split();
+    let request = request?;
+    let dir = fixtures_dir()?;
+    let recorded = RecordedRequest {
+        method: request.method().to_string(),
+        path: match request.url().query() {
+            Some(query) => format!("{}?{query}", request.url().path()),
+            None => request.url().path().to_string(),
+        },
+        body: body_value(
+            request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .unwrap_or_default(),
+        ),
+    };
+    let key = fixture_key(&recorded);
+    let call = next_call(&key);
+
+    match mode {
+        HttpMode::Replay => replay(&dir, &key, call, &recorded),
+        _ => {
+            let response = client.execute(request).await?;
+            let status = response.status();
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let content_type = header(reqwest::header::CONTENT_TYPE);
+            let retry_after = header(reqwest::header::RETRY_AFTER);
+            let body = response.bytes().await?;
+
+            let fixture = Fixture {
+                request: recorded,
+                response: RecordedResponse {
+                    status: status.as_u16(),
+                    content_type,
+                    retry_after,
+                    body: body_value(&body),
+                },
+            };
+            let path = dir.join(fixture_name(&key, call));
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("failed to create fixture directory {}", dir.display()))?;
+            fs::write(&path, serde_json::to_string_pretty(&fixture)?)
+                .with_context(|| format!("failed to write fixture {}", path.display()))?;
+
+            build_response(&fixture.response)
+        }
+    }
+}
+
+/// serve the recorded answer for this request, reusing the last one once a sequence runs out
+fn replay(
+    dir: &Path,
+    key: &str,
+    call: usize,
+    request: &RecordedRequest,
+) -> Result<reqwest::Response> {
+    let path = (0..=call)
+        .rev()
+        .map(|n| dir.join(fixture_name(key, n)))
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "no recorded response for {} {} in {} (expected {}). record one with {HTTP_MODE_ENV}=record",
+                request.method,
+                request.path,
+                dir.display(),
+                fixture_name(key, 0)
+            )
+        })?;
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read fixture {}", path.display()))?;
+    let fixture: Fixture = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse fixture {}", path.display()))?;
+    build_response(&fixture.response)
+}
+
+fn build_response(recorded: &RecordedResponse) -> Result<reqwest::Response> {
+    let body = match &recorded.body {
+        serde_json::Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    let mut builder = ::http::Response::builder().status(recorded.status);
+    if let Some(content_type) = &recorded.content_type {
+        builder = builder.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    if let Some(retry_after) = &recorded.retry_after {
+        builder = builder.header(reqwest::header::RETRY_AFTER, retry_after);
+    }
+    Ok(reqwest::Response::from(builder.body(body)?))
+}
+
+fn fixtures_dir() -> Result<PathBuf> {
+    env::var(FIXTURES_ENV)
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .with_context(|| {
+            format!("{FIXTURES_ENV} must point at a fixture directory when {HTTP_MODE_ENV} is record or replay")
+        })
+}
+
+/// json bodies are kept as json so fixtures stay readable and easy to edit by hand
+fn body_value(bytes: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// requests are matched on method, path and body; the host is left out so fixtures
+/// recorded against a real api replay under any base url
+fn fixture_key(request: &RecordedRequest) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(request.method.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(request.path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(request.body.to_string().as_bytes());
+    hasher.finalize()[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn fixture_name(key: &str, call: usize) -> String {
+    format!("{key}-{call}.json")
+}
+
+/// how many times this exact request has already been sent during this run, so
+/// repeated requests (retries) can be recorded and replayed as a sequence
+fn next_call(key: &str) -> usize {
+    static CALLS: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+    let mut calls = CALLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let count = calls.entry(key.to_string()).or_insert(0);
+    let call = *count;
+    *count += 1;
+    call
+}
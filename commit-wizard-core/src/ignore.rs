@@ -0,0 +1,396 @@
+// ignore module - decides which changed files are kept out of the ai context, from
+// .gitattributes and a gitignore-style .commitwizardignore
+
+use git2::{AttrCheckFlags, AttrValue, Repository};
+use std::path::Path;
+
+/// the project ignore file, read from the root of the working tree
+pub const IGNORE_FILE: &str = ".commitwizardignore";
+
+/// whether a file's content may be sent to the model
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ContextRule {
+    /// left to the built-in lists of generated, minified and lock files
+    #[default]
+    Default,
+    /// counted in the summary but its content is withheld; holds the rule responsible
+    Excluded(String),
+    /// explicitly wanted, e.g. `linguist-generated=false` or a `!` ignore rule, so the
+    /// built-in lists don't apply
+    Included,
+}
+
+/// the rules for one repository, loaded once per diff
+pub struct ContextRules<'r> {
+    repo: &'r Repository,
+    ignore: Vec<IgnorePattern>,
+}
+
+impl<'r> ContextRules<'r> {
+    pub fn load(repo: &'r Repository) -> Self {
+        let ignore = repo
+            .workdir()
+            .and_then(|workdir| std::fs::read_to_string(workdir.join(IGNORE_FILE)).ok())
+            .map(|content| content.lines().filter_map(IgnorePattern::parse).collect())
+            .unwrap_or_default();
+        Self { repo, ignore }
+    }
+
+    /// the ignore file wins over attributes, since it's specific to commit-wizard
+    pub fn rule_for(&self, path: &str) -> ContextRule {
+        ignore_rule(&self.ignore, path).unwrap_or_else(|| self.attribute_rule(path))
+    }
+
+    fn attribute_rule(&self, path: &str) -> ContextRule {
+        let attr = |name: &str| {
+            self.repo
+                .get_attr(Path::new(path), name, AttrCheckFlags::FILE_THEN_INDEX)
+                .ok()
+                .flatten()
+                .and_then(|value| match AttrValue::from_string(Some(value)) {
+                    AttrValue::True | AttrValue::String("true") => Some(true),
+                    AttrValue::False | AttrValue::String("false") => Some(false),
+                    _ => None,
+                })
+        };
+
+        if attr("binary") == Some(true) {
+            return ContextRule::Excluded("binary in .gitattributes".to_string());
+        }
+        if attr("diff") == Some(false) {
+            return ContextRule::Excluded("-diff in .gitattributes".to_string());
+        }
+        for name in ["linguist-generated", "linguist-vendored"] {
+            if attr(name) == Some(true) {
+                return ContextRule::Excluded(format!("{name} in .gitattributes"));
+            }
+        }
+        if attr("linguist-generated") == Some(false) {
+            return ContextRule::Included;
+        }
+        ContextRule::Default
+    }
+}
+
+/// like git, each directory on the way down is checked before the file, and the last
+/// matching pattern decides; once a directory is excluded nothing below it can be
+/// re-included, while a re-included directory includes the files under it
+fn ignore_rule(patterns: &[IgnorePattern], path: &str) -> Option<ContextRule> {
+    let last_match = |prefix: &str, is_directory: bool| {
+        patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(prefix, is_directory))
+    };
+
+    let excluded = |pattern: &IgnorePattern| {
+        ContextRule::Excluded(format!("{IGNORE_FILE}: {}", pattern.source))
+    };
+
+    let mut included = false;
+    for (end, _) in path.match_indices('/') {
+        match last_match(&path[..end], true) {
+            Some(pattern) if pattern.negated => included = true,
+            Some(pattern) => return Some(excluded(pattern)),
+            None => {}
+        }
+    }
+    match last_match(path, false) {
+        Some(pattern) if pattern.negated => Some(ContextRule::Included),
+        Some(pattern) => Some(excluded(pattern)),
+        None => included.then_some(ContextRule::Included),
+    }
+}
+
+/// one line of a gitignore-style file
+struct IgnorePattern {
+    source: String,
+    glob: Vec<char>,
+    negated: bool,
+    directory_only: bool, // a trailing slash: matches directories, so everything below them
+    anchored: bool,       // a leading or inner slash: matches from the root only
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let source = line.trim_end();
+        if source.is_empty() || source.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match source.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, source.strip_prefix('\\').unwrap_or(source)),
+        };
+        let (directory_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let anchored = pattern.trim_start_matches("**/").contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(Self {
+            source: source.to_string(),
+            glob: pattern.chars().collect(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    /// whether the pattern matches `path` itself; directories above it are checked separately
+    fn matches(&self, path: &str, is_directory: bool) -> bool {
+        if self.directory_only && !is_directory {
+            return false;
+        }
+        let candidate = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        glob_match(&self.glob, &candidate.chars().collect::<Vec<_>>())
+    }
+}
+
+/// gitignore globbing: `*` and `?` stay within a directory, `**` crosses them
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // "**/" matches zero or more whole directories, a trailing "**" everything
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            rest.is_empty()
+                || (0..=text.len())
+                    .filter(|&i| i == 0 || text[i - 1] == '/')
+                    .any(|i| glob_match(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != '/') && glob_match(rest, &text[1..])
+        }
+        ['[', rest @ ..] => match character_class(rest) {
+            Some((accepts, after)) => match text.first() {
+                Some(c) if *c != '/' && accepts(*c) => glob_match(after, &text[1..]),
+                _ => false,
+            },
+            // an unclosed bracket is literal
+            None => text.first() == Some(&'[') && glob_match(rest, &text[1..]),
+        },
+        ['\\', literal, rest @ ..] | [literal, rest @ ..] => {
+            text.first() == Some(literal) && glob_match(rest, &text[1..])
+        }
+    }
+}
+
+/// parse `[...]` after its opening bracket into a predicate and the rest of the pattern
+fn character_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool + '_, &[char])> {
+    let (negated, body) = match pattern.first() {
+        Some('!' | '^') => (true, &pattern[1..]),
+        _ => (false, pattern),
+    };
+    // a ']' straight after the opening bracket is part of the class
+    let close = body.iter().skip(1).position(|c| *c == ']')? + 1;
+    let (class, after) = (&body[..close], &body[close + 1..]);
+    let accepts = move |c: char| {
+        let mut found = false;
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                found |= (class[i]..=class[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= class[i] == c;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((accepts, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        glob_match(&pattern, &text.chars().collect::<Vec<_>>())
+    }
+
+    fn rule(lines: &[&str], path: &str) -> Option<ContextRule> {
+        let patterns: Vec<IgnorePattern> = lines
+            .iter()
+            .filter_map(|l| IgnorePattern::parse(l))
+            .collect();
+        ignore_rule(&patterns, path)
+    }
+
+    fn excluded_by(source: &str) -> Option<ContextRule> {
+        Some(ContextRule::Excluded(format!("{IGNORE_FILE}: {source}")))
+    }
+
+    #[test]
+    fn stars_stay_within_a_directory_and_double_stars_cross_them() {
+        assert!(glob("*.json", "data.json"));
+        assert!(!glob("*.json", "fixtures/data.json"));
+        assert!(glob("fixtures/*", "fixtures/data.json"));
+        assert!(!glob("fixtures/*", "fixtures/deep/data.json"));
+        assert!(glob("file?.rs", "file1.rs"));
+        assert!(!glob("file?.rs", "file/.rs"));
+
+        assert!(glob("**/data.json", "data.json"));
+        assert!(glob("**/data.json", "a/b/data.json"));
+        assert!(!glob("**/data.json", "a/bdata.json"));
+        assert!(glob("tests/**/*.json", "tests/x.json"));
+        assert!(glob("tests/**/*.json", "tests/a/b/x.json"));
+        assert!(glob("tests/**", "tests/a/b/x.json"));
+        assert!(!glob("tests/**", "tests"));
+    }
+
+    #[test]
+    fn character_classes_ranges_negation_and_escapes() {
+        assert!(glob("v[0-9].txt", "v7.txt"));
+        assert!(!glob("v[0-9].txt", "vx.txt"));
+        assert!(glob("[abc]", "b"));
+        assert!(glob("[!abc]", "d"));
+        assert!(!glob("[^abc]", "a"));
+        assert!(glob("[]x]", "]"));
+        assert!(!glob("a[/]b", "a/b"));
+        // an unclosed bracket is literal
+        assert!(glob("[abc", "[abc"));
+        assert!(glob("\\*literal", "*literal"));
+        assert!(!glob("\\*literal", "xliteral"));
+    }
+
+    #[test]
+    fn parses_comments_negation_and_trailing_slashes() {
+        assert!(IgnorePattern::parse("# comment").is_none());
+        assert!(IgnorePattern::parse("   ").is_none());
+        assert!(IgnorePattern::parse("/").is_none());
+
+        let pattern = IgnorePattern::parse("!build/  ").unwrap();
+        assert!(pattern.negated && pattern.directory_only && !pattern.anchored);
+        assert_eq!(pattern.source, "!build/");
+
+        let escaped = IgnorePattern::parse("\\!important.md").unwrap();
+        assert!(!escaped.negated);
+        assert!(escaped.matches("docs/!important.md", false));
+    }
+
+    #[test]
+    fn slashes_anchor_a_pattern_to_the_root() {
+        let unanchored = IgnorePattern::parse("snapshot.json").unwrap();
+        assert!(unanchored.matches("snapshot.json", false));
+        assert!(unanchored.matches("a/b/snapshot.json", false));
+
+        for source in ["/snapshot.json", "tests/snapshot.json"] {
+            let anchored = IgnorePattern::parse(source).unwrap();
+            assert!(anchored.anchored, "{source}");
+            assert!(
+                !anchored.matches("src/tests/snapshot.json", false),
+                "{source}"
+            );
+        }
+        assert!(IgnorePattern::parse("/snapshot.json")
+            .unwrap()
+            .matches("snapshot.json", false));
+
+        // a leading **/ keeps the pattern floating
+        let floating = IgnorePattern::parse("**/snapshot.json").unwrap();
+        assert!(!floating.anchored);
+        assert!(floating.matches("a/snapshot.json", false));
+    }
+
+    #[test]
+    fn a_trailing_slash_only_matches_directories() {
+        let pattern = IgnorePattern::parse("__snapshots__/").unwrap();
+        assert!(pattern.matches("src/__snapshots__", true));
+        assert!(!pattern.matches("src/__snapshots__", false));
+
+        assert_eq!(
+            rule(&["__snapshots__/"], "src/__snapshots__/app.snap"),
+            excluded_by("__snapshots__/")
+        );
+        assert_eq!(rule(&["__snapshots__/"], "__snapshots__"), None);
+    }
+
+    #[test]
+    fn the_last_matching_pattern_decides() {
+        let lines = ["tests/fixtures/**/*.json", "!tests/fixtures/schema.json"];
+        assert_eq!(
+            rule(&lines, "tests/fixtures/a/data.json"),
+            excluded_by("tests/fixtures/**/*.json")
+        );
+        assert_eq!(
+            rule(&lines, "tests/fixtures/schema.json"),
+            Some(ContextRule::Included)
+        );
+        assert_eq!(rule(&lines, "src/schema.json"), None);
+        assert_eq!(
+            rule(&["!*.min.js", "*.js"], "app.min.js"),
+            excluded_by("*.js")
+        );
+    }
+
+    #[test]
+    fn files_under_an_excluded_directory_cannot_be_re_included() {
+        let lines = ["vendor/", "!vendor/keep.rs"];
+        assert_eq!(rule(&lines, "vendor/keep.rs"), excluded_by("vendor/"));
+        assert_eq!(rule(&lines, "vendor/deep/other.rs"), excluded_by("vendor/"));
+
+        // excluding the contents rather than the directory leaves room for exceptions
+        let lines = ["vendor/*", "!vendor/keep.rs"];
+        assert_eq!(rule(&lines, "vendor/keep.rs"), Some(ContextRule::Included));
+        assert_eq!(rule(&lines, "vendor/other.rs"), excluded_by("vendor/*"));
+        assert_eq!(
+            rule(&lines, "vendor/deep/other.rs"),
+            excluded_by("vendor/*")
+        );
+    }
+
+    #[test]
+    fn a_re_included_directory_includes_its_files() {
+        let lines = ["generated/", "!generated/"];
+        assert_eq!(
+            rule(&lines, "generated/api.rs"),
+            Some(ContextRule::Included)
+        );
+        let lines = ["!generated/", "generated/*.rs"];
+        assert_eq!(
+            rule(&lines, "generated/api.rs"),
+            excluded_by("generated/*.rs")
+        );
+    }
+
+    #[test]
+    fn the_ignore_file_wins_over_attributes() {
+        let (dir, repo) = test_support::scratch_repo();
+        test_support::write(
+            &dir,
+            ".gitattributes",
+            "*.pb.go linguist-generated\nschema.rs linguist-generated=false\n*.bin binary\n",
+        );
+        test_support::write(&dir, IGNORE_FILE, "docs/\n!api.pb.go\n");
+        let rules = ContextRules::load(&repo);
+
+        assert_eq!(rules.rule_for("api.pb.go"), ContextRule::Included);
+        assert_eq!(
+            rules.rule_for("other.pb.go"),
+            ContextRule::Excluded("linguist-generated in .gitattributes".to_string())
+        );
+        assert_eq!(
+            rules.rule_for("data.bin"),
+            ContextRule::Excluded("binary in .gitattributes".to_string())
+        );
+        assert_eq!(rules.rule_for("schema.rs"), ContextRule::Included);
+        assert_eq!(
+            rules.rule_for("docs/guide.md"),
+            excluded_by("docs/").unwrap()
+        );
+        assert_eq!(rules.rule_for("src/main.rs"), ContextRule::Default);
+    }
+}
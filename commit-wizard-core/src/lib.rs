@@ -2,17 +2,31 @@
 
 // declare modules
 pub mod ai;
+pub mod commit;
+pub mod error;
 pub mod git;
+pub mod http;
+pub mod ignore;
+pub mod lint;
+pub mod reword;
+pub mod secrets;
+pub mod split;
+pub mod usage;
 pub mod utils;
 
+#[cfg(test)]
+mod test_support;
+
 // re-export key structs/functions for external use by other crates
 pub use anyhow::{Context, Result}; // re-export for convenience
 pub use clap::Parser; // re-export Parser for CLI crate
+use clap::Subcommand;
 pub use console::style; // re-export for CLI/NAPI crates if they do printing
 pub use dialoguer::{theme::ColorfulTheme, Select}; // re-export for CLI/NAPI
 pub use dotenv::dotenv;
 pub use indicatif::{ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 pub use std::env;
 use std::fs;
 pub use std::process::Command as StdCommand;
@@ -23,7 +37,11 @@ use std::sync::{
 pub use std::time::Duration;
 
 pub use crate::ai::{generate_conventional_commit, generate_conventional_commit_with_model};
-pub use crate::git::{get_diff_info, get_staged_files, has_staged_changes, DiffInfo, ModifiedFile};
+pub use crate::error::{ValidationRule, WizardError};
+pub use crate::git::{
+    get_amend_diff_info, get_diff_info, get_staged_files, has_staged_changes, DiffInfo, FileStatus,
+    ModifiedFile,
+};
 
 // configuration structure for commit-wizard
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,6 +49,247 @@ pub struct Config {
     pub models: ModelConfig,
     pub current_model: Option<String>, // save user's preferred model
     pub auto_select: bool,             // enable automatic complexity-based selection
+    #[serde(default)]
+    pub profile: Option<String>, // name of the active provider profile
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProviderConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_spend_cap: Option<f64>, // usd; premium models are blocked once reached
+    #[serde(default, skip_serializing_if = "http::HttpConfig::is_default")]
+    pub http: http::HttpConfig, // proxy, certificates, timeouts and retries
+    #[serde(default, skip_serializing_if = "commit::CommitConfig::is_default")]
+    pub commit: commit::CommitConfig, // signing, signoff and hook defaults
+    #[serde(skip)]
+    pub profile_override: Option<String>, // --profile flag, never persisted
+    #[serde(skip)]
+    pub stream: bool, // --stream flag, never persisted
+}
+
+/// which llm backend a profile talks to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderKind {
+    #[default]
+    Openrouter,
+    OpenaiCompatible,
+    Anthropic,
+    Ollama,
+    LlamaCpp,
+}
+
+/// a named provider profile: backend, endpoint, key source and preferred model
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProviderConfig {
+    pub provider: ProviderKind,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub context_length: Option<u32>, // override for backends that don't report one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<bool>, // force json output on or off for every model
+}
+
+impl ProviderConfig {
+    /// api base url: the environment override, then the profile, then the provider's public endpoint
+    pub fn base_url(&self) -> String {
+        if let Some(url) = http::api_base_override() {
+            return url;
+        }
+        if let Some(url) = &self.base_url {
+            return url.clone();
+        }
+        match self.provider {
+            ProviderKind::Openrouter => "https://openrouter.ai/api/v1",
+            ProviderKind::OpenaiCompatible => "https://api.openai.com/v1",
+            ProviderKind::Anthropic => "https://api.anthropic.com/v1",
+            ProviderKind::Ollama => "http://localhost:11434",
+            ProviderKind::LlamaCpp => "http://localhost:8080",
+        }
+        .to_string()
+    }
+
+    /// whether this profile runs models on the local machine
+    pub fn is_local(&self) -> bool {
+        matches!(self.provider, ProviderKind::Ollama | ProviderKind::LlamaCpp)
+    }
+
+    /// local servers work without a key; hosted apis need one unless replaying fixtures
+    pub fn requires_api_key(&self) -> bool {
+        !self.is_local() && !http::is_replaying()
+    }
+
+    /// environment variable holding the api key for this profile, if it uses one
+    pub fn api_key_env(&self) -> Option<String> {
+        if let Some(var) = &self.api_key_env {
+            return Some(var.clone());
+        }
+        match self.provider {
+            ProviderKind::Openrouter => Some("OPENROUTER_API_KEY".to_string()),
+            ProviderKind::OpenaiCompatible => Some("OPENAI_API_KEY".to_string()),
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY".to_string()),
+            ProviderKind::Ollama | ProviderKind::LlamaCpp => None,
+        }
+    }
+
+    /// model used when nothing else has been chosen
+    pub fn default_model(&self) -> Option<&'static str> {
+        match self.provider {
+            ProviderKind::Openrouter => None,
+            ProviderKind::OpenaiCompatible => Some("gpt-4o-mini"),
+            ProviderKind::Anthropic => Some("claude-3-5-haiku-latest"),
+            ProviderKind::Ollama => Some("llama3.1"),
+            // llama.cpp serves whichever model the server was started with
+            ProviderKind::LlamaCpp => Some("default"),
+        }
+    }
+
+    /// context window assumed when neither the profile nor the model catalogue gives one
+    pub fn default_context_length(&self) -> u32 {
+        self.context_length.unwrap_or(match self.provider {
+            // ollama and llama.cpp default to small windows unless started otherwise
+            ProviderKind::Ollama | ProviderKind::LlamaCpp => 4_096,
+            ProviderKind::Openrouter | ProviderKind::OpenaiCompatible => 32_768,
+            ProviderKind::Anthropic => 200_000,
+        })
+    }
+
+    /// human-readable provider label
+    pub fn display_name(&self) -> String {
+        match self.provider {
+            ProviderKind::Openrouter => "openrouter".to_string(),
+            ProviderKind::Anthropic => "anthropic".to_string(),
+            ProviderKind::Ollama => "ollama".to_string(),
+            ProviderKind::LlamaCpp => "llama.cpp".to_string(),
+            ProviderKind::OpenaiCompatible => {
+                // name openai-compatible backends after their host where possible
+                let url = self.base_url();
+                url.split("://")
+                    .nth(1)
+                    .and_then(|rest| rest.split(['/', ':']).next())
+                    .unwrap_or("openai-compatible")
+                    .to_string()
+            }
+        }
+    }
+}
+
+impl Config {
+    /// name of the provider profile in effect, if any
+    pub fn active_profile_name(&self) -> Option<&str> {
+        self.profile_override.as_deref().or(self.profile.as_deref())
+    }
+
+    /// resolve the provider profile in effect (openrouter when none is configured)
+    pub fn active_provider(&self) -> Result<ProviderConfig> {
+        match self.active_profile_name() {
+            Some(name) => self.profiles.get(name).cloned().ok_or_else(|| {
+                WizardError::Config(format!(
+                    "unknown provider profile '{}'. available profiles: {}",
+                    name,
+                    if self.profiles.is_empty() {
+                        "none".to_string()
+                    } else {
+                        self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                    }
+                ))
+                .into()
+            }),
+            None => Ok(ProviderConfig::default()),
+        }
+    }
+
+    /// the user's saved model preference for the active profile
+    pub fn preferred_model(&self) -> Option<String> {
+        match self.active_profile_name() {
+            Some(name) => self.profiles.get(name).and_then(|p| p.model.clone()),
+            None => self.current_model.clone(),
+        }
+    }
+
+    /// save a model preference against the active profile
+    pub fn set_preferred_model(&mut self, model: Option<String>) {
+        let profile = self.active_profile_name().map(str::to_string);
+        match profile.and_then(|name| self.profiles.get_mut(&name)) {
+            Some(provider) => provider.model = model,
+            None => self.current_model = model,
+        }
+    }
+
+    /// context window of a model in tokens: profile override, then the configured
+    /// and cached model catalogues, then the provider's default
+    pub fn context_length(&self, model: &str) -> u32 {
+        let provider = self.active_provider().unwrap_or_default();
+        if let Some(length) = provider.context_length {
+            return length;
+        }
+
+        self.catalogue_lookup(model, |m| m.context_length)
+            .unwrap_or_else(|| provider.default_context_length())
+    }
+
+    /// per-token pricing of a model from the configured or cached model catalogues
+    pub fn model_pricing(&self, model: &str) -> Option<usage::TokenPricing> {
+        self.catalogue_lookup(model, |m| m.pricing).or_else(|| {
+            // openrouter's :free variants cost nothing by definition
+            model.ends_with(":free").then_some(usage::TokenPricing {
+                prompt: 0.0,
+                completion: 0.0,
+            })
+        })
+    }
+
+    /// whether a model can be asked for json matching a schema: profile override,
+    /// then the model catalogues. unknown models get the tagged text format
+    pub fn supports_structured_output(&self, model: &str) -> bool {
+        let provider = self.active_provider().unwrap_or_default();
+        provider
+            .structured_output
+            .or_else(|| self.catalogue_lookup(model, |m| m.structured_output))
+            .unwrap_or(false)
+    }
+
+    /// generation params for a model: its own entry, then the defaults under `[models.params]`
+    pub fn generation_params(&self, model: &str) -> GenerationParams {
+        self.catalogue_lookup(model, |m| (!m.params.is_empty()).then(|| m.params.clone()))
+            .unwrap_or_default()
+            .or(&self.models.params)
+    }
+
+    /// find a detail about a model, preferring the configured list over the cached catalogue
+    fn catalogue_lookup<T>(
+        &self,
+        model: &str,
+        field: impl Fn(&AvailableModel) -> Option<T>,
+    ) -> Option<T> {
+        let lookup =
+            |models: &[AvailableModel]| models.iter().find(|m| m.name == model).and_then(&field);
+        lookup(&self.models.available).or_else(|| {
+            // model details rarely change, so a stale catalogue is still good enough here
+            let is_openrouter = self
+                .active_provider()
+                .is_ok_and(|p| p.provider == ProviderKind::Openrouter);
+            is_openrouter
+                .then(read_cached_models)
+                .and_then(Result::ok)
+                .and_then(|models| lookup(&models))
+        })
+    }
+
+    /// model used when no explicit or complexity-based choice is made
+    pub fn default_model(&self) -> String {
+        match self.active_provider() {
+            Ok(provider) if provider.provider != ProviderKind::Openrouter => provider
+                .model
+                .clone()
+                .or_else(|| provider.default_model().map(str::to_string))
+                .unwrap_or_else(|| self.models.default.clone()),
+            _ => self.models.default.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,16 +299,83 @@ pub struct ModelConfig {
     #[serde(default = "default_model")]
     pub default: String,
     pub available: Vec<AvailableModel>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>, // tried in order when the selected model fails
+    #[serde(default, skip_serializing_if = "GenerationParams::is_empty")]
+    pub params: GenerationParams, // defaults for every model, under any per-model params
 }
 
 fn default_model() -> String {
     "deepseek/deepseek-r1-0528-qwen3-8b:free".to_string()
 }
 
+// r1 models think before answering, which the default 400 token limit doesn't leave room for
+fn reasoning_model_params() -> GenerationParams {
+    GenerationParams {
+        max_tokens: Some(4000),
+        ..GenerationParams::default()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AvailableModel {
     pub name: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>, // tokens, as reported by the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<usage::TokenPricing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<bool>, // honours a json schema in response_format
+    #[serde(default, skip_serializing_if = "GenerationParams::is_empty")]
+    pub params: GenerationParams,
+}
+
+/// sampling and provider settings sent with each request; unset fields use the built-in defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>, // includes any reasoning tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>, // replaces the format's own stop sequence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra: serde_json::Map<String, serde_json::Value>, // merged into the request body as-is
+}
+
+impl GenerationParams {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// these params with any gaps filled from `base`
+    pub fn or(mut self, base: &GenerationParams) -> Self {
+        self.temperature = self.temperature.or(base.temperature);
+        self.max_tokens = self.max_tokens.or(base.max_tokens);
+        self.top_p = self.top_p.or(base.top_p);
+        self.stop = self.stop.or_else(|| base.stop.clone());
+        self.reasoning_effort = self.reasoning_effort.or(base.reasoning_effort);
+        for (key, value) in &base.extra {
+            self.extra
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        self
+    }
+}
+
+/// how hard a reasoning model should think before answering
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
 }
 
 impl Default for Config {
@@ -64,40 +390,81 @@ impl Default for Config {
                         name: "deepseek/deepseek-r1-0528:free".to_string(),
                         description: "deepseek r1 (thinking model - best for complex commits)"
                             .to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: reasoning_model_params(),
                     },
                     AvailableModel {
                         name: "deepseek/deepseek-chat-v3-0324:free".to_string(),
                         description: "deepseek chat v3 (fast model - good for simple commits)"
                             .to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                     AvailableModel {
                         name: "deepseek/deepseek-r1-0528-qwen3-8b:free".to_string(),
                         description: "deepseek r1 qwen3 8b (balanced - free model)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: reasoning_model_params(),
                     },
                     AvailableModel {
                         name: "anthropic/claude-3.5-sonnet".to_string(),
                         description: "claude 3.5 sonnet (premium - high quality)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                     AvailableModel {
                         name: "openai/gpt-4o".to_string(),
                         description: "gpt-4o (premium - balanced performance)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                     AvailableModel {
                         name: "openai/gpt-4o-mini".to_string(),
                         description: "gpt-4o mini (affordable - good quality)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                     AvailableModel {
                         name: "meta-llama/llama-3.1-8b-instruct:free".to_string(),
                         description: "llama 3.1 8b (free - basic quality)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                     AvailableModel {
                         name: "qwen/qwen-2.5-72b-instruct:free".to_string(),
                         description: "qwen 2.5 72b (free - good quality)".to_string(),
+                        context_length: None,
+                        pricing: None,
+                        structured_output: None,
+                        params: GenerationParams::default(),
                     },
                 ],
+                fallbacks: Vec::new(),
+                params: GenerationParams::default(),
             },
             current_model: None, // no saved model initially
             auto_select: false,  // default to not auto-selecting
+            profile: None,       // built-in openrouter profile
+            profiles: BTreeMap::new(),
+            monthly_spend_cap: None,
+            http: http::HttpConfig::default(),
+            commit: commit::CommitConfig::default(),
+            profile_override: None,
+            stream: false,
         }
     }
 }
@@ -145,10 +512,158 @@ pub struct CoreCliArgs {
     /// require AI generation to succeed in --test-diff mode (for CI smoke tests)
     #[arg(long)]
     pub ai_smoke: bool,
+
+    /// provider profile from the config file to use for this run
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// generate the message from local analysis only, without any network access
+    #[arg(long)]
+    pub offline: bool,
+
+    /// stream the message as the model writes it instead of waiting for the full response
+    #[arg(long)]
+    pub stream: bool,
+
+    /// generate this many candidate messages in parallel and pick one from a ranked list
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub candidates: u8,
+
+    /// send the same prompt to these models at once, compare their messages and pick one
+    #[arg(
+        long,
+        value_name = "MODELS",
+        value_delimiter = ',',
+        conflicts_with_all = ["candidates", "offline"]
+    )]
+    pub compare: Vec<String>,
+
+    /// sign the commit with gpg or ssh, whatever git's gpg.format says
+    #[arg(short = 'S', long, conflicts_with = "no_gpg_sign")]
+    pub gpg_sign: bool,
+
+    /// don't sign the commit, even when commit.gpgsign is set
+    #[arg(long)]
+    pub no_gpg_sign: bool,
+
+    /// skip the pre-commit and commit-msg hooks
+    #[arg(short = 'n', long)]
+    pub no_verify: bool,
+
+    /// add a Signed-off-by trailer for the committer
+    #[arg(short = 's', long)]
+    pub signoff: bool,
+
+    /// commit as someone else ("name <email>")
+    #[arg(long, value_name = "AUTHOR")]
+    pub author: Option<String>,
+
+    /// override the author date (e.g. "2024-05-01 14:30", rfc 2822 or a unix timestamp)
+    #[arg(long, value_name = "DATE")]
+    pub date: Option<String>,
+
+    /// regenerate the message of the last commit (plus anything staged since) and amend it
+    #[arg(long, conflicts_with = "test_diff")]
+    pub amend: bool,
+
+    /// split the changes into several atomic commits, each with its own message
+    #[arg(long, conflicts_with_all = ["amend", "compare", "test_diff"])]
+    pub split: bool,
+
+    /// refuse to commit when the staged changes look like they contain secrets
+    #[arg(long)]
+    pub abort_on_secrets: bool,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+impl CoreCliArgs {
+    /// commit options from the flags, falling back to the `[commit]` config defaults
+    pub fn commit_options(&self, config: &commit::CommitConfig) -> commit::CommitOptions {
+        let sign = if self.gpg_sign {
+            Some(true)
+        } else if self.no_gpg_sign {
+            Some(false)
+        } else {
+            config.sign
+        };
+        commit::CommitOptions {
+            sign,
+            no_verify: self.no_verify || config.no_verify,
+            signoff: self.signoff || config.signoff,
+            author: self.author.clone(),
+            date: self.date.clone(),
+            amend: self.amend,
+        }
+    }
+}
+
+/// subcommands that run instead of the commit flow
+#[derive(Subcommand, Debug, Clone)]
+pub enum CliCommand {
+    /// show token usage and spend recorded in the local ledger, by model and by day
+    Usage {
+        /// how many days back to report
+        #[arg(long, default_value = "30", value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+    },
+    /// check existing commit messages against the conventional commit rules
+    Lint {
+        /// commits to check: `a..b`, or a base commit to check everything after it (e.g. origin/main)
+        #[arg(required_unless_present = "message_file")]
+        range: Option<String>,
+        /// check a message file instead, e.g. from a commit-msg hook
+        #[arg(long, value_name = "FILE", conflicts_with = "range")]
+        message_file: Option<String>,
+        /// report format
+        #[arg(long, value_enum, default_value_t = lint::LintFormat::Text)]
+        format: lint::LintFormat,
+    },
+    /// regenerate the messages of existing commits, review them and rewrite the history
+    Reword {
+        /// commits to reword: `a..b`, or a base commit to reword everything after it (e.g. HEAD~5)
+        range: String,
+    },
+}
+
+/// first configured model known to cost nothing, for when the spend cap blocks premium ones
+fn free_fallback_model(config: &Config) -> Option<String> {
+    config
+        .models
+        .available
+        .iter()
+        .map(|m| m.name.clone())
+        .find(|name| config.model_pricing(name).is_some_and(|p| p.is_free()))
+}
+
+/// models to try, in order, after `failed` gives up: the configured fallbacks,
+/// or a safe default when none are configured
+fn fallback_chain(config: &Config, failed: &str) -> Vec<String> {
+    let configured = if config.models.fallbacks.is_empty() {
+        vec![safe_fallback_model(config)]
+    } else {
+        config.models.fallbacks.clone()
+    };
+
+    let mut chain: Vec<String> = Vec::new();
+    for model in configured {
+        if model != failed && !chain.contains(&model) {
+            chain.push(model);
+        }
+    }
+    chain
 }
 
 /// get a safe fallback model that should always work
 fn safe_fallback_model(config: &Config) -> String {
+    // the openrouter catalogue below means nothing to other providers
+    if let Ok(provider) = config.active_provider() {
+        if provider.provider != ProviderKind::Openrouter {
+            return config.default_model();
+        }
+    }
+
     // try in order of preference
     if config
         .models
@@ -183,8 +698,8 @@ fn get_current_model(config: &Config, args: &CoreCliArgs, diff_info: Option<&Dif
     }
 
     // if user has a saved preference, use that
-    if let Some(saved_model) = &config.current_model {
-        return saved_model.clone();
+    if let Some(saved_model) = config.preferred_model() {
+        return saved_model;
     }
 
     // if smart model is enabled and we have diff info, choose based on complexity
@@ -195,6 +710,13 @@ fn get_current_model(config: &Config, args: &CoreCliArgs, diff_info: Option<&Dif
         }
     }
 
+    // non-openrouter profiles fall back to their own default model
+    if let Ok(provider) = config.active_provider() {
+        if provider.provider != ProviderKind::Openrouter {
+            return config.default_model();
+        }
+    }
+
     // fallback to environment variable or default thinking model
     env::var("OPENROUTER_MODEL").unwrap_or_else(|_| config.models.thinking.clone())
 }
@@ -220,27 +742,66 @@ fn get_model_description(config: &Config, model_name: &str) -> String {
 
 // the core commit generation and interaction logic
 pub async fn execute_commit_wizard_flow(args: CoreCliArgs) -> Result<(String, bool)> {
+    // lint needs no config or api key, and its output may be read by a machine
+    if let Some(CliCommand::Lint {
+        range,
+        message_file,
+        format,
+    }) = &args.command
+    {
+        let repo_path = args.path.as_deref().unwrap_or(".");
+        let report = match (message_file, range) {
+            (Some(path), _) => lint::lint_message_file(repo_path, path)?,
+            (None, range) => lint::lint_range(repo_path, range.as_deref().unwrap_or("HEAD"))?,
+        };
+        print!("{}", report.render(*format));
+        report.check()?;
+        return Ok((String::new(), false));
+    }
+
     // load configuration once
     let mut config = load_config()?;
 
+    if let Some(CliCommand::Usage { days }) = &args.command {
+        usage::print_usage_report(&config, *days)?;
+        return Ok((String::new(), false));
+    }
+
+    config.profile_override = args.profile.clone();
+    config.stream = args.stream;
+    let provider = config.active_provider()?;
+
     // centralised API key validation - check both existence and content
+    // (offline mode and local providers never leave the machine and need no key)
     dotenv().ok();
-    let api_key = env::var("OPENROUTER_API_KEY").map_err(|_| {
-        anyhow::anyhow!("OPENROUTER_API_KEY environment variable is not set. please set it with: export OPENROUTER_API_KEY=your-api-key")
-    })?;
+    let needs_key = !args.offline && provider.requires_api_key();
+    if let (true, Some(key_env)) = (needs_key, provider.api_key_env()) {
+        let api_key = env::var(&key_env).map_err(|_| {
+            WizardError::Auth(format!("{key_env} environment variable is not set. please set it with: export {key_env}=your-api-key"))
+        })?;
+
+        // trim the API key and validate
+        if api_key.trim().is_empty() {
+            return Err(WizardError::Auth(format!(
+                "{key_env} is empty. please provide a valid API key"
+            ))
+            .into());
+        }
+    }
 
-    // trim the API key and validate - store trimmed version for actual use
-    let api_key = api_key.trim();
-    if api_key.is_empty() {
-        return Err(anyhow::anyhow!(
-            "OPENROUTER_API_KEY is empty. please provide a valid API key"
-        ));
+    if let Some(CliCommand::Reword { range }) = &args.command {
+        return reword_range(&args, &config, range).await;
     }
 
-    // background refresh of model catalogue (non-blocking)
-    let is_updating_models = Arc::new(AtomicBool::new(true));
+    if args.split {
+        return split_and_commit(&args, &mut config).await;
+    }
+
+    // background refresh of the openrouter model catalogue (non-blocking)
+    let refresh_catalogue = !args.offline && provider.provider == ProviderKind::Openrouter;
+    let is_updating_models = Arc::new(AtomicBool::new(refresh_catalogue));
     let just_updated_models = Arc::new(AtomicBool::new(false));
-    {
+    if refresh_catalogue {
         let updating = is_updating_models.clone();
         let updated = just_updated_models.clone();
         tokio::spawn(async move {
@@ -266,6 +827,11 @@ pub async fn execute_commit_wizard_flow(args: CoreCliArgs) -> Result<(String, bo
         style("ai-powered conventional commit message generator").dim()
     );
 
+    // offline mode has no models to configure, so go straight to generation
+    if args.offline {
+        return run_generate_and_commit_flow(args.clone(), &mut config).await;
+    }
+
     loop {
         // show one-time background update completion notice
         if just_updated_models.swap(false, Ordering::Relaxed) {
@@ -328,7 +894,7 @@ pub async fn execute_commit_wizard_flow(args: CoreCliArgs) -> Result<(String, bo
                         .unwrap_or_else(|_| ProgressStyle::default_spinner()),
                 );
                 pb.enable_steady_tick(Duration::from_millis(80));
-                match fetch_openrouter_models().await {
+                match fetch_provider_models(&config).await {
                     Ok(models) => {
                         pb.finish_and_clear();
                         println!(
@@ -355,6 +921,61 @@ pub async fn execute_commit_wizard_flow(args: CoreCliArgs) -> Result<(String, bo
     }
 }
 
+/// warn about likely secrets in the changes about to be committed. they're already
+/// redacted from the prompt; this decides whether the commit goes ahead at all
+fn check_staged_secrets(args: &CoreCliArgs, config: &Config, diff_info: &DiffInfo) -> Result<()> {
+    let findings: Vec<(&str, &secrets::SecretFinding)> = diff_info
+        .files
+        .iter()
+        .flat_map(|file| file.secrets.iter().map(|s| (file.path.as_str(), s)))
+        .collect();
+    if findings.is_empty() {
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        style("🔐 possible secrets in the staged changes:")
+            .yellow()
+            .bold()
+    );
+    for (path, finding) in &findings {
+        println!(
+            "  {} {} {}",
+            style(format!("{path}:{}", finding.line)).yellow(),
+            finding.kind.label(),
+            style(format!("({})", finding.preview)).dim()
+        );
+    }
+    if !args.offline {
+        println!(
+            "{}",
+            style("these are redacted before anything is sent to the model").dim()
+        );
+    }
+    println!();
+
+    if args.abort_on_secrets || config.commit.abort_on_secrets {
+        return Err(WizardError::SecretsFound {
+            count: findings.len(),
+        }
+        .into());
+    }
+    if args.yes {
+        return Ok(());
+    }
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("commit them anyway?")
+        .default(1)
+        .items(&["yes, they're meant to be committed", "no, abort"])
+        .interact()?;
+    match selection {
+        0 => Ok(()),
+        _ => Err(WizardError::Cancelled.into()),
+    }
+}
+
 /// handles the entire commit generation process
 async fn run_generate_and_commit_flow(
     args: CoreCliArgs,
@@ -365,11 +986,11 @@ async fn run_generate_and_commit_flow(
     // validate git repository early for clearer errors
     let repo = match git2::Repository::discover(&repo_path) {
         Ok(r) => r,
-        Err(e) => return Err(anyhow::anyhow!("invalid git repository: {}", e)),
+        Err(e) => return Err(WizardError::Git(format!("invalid git repository: {e}")).into()),
     };
 
     if repo.is_bare() {
-        return Err(anyhow::anyhow!("bare repositories not supported"));
+        return Err(WizardError::Git("bare repositories not supported".to_string()).into());
     }
     if args.smart_model {
         println!("{}", style("🤖 smart model selection enabled").green());
@@ -379,32 +1000,44 @@ async fn run_generate_and_commit_flow(
         );
     }
 
-    match git::has_staged_changes(&repo_path) {
-        Ok(has_staged) => {
-            if has_staged {
-                if let Ok(files) = git::get_staged_files(&repo_path) {
-                    println!("{}\n", style("staged files:").cyan().bold());
-                    for file in files {
-                        println!("{}", style(format!("  - {file}")).green());
+    if args.amend {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|_| WizardError::Git("there is no commit to amend yet".to_string()))?;
+        println!(
+            "{} {}\n",
+            style("✏️  amending:").cyan().bold(),
+            style(head.summary().unwrap_or_default()).yellow()
+        );
+    } else {
+        match git::has_staged_changes(&repo_path) {
+            Ok(has_staged) => {
+                if has_staged {
+                    if let Ok(files) = git::get_staged_files(&repo_path) {
+                        println!("{}\n", style("staged files:").cyan().bold());
+                        for file in files {
+                            println!("{}", style(format!("  - {file}")).green());
+                        }
+                        println!();
                     }
-                    println!();
+                } else {
+                    println!(
+                        "{}\n",
+                        style("⚠️  no staged changes found, will analyse unstaged changes instead")
+                            .yellow()
+                            .bold()
+                    );
                 }
-            } else {
-                println!(
-                    "{}\n",
-                    style("⚠️  no staged changes found, will analyse unstaged changes instead")
-                        .yellow()
-                        .bold()
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} {}",
+                    style("❌ error checking staged changes:").red().bold(),
+                    style(e).red()
                 );
             }
         }
-        Err(e) => {
-            eprintln!(
-                "{} {}",
-                style("❌ error checking staged changes:").red().bold(),
-                style(e).red()
-            );
-        }
     }
 
     let spinner = ProgressBar::new_spinner();
@@ -418,15 +1051,29 @@ async fn run_generate_and_commit_flow(
     );
     spinner.enable_steady_tick(Duration::from_millis(120));
 
-    let diff_info = git::get_diff_info(
-        &repo_path,
-        args.max_size * 1024,
-        args.max_files,
-        args.verbose,
-    )
+    let diff_info = if args.amend {
+        git::get_amend_diff_info(
+            &repo_path,
+            args.max_size * 1024,
+            args.max_files,
+            args.verbose,
+        )
+    } else {
+        git::get_diff_info(
+            &repo_path,
+            args.max_size * 1024,
+            args.max_files,
+            args.verbose,
+        )
+    }
+    .map_err(|e| match WizardError::find(&e) {
+        Some(_) => e,
+        None => WizardError::Git(format!("{e:#}")).into(),
+    })
     .context("failed to get git diff information")?;
 
     spinner.finish_and_clear();
+    check_staged_secrets(&args, config, &diff_info)?;
 
     if args.verbose {
         println!("found {} modified files", diff_info.files.len());
@@ -439,273 +1086,989 @@ async fn run_generate_and_commit_flow(
     }
 
     if diff_info.files.is_empty() {
-        return Err(anyhow::anyhow!("no changes detected in the repository"));
+        return Err(WizardError::NoChanges.into());
     }
 
     let mut selected_model = get_current_model(config, &args, Some(&diff_info));
-    println!("{}", style("🤖 selected model:").cyan().bold());
-    if config.auto_select {
+    // every generation behind this commit, so its cost can be shown afterwards
+    let mut usage_log: Vec<usage::UsageRecord> = Vec::new();
+    // the model that actually wrote the message, which may be a fallback
+    let mut message_model: Option<String> = None;
+
+    let mut commit_message = if args.offline {
         println!(
-            "{} {}",
-            style(&get_model_description(config, &selected_model)).yellow(),
-            style("(auto-complexity)").dim()
+            "{}\n",
+            style("🧠 offline mode: generating from local analysis (no ai call)")
+                .cyan()
+                .bold()
         );
+        ai::generate_offline_commit(&diff_info)
+            .context("failed to generate offline commit message")?
+    } else if !args.compare.is_empty() {
+        let (message, model) = compare_and_pick(&args, config, &diff_info, &mut usage_log).await?;
+        // regenerating carries on with the model that won
+        selected_model = model.clone();
+        message_model = Some(model);
+        message
+    } else {
+        println!("{}", style("🤖 selected model:").cyan().bold());
+        if config.auto_select {
+            println!(
+                "{} {}",
+                style(&get_model_description(config, &selected_model)).yellow(),
+                style("(auto-complexity)").dim()
+            );
+        } else {
+            println!(
+                "{}",
+                style(&get_model_description(config, &selected_model)).yellow()
+            );
+        }
+        println!();
+
+        match generate_with_fallbacks(&args, config, &diff_info, &selected_model, &mut usage_log)
+            .await
+        {
+            Ok((message, model)) => {
+                message_model = Some(model);
+                message
+            }
+            Err(e) => {
+                if !matches!(
+                    WizardError::find(&e),
+                    Some(WizardError::SpendCapReached { .. })
+                ) {
+                    return Err(e.context("failed to generate commit message"));
+                }
+                let Some(free_model) = free_fallback_model(config) else {
+                    return Err(e.context("no free model is configured to fall back to"));
+                };
+                eprintln!("{} {}", style("💸").yellow(), style(&e).yellow());
+                selected_model = free_model;
+                println!(
+                    "{} {}",
+                    style("🆓 using free model for this commit:").cyan(),
+                    style(&get_model_description(config, &selected_model)).yellow()
+                );
+
+                let (message, model) = generate_with_fallbacks(
+                    &args,
+                    config,
+                    &diff_info,
+                    &selected_model,
+                    &mut usage_log,
+                )
+                .await
+                .context("failed to generate commit message with free model")?;
+                message_model = Some(model);
+                message
+            }
+        }
+    };
+
+    println!(
+        "\n{}\n",
+        style("✅ generated commit message:").green().bold()
+    );
+    println!("{}", style(&commit_message).yellow());
+    print_message_model(message_model.as_deref());
+    println!();
+
+    let mut should_commit_now = args.yes;
+    let mut commit_succeeded = false;
+
+    if !args.yes {
+        println!("{}", style("press ctrl+c at any time to exit").dim());
+
+        loop {
+            // offline messages are deterministic, so regenerating or switching models is moot
+            let options: &[&str] = if args.offline {
+                &["yes, commit this message", "edit this message"]
+            } else {
+                &[
+                    "yes, commit this message",
+                    "edit this message",
+                    "no, regenerate message",
+                    "model settings",
+                ]
+            };
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("what would you like to do?")
+                .default(0)
+                .items(options)
+                .interact()?;
+
+            match selection {
+                0 => {
+                    println!("{}", style("proceeding with commit...").green());
+                    should_commit_now = true;
+                    break;
+                }
+                1 => {
+                    println!("{}", style("opening editor for commit message...").cyan());
+                    if let Some(edited_message) = open_editor_for_message(&commit_message)? {
+                        commit_message = edited_message;
+                        println!("{}", style("commit message updated").green());
+                    } else {
+                        println!(
+                            "{}",
+                            style("edit cancelled, using previous message").yellow()
+                        );
+                    }
+                    println!("\n{}", style("current commit message:").cyan().bold());
+                    println!("{}", style(&commit_message).yellow());
+                    println!();
+                }
+                2 => {
+                    println!("\n{}", style("regenerating...").cyan());
+                    let (message, model) = generate_with_fallbacks(
+                        &args,
+                        config,
+                        &diff_info,
+                        &selected_model,
+                        &mut usage_log,
+                    )
+                    .await
+                    .context("failed to regenerate commit message")?;
+                    commit_message = message;
+                    message_model = Some(model);
+                    println!(
+                        "\n{}\n",
+                        style("✅ newly generated commit message:").green().bold()
+                    );
+                    println!("{}", style(&commit_message).yellow());
+                    print_message_model(message_model.as_deref());
+                    println!("\n{}", style("current commit message:").cyan().bold());
+                    println!("{}", style(&commit_message).yellow());
+                    println!();
+                }
+                3 => {
+                    // model settings
+                    println!("\n{}", style("model settings").cyan().bold());
+
+                    match select_model_interactively(config).await {
+                        Ok(new_model) => {
+                            if new_model == "AUTO_COMPLEXITY" {
+                                config.auto_select = true;
+                                config.set_preferred_model(None);
+                                if let Err(e) = save_config(config) {
+                                    eprintln!(
+                                        "{} {}",
+                                        style(
+                                            "⚠️  warning: failed to save auto-complexity setting:"
+                                        )
+                                        .yellow(),
+                                        e
+                                    );
+                                } else {
+                                    println!(
+                                        "{}",
+                                        style("✅ auto-complexity selection enabled and saved")
+                                            .green()
+                                    );
+                                }
+
+                                selected_model = get_current_model(config, &args, Some(&diff_info));
+                                println!(
+                                    "{} {}",
+                                    style("🤖 auto-selected:").cyan(),
+                                    style(get_model_description(config, &selected_model)).yellow()
+                                );
+                            } else {
+                                config.set_preferred_model(Some(new_model.clone()));
+                                config.auto_select = false;
+                                if let Err(e) = save_config(config) {
+                                    eprintln!(
+                                        "{} {}",
+                                        style("⚠️  warning: failed to save model preference:")
+                                            .yellow(),
+                                        e
+                                    );
+                                } else {
+                                    println!(
+                                        "{} {}",
+                                        style("✅ model preference saved:").green(),
+                                        style(get_model_description(config, &new_model)).yellow()
+                                    );
+                                }
+
+                                selected_model = new_model;
+                            }
+
+                            // regenerate with new model
+                            println!("\n{}", style("regenerating with new model...").cyan());
+                            let (message, model) = generate_with_fallbacks(
+                                &args,
+                                config,
+                                &diff_info,
+                                &selected_model,
+                                &mut usage_log,
+                            )
+                            .await
+                            .context("failed to regenerate commit message with new model")?;
+                            commit_message = message;
+                            message_model = Some(model);
+                            println!(
+                                "\n{}\n",
+                                style("✅ newly generated commit message:").green().bold()
+                            );
+                            println!("{}", style(&commit_message).yellow());
+                            print_message_model(message_model.as_deref());
+                        }
+                        Err(e) => {
+                            if !matches!(WizardError::find(&e), Some(WizardError::Cancelled)) {
+                                eprintln!(
+                                    "{} {}",
+                                    style("⚠️  model selection failed:").yellow(),
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    println!("\n{}", style("current commit message:").cyan().bold());
+                    println!("{}", style(&commit_message).yellow());
+                    println!();
+                }
+                _ => unreachable!(),
+            }
+        }
     } else {
         println!(
             "{}",
-            style(&get_model_description(config, &selected_model)).yellow()
+            style("--yes flag detected, proceeding with generated message automatically.").green()
         );
     }
-    println!();
 
-    let mut commit_message = match ai::generate_conventional_commit_with_model(
-        &diff_info,
+    if should_commit_now {
+        println!("{}", style("executing commit command...").cyan());
+        match commit::create_commit(
+            &repo_path,
+            &commit_message,
+            &args.commit_options(&config.commit),
+        ) {
+            Ok(outcome) => {
+                println!("{}", style("\n✅ commit successful!").green().bold());
+                println!("{}", outcome.describe());
+                usage::print_commit_usage(&usage_log);
+                commit_succeeded = true;
+            }
+            Err(e) => {
+                eprintln!("{}", style("\n❌ commit failed:").red().bold());
+                return Err(e);
+            }
+        }
+    }
+
+    Ok((commit_message, commit_succeeded))
+}
+
+/// generate a single message, or let the user pick from several when --candidates is set
+async fn generate_for_review(
+    args: &CoreCliArgs,
+    config: &Config,
+    diff_info: &DiffInfo,
+    model: &str,
+    usage_log: &mut Vec<usage::UsageRecord>,
+) -> Result<String> {
+    if args.candidates <= 1 {
+        let outcome = ai::generate_commit_outcome(
+            diff_info,
+            args.debug,
+            args.smart_model,
+            Some(model.to_string()),
+            config,
+        )
+        .await?;
+        usage_log.extend(outcome.usage);
+        return Ok(outcome.message);
+    }
+
+    let ai::CandidateSet { candidates, usage } = ai::generate_commit_candidates(
+        diff_info,
         args.debug,
         args.smart_model,
-        Some(selected_model.clone()),
+        Some(model.to_string()),
         config,
+        args.candidates as usize,
     )
-    .await
-    {
-        Ok(message) => message,
-        Err(e) => {
-            let error_str = e.to_string();
-            if error_str.contains("invalid model") {
-                eprintln!("{} {}", style("❌ model error:").red(), e);
-                println!(
-                    "{}",
-                    style("🔧 automatically recovering with safe fallback model...").cyan()
-                );
+    .await?;
+    usage_log.extend(usage);
 
-                let fallback_model = safe_fallback_model(config);
-                selected_model = fallback_model.clone();
+    // --yes takes the best-ranked candidate without asking
+    if args.yes || candidates.len() == 1 {
+        return Ok(candidates[0].message.clone());
+    }
 
-                config.current_model = Some(fallback_model.clone());
-                config.auto_select = false;
-                if let Err(save_err) = save_config(config) {
-                    eprintln!(
-                        "{} failed to save recovery config: {}",
-                        style("⚠️").yellow(),
-                        save_err
-                    );
+    println!(
+        "\n{}",
+        style(format!("📋 {} candidate messages:", candidates.len()))
+            .cyan()
+            .bold()
+    );
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!("\n{}", style(format!("{}.", i + 1)).cyan());
+        println!("{}", style(&candidate.message).yellow());
+        if let Some(error) = &candidate.validation_error {
+            println!("{}", style(format!("⚠️  fails validation: {error}")).red());
+        }
+    }
+    println!();
+
+    let items: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let subject = candidate.message.lines().next().unwrap_or_default();
+            if candidate.is_valid() {
+                format!("{}. {subject}", i + 1)
+            } else {
+                format!("{}. {subject} (invalid)", i + 1)
+            }
+        })
+        .collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("which message would you like to use?")
+        .default(0)
+        .items(&items)
+        .interact()?;
+
+    Ok(candidates[selection].message.clone())
+}
+
+/// run --compare: show every model's message with its latency, cost and validation
+/// result, let the user pick one and optionally keep that model. returns the message
+/// and the model that wrote it
+async fn compare_and_pick(
+    args: &CoreCliArgs,
+    config: &mut Config,
+    diff_info: &DiffInfo,
+    usage_log: &mut Vec<usage::UsageRecord>,
+) -> Result<(String, String)> {
+    let mut models: Vec<String> = Vec::new();
+    for model in args.compare.iter().map(|m| m.trim()) {
+        if !model.is_empty() && !models.iter().any(|m| m == model) {
+            models.push(model.to_string());
+        }
+    }
+    if models.len() < 2 {
+        return Err(WizardError::Config(
+            "--compare needs at least two different models, separated by commas".to_string(),
+        )
+        .into());
+    }
+
+    println!(
+        "{} {}\n",
+        style("⚖️  comparing:").cyan().bold(),
+        style(models.join(", ")).yellow()
+    );
+    let comparisons = ai::compare_models(diff_info, args.debug, &models, config).await?;
+    usage_log.extend(comparisons.iter().filter_map(|c| c.usage.clone()));
+
+    println!(
+        "{}",
+        style(format!("📊 {} models compared:", comparisons.len()))
+            .cyan()
+            .bold()
+    );
+    for (i, comparison) in comparisons.iter().enumerate() {
+        let cost = match comparison.usage.as_ref() {
+            Some(record) => {
+                let tokens = record.prompt_tokens + record.completion_tokens;
+                let approx = if record.estimated { "~" } else { "" };
+                match record.cost {
+                    Some(cost) => format!("{approx}{tokens} tokens · ${cost:.4}"),
+                    None => format!("{approx}{tokens} tokens · cost unknown"),
+                }
+            }
+            None => "no tokens used".to_string(),
+        };
+        println!(
+            "\n{} {}",
+            style(format!("{}. {}", i + 1, comparison.model)).cyan(),
+            style(format!(
+                "({:.1}s · {cost})",
+                comparison.latency.as_secs_f64()
+            ))
+            .dim()
+        );
+        match &comparison.result {
+            Ok(candidate) => {
+                println!("{}", style(&candidate.message).yellow());
+                match &candidate.validation_error {
+                    Some(error) => {
+                        println!("{}", style(format!("⚠️  fails validation: {error}")).red())
+                    }
+                    None => println!("{}", style("✅ passes validation").green()),
+                }
+            }
+            Err(e) => println!("{}", style(format!("❌ failed: {e}")).red()),
+        }
+    }
+    println!();
+
+    let mut answered: Vec<(&str, &ai::CommitCandidate)> = comparisons
+        .iter()
+        .filter_map(|c| Some((c.model.as_str(), c.result.as_ref().ok()?)))
+        .collect();
+    if answered.is_empty() {
+        let first_error = comparisons
+            .into_iter()
+            .find_map(|c| c.result.err())
+            .unwrap_or_else(|| anyhow::anyhow!("no model produced a commit message"));
+        return Err(first_error.context("every compared model failed"));
+    }
+    // valid messages first; --yes takes the top one
+    answered.sort_by_key(|(_, candidate)| !candidate.is_valid());
+
+    let selection = if args.yes {
+        0
+    } else {
+        let items: Vec<String> = answered
+            .iter()
+            .map(|(model, candidate)| {
+                let subject = candidate.message.lines().next().unwrap_or_default();
+                if candidate.is_valid() {
+                    format!("{model}: {subject}")
+                } else {
+                    format!("{model}: {subject} (invalid)")
                 }
+            })
+            .collect();
+        Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("which message would you like to use?")
+            .default(0)
+            .items(&items)
+            .interact()?
+    };
+    let (model, candidate) = answered[selection];
+    let (model, message) = (model.to_string(), candidate.message.clone());
 
+    if !args.yes {
+        let keep = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("save {model} as your preferred model?"))
+            .default(0)
+            .items(&["no, just use it for this commit", "yes, save it"])
+            .interact()?;
+        if keep == 1 {
+            config.set_preferred_model(Some(model.clone()));
+            config.auto_select = false;
+            if let Err(e) = save_config(config) {
+                eprintln!(
+                    "{} {}",
+                    style("⚠️  warning: failed to save model preference:").yellow(),
+                    e
+                );
+            } else {
                 println!(
                     "{} {}",
-                    style("✅ recovered with model:").green(),
-                    style(&get_model_description(config, &selected_model)).yellow()
+                    style("✅ preferred model saved:").green(),
+                    style(&model).yellow()
                 );
+            }
+        }
+    }
 
-                ai::generate_conventional_commit_with_model(
-                    &diff_info,
-                    args.debug,
-                    args.smart_model,
-                    Some(selected_model.clone()),
-                    config,
-                )
-                .await
-                .context("failed to generate commit message even with fallback model")?
-            } else {
-                return Err(e.context("failed to generate commit message"));
+    Ok((message, model))
+}
+
+/// generate with the selected model, walking the fallback chain when it fails in a way
+/// another model might not. returns the message and the model that actually wrote it.
+/// fallbacks only apply to this run and never replace the saved preference
+async fn generate_with_fallbacks(
+    args: &CoreCliArgs,
+    config: &Config,
+    diff_info: &DiffInfo,
+    model: &str,
+    usage_log: &mut Vec<usage::UsageRecord>,
+) -> Result<(String, String)> {
+    let mut last_error = match generate_for_review(args, config, diff_info, model, usage_log).await
+    {
+        Ok(message) => return Ok((message, model.to_string())),
+        Err(e) if ai::warrants_fallback(&e) => e,
+        Err(e) => return Err(e),
+    };
+
+    let mut failed = model.to_string();
+    for fallback in fallback_chain(config, model) {
+        eprintln!(
+            "{} {}",
+            style(format!("⚠️  {failed} failed:")).yellow(),
+            style(&last_error).dim()
+        );
+        println!(
+            "{} {}",
+            style("↪️  falling back to:").cyan(),
+            style(&get_model_description(config, &fallback)).yellow()
+        );
+
+        match generate_for_review(args, config, diff_info, &fallback, usage_log).await {
+            Ok(message) => return Ok((message, fallback)),
+            Err(e) if ai::warrants_fallback(&e) => {
+                last_error = e;
+                failed = fallback;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error)
+}
+
+/// what to do with one commit's proposed message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RewordChoice {
+    Accept,
+    Skip,
+}
+
+/// run `reword <range>`: generate a message per commit from its own diff, let the user
+/// accept, edit or skip each one, then rewrite the history with the accepted ones
+async fn reword_range(args: &CoreCliArgs, config: &Config, range: &str) -> Result<(String, bool)> {
+    let repo_path = args.path.clone().unwrap_or_else(|| ".".to_string());
+    let plan = reword::plan_reword(&repo_path, range, &config.commit.protected_branches())?;
+    let repo = git2::Repository::discover(&repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?;
+
+    println!(
+        "{} {}",
+        style("✏️  rewording").cyan().bold(),
+        style(format!(
+            "{} commit{}",
+            plan.commits.len(),
+            if plan.commits.len() == 1 { "" } else { "s" }
+        ))
+        .yellow()
+    );
+    if !plan.descendants.is_empty() {
+        println!(
+            "{}",
+            style(format!(
+                "{} later commit{} will be rebuilt on top with unchanged messages",
+                plan.descendants.len(),
+                if plan.descendants.len() == 1 { "" } else { "s" }
+            ))
+            .dim()
+        );
+    }
+    println!();
+
+    let mut usage_log: Vec<usage::UsageRecord> = Vec::new();
+    // (commit, original subject, proposed message) in history order
+    let mut proposals: Vec<(git2::Oid, String, Option<String>)> = Vec::new();
+    for (i, id) in plan.commits.iter().enumerate() {
+        let subject = repo
+            .find_commit(*id)
+            .map_err(|e| WizardError::Git(e.message().to_string()))?
+            .summary()
+            .unwrap_or_default()
+            .to_string();
+        println!(
+            "{} {} {}",
+            style(format!("[{}/{}]", i + 1, plan.commits.len())).dim(),
+            style(reword::short(*id)).cyan(),
+            subject
+        );
+
+        let diff_info = match git::get_commit_diff_info(
+            &repo_path,
+            *id,
+            args.max_size * 1024,
+            args.max_files,
+            args.verbose,
+        ) {
+            Ok(diff_info) => diff_info,
+            Err(e) if matches!(WizardError::find(&e), Some(WizardError::NoChanges)) => {
+                println!("{}", style("   empty commit, keeping its message").dim());
+                proposals.push((*id, subject, None));
+                continue;
             }
+            Err(e) => return Err(e),
+        };
+
+        let message = if args.offline {
+            ai::generate_offline_commit(&diff_info)?
+        } else {
+            let model = get_current_model(config, args, Some(&diff_info));
+            generate_with_fallbacks(args, config, &diff_info, &model, &mut usage_log)
+                .await
+                .with_context(|| {
+                    format!("failed to generate a message for {}", reword::short(*id))
+                })?
+                .0
+        };
+        proposals.push((*id, subject, Some(message)));
+    }
+
+    let mut choices: Vec<RewordChoice> = proposals
+        .iter()
+        .map(|(_, _, message)| match message {
+            Some(_) => RewordChoice::Accept,
+            None => RewordChoice::Skip,
+        })
+        .collect();
+    print_reword_table(&proposals, &choices);
+
+    // --yes accepts every proposal as it is
+    if !args.yes {
+        for (i, (id, subject, message)) in proposals.iter_mut().enumerate() {
+            let Some(proposed) = message.as_mut() else {
+                continue;
+            };
+            println!(
+                "\n{} {} {}",
+                style(reword::short(*id)).cyan(),
+                style(subject.as_str()).dim(),
+                style("→").dim()
+            );
+            println!("{}", style(proposed.as_str()).yellow());
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("use this message?")
+                .default(0)
+                .items(&["accept", "edit", "skip (keep the original)"])
+                .interact()?;
+            choices[i] = match selection {
+                0 => RewordChoice::Accept,
+                1 => {
+                    match open_editor_for_message(proposed)? {
+                        Some(edited) => *proposed = edited,
+                        None => println!(
+                            "{}",
+                            style("edit cancelled, using the proposed message").yellow()
+                        ),
+                    }
+                    RewordChoice::Accept
+                }
+                _ => RewordChoice::Skip,
+            };
+        }
+        print_reword_table(&proposals, &choices);
+    }
+
+    let messages: HashMap<git2::Oid, String> = proposals
+        .iter()
+        .zip(&choices)
+        .filter(|(_, choice)| **choice == RewordChoice::Accept)
+        .filter_map(|((id, _, message), _)| message.clone().map(|message| (*id, message)))
+        .collect();
+    if messages.is_empty() {
+        println!(
+            "{}",
+            style("nothing to reword, history left as it is").dim()
+        );
+        return Ok((String::new(), false));
+    }
+
+    if !args.yes {
+        let rewrite = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "rewrite history with {} new message{}?",
+                messages.len(),
+                if messages.len() == 1 { "" } else { "s" }
+            ))
+            .default(0)
+            .items(&["yes, rewrite it", "no, leave history as it is"])
+            .interact()?;
+        if rewrite != 0 {
+            println!("{}", style("history left as it is").dim());
+            return Ok((String::new(), false));
         }
-    };
+    }
 
+    let sign = args.commit_options(&config.commit).sign;
+    let new_head = reword::rewrite_messages(&repo_path, &plan, &messages, sign)?;
     println!(
-        "\n{}\n",
-        style("✅ generated commit message:").green().bold()
+        "\n{} {}",
+        style(format!(
+            "✅ reworded {} commit{}, HEAD is now",
+            messages.len(),
+            if messages.len() == 1 { "" } else { "s" }
+        ))
+        .green()
+        .bold(),
+        style(reword::short(new_head)).cyan()
     );
-    println!("{}", style(&commit_message).yellow());
-    println!();
+    println!("{}", style("undo with: git reset --soft ORIG_HEAD").dim());
+    usage::print_commit_usage(&usage_log);
+    Ok((String::new(), true))
+}
 
-    let mut should_commit_now = args.yes;
-    let mut commit_succeeded = false;
+fn print_reword_table(proposals: &[(git2::Oid, String, Option<String>)], choices: &[RewordChoice]) {
+    println!("\n{}", style("📋 proposed messages:").cyan().bold());
+    for ((id, subject, message), choice) in proposals.iter().zip(choices) {
+        let new_subject = message
+            .as_deref()
+            .and_then(|message| message.lines().next())
+            .unwrap_or_default();
+        match choice {
+            RewordChoice::Accept => println!(
+                "  {} {} {} {}",
+                style(reword::short(*id)).cyan(),
+                style(subject).dim().strikethrough(),
+                style("→").dim(),
+                style(new_subject).green()
+            ),
+            RewordChoice::Skip => println!(
+                "  {} {} {}",
+                style(reword::short(*id)).cyan(),
+                subject,
+                style("(kept)").dim()
+            ),
+        }
+    }
+}
 
-    if !args.yes {
-        println!("{}", style("press ctrl+c at any time to exit").dim());
+/// run --split: cluster the changes into atomic groups, let the user adjust the plan,
+/// then stage and commit each group in turn with its own generated message
+async fn split_and_commit(args: &CoreCliArgs, config: &mut Config) -> Result<(String, bool)> {
+    let repo_path = args.path.clone().unwrap_or_else(|| ".".to_string());
+    let mut snapshot = split::SplitSnapshot::capture(&repo_path)?;
 
+    // every file counts when splitting; each group's prompt is budgeted on its own
+    let diff_info = git::get_diff_info(&repo_path, args.max_size * 1024, usize::MAX, args.verbose)
+        .map_err(|e| match WizardError::find(&e) {
+            Some(_) => e,
+            None => WizardError::Git(format!("{e:#}")).into(),
+        })
+        .context("failed to get git diff information")?;
+
+    let mut groups = ai::cluster_changes(&diff_info);
+    if groups.len() < 2 {
+        println!(
+            "{}\n",
+            style("these changes already look atomic, making a single commit").dim()
+        );
+        return run_generate_and_commit_flow(args.clone(), config).await;
+    }
+    check_staged_secrets(args, config, &diff_info)?;
+
+    // --yes takes the proposed plan as it is
+    if !args.yes {
         loop {
-            let options = &[
-                "yes, commit this message",
-                "edit this message",
-                "no, regenerate message",
-                "model settings",
+            // with every file left out there's nothing to commit, same as cancelling
+            if groups.is_empty() {
+                println!(
+                    "{}",
+                    style("every file was left out, nothing was committed").dim()
+                );
+                return Ok((String::new(), false));
+            }
+            print_split_plan(&groups);
+            let mut actions = vec![
+                "commit these groups in order",
+                "move a file to another group",
             ];
+            if groups.len() > 1 {
+                actions.push("merge two groups");
+            }
+            actions.extend(["leave a file out", "cancel"]);
             let selection = Select::with_theme(&ColorfulTheme::default())
                 .with_prompt("what would you like to do?")
                 .default(0)
-                .items(options)
+                .items(&actions)
                 .interact()?;
-
-            match selection {
-                0 => {
-                    println!("{}", style("proceeding with commit...").green());
-                    should_commit_now = true;
-                    break;
+            match actions[selection] {
+                "commit these groups in order" => break,
+                "move a file to another group" => move_split_file(&mut groups)?,
+                "merge two groups" => merge_split_groups(&mut groups)?,
+                "leave a file out" => {
+                    let (group, file) =
+                        pick_split_file(&groups, "which file should stay uncommitted?")?;
+                    let path = groups[group].files[file].clone();
+                    groups[group].remove(&path);
                 }
-                1 => {
-                    println!("{}", style("opening editor for commit message...").cyan());
-                    if let Some(edited_message) = open_editor_for_message(&commit_message)? {
-                        commit_message = edited_message;
-                        println!("{}", style("commit message updated").green());
-                    } else {
-                        println!(
-                            "{}",
-                            style("edit cancelled, using previous message").yellow()
-                        );
-                    }
-                    println!("\n{}", style("current commit message:").cyan().bold());
-                    println!("{}", style(&commit_message).yellow());
-                    println!();
+                _ => {
+                    println!("{}", style("split cancelled, nothing was committed").dim());
+                    return Ok((String::new(), false));
                 }
-                2 => {
-                    println!("\n{}", style("regenerating...").cyan());
-                    commit_message = ai::generate_conventional_commit_with_model(
-                        &diff_info,
-                        args.debug,
-                        args.smart_model,
-                        Some(selected_model.clone()),
-                        config,
-                    )
+            }
+            groups.retain(|group| !group.files.is_empty());
+        }
+    }
+
+    let mut usage_log: Vec<usage::UsageRecord> = Vec::new();
+    let mut commits = 0;
+    let result: Result<()> = async {
+        for (i, group) in groups.iter().enumerate() {
+            println!(
+                "\n{} {} {}",
+                style(format!("[{}/{}]", i + 1, groups.len())).dim(),
+                style(&group.label).cyan().bold(),
+                style(group.files.join(", ")).dim()
+            );
+            let group_info = diff_info.subset(&group.files, &group.hunks);
+
+            let mut message = if args.offline {
+                ai::generate_offline_commit(&group_info)?
+            } else {
+                let model = get_current_model(config, args, Some(&group_info));
+                generate_with_fallbacks(args, config, &group_info, &model, &mut usage_log)
                     .await
-                    .context("failed to regenerate commit message")?;
-                    println!(
-                        "\n{}\n",
-                        style("✅ newly generated commit message:").green().bold()
-                    );
-                    println!("{}", style(&commit_message).yellow());
-                    println!("\n{}", style("current commit message:").cyan().bold());
-                    println!("{}", style(&commit_message).yellow());
-                    println!();
+                    .with_context(|| format!("failed to generate a message for {}", group.label))?
+                    .0
+            };
+            println!("{}", style(&message).yellow());
+
+            if !args.yes {
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("commit this group?")
+                    .default(0)
+                    .items(&["yes, commit it", "edit this message", "skip this group"])
+                    .interact()?;
+                match selection {
+                    0 => {}
+                    1 => match open_editor_for_message(&message)? {
+                        Some(edited) => message = edited,
+                        None => println!(
+                            "{}",
+                            style("edit cancelled, using the generated message").yellow()
+                        ),
+                    },
+                    _ => continue,
                 }
-                3 => {
-                    // model settings
-                    println!("\n{}", style("model settings").cyan().bold());
+            }
 
-                    match select_model_interactively(config).await {
-                        Ok(new_model) => {
-                            if new_model == "AUTO_COMPLEXITY" {
-                                config.auto_select = true;
-                                config.current_model = None;
-                                if let Err(e) = save_config(config) {
-                                    eprintln!(
-                                        "{} {}",
-                                        style(
-                                            "⚠️  warning: failed to save auto-complexity setting:"
-                                        )
-                                        .yellow(),
-                                        e
-                                    );
-                                } else {
-                                    println!(
-                                        "{}",
-                                        style("✅ auto-complexity selection enabled and saved")
-                                            .green()
-                                    );
-                                }
+            snapshot.stage(&repo_path, &diff_info, group)?;
+            let outcome =
+                commit::create_commit(&repo_path, &message, &args.commit_options(&config.commit))?;
+            println!("{}", style(outcome.describe()).green());
+            snapshot.mark_committed(&diff_info, group);
+            commits += 1;
+        }
+        Ok(())
+    }
+    .await;
 
-                                selected_model = get_current_model(config, &args, Some(&diff_info));
-                                println!(
-                                    "{} {}",
-                                    style("🤖 auto-selected:").cyan(),
-                                    style(get_model_description(config, &selected_model)).yellow()
-                                );
-                            } else {
-                                config.current_model = Some(new_model.clone());
-                                config.auto_select = false;
-                                if let Err(e) = save_config(config) {
-                                    eprintln!(
-                                        "{} {}",
-                                        style("⚠️  warning: failed to save model preference:")
-                                            .yellow(),
-                                        e
-                                    );
-                                } else {
-                                    println!(
-                                        "{} {}",
-                                        style("✅ model preference saved:").green(),
-                                        style(get_model_description(config, &new_model)).yellow()
-                                    );
-                                }
+    // whatever wasn't committed goes back to how it was staged, even after a failure
+    snapshot.restore(&repo_path)?;
+    result?;
 
-                                selected_model = new_model;
-                            }
+    println!(
+        "\n{}",
+        style(format!(
+            "✅ split into {commits} commit{}",
+            if commits == 1 { "" } else { "s" }
+        ))
+        .green()
+        .bold()
+    );
+    usage::print_commit_usage(&usage_log);
+    Ok((String::new(), commits > 0))
+}
 
-                            // regenerate with new model
-                            println!("\n{}", style("regenerating with new model...").cyan());
-                            commit_message = ai::generate_conventional_commit_with_model(
-                                &diff_info,
-                                args.debug,
-                                args.smart_model,
-                                Some(selected_model.clone()),
-                                config,
-                            )
-                            .await
-                            .context("failed to regenerate commit message with new model")?;
-                            println!(
-                                "\n{}\n",
-                                style("✅ newly generated commit message:").green().bold()
-                            );
-                            println!("{}", style(&commit_message).yellow());
-                        }
-                        Err(e) => {
-                            if e.to_string() != "cancelled" {
-                                eprintln!(
-                                    "{} {}",
-                                    style("⚠️  model selection failed:").yellow(),
-                                    e
-                                );
-                            }
-                        }
-                    }
-                    println!("\n{}", style("current commit message:").cyan().bold());
-                    println!("{}", style(&commit_message).yellow());
-                    println!();
-                }
-                _ => unreachable!(),
-            }
-        }
-    } else {
+fn print_split_plan(groups: &[ai::ChangeGroup]) {
+    println!("\n{}", style("📋 proposed commits:").cyan().bold());
+    for (i, group) in groups.iter().enumerate() {
         println!(
-            "{}",
-            style("--yes flag detected, proceeding with generated message automatically.").green()
+            "  {} {}",
+            style(format!("{}.", i + 1)).cyan(),
+            style(&group.label).bold()
         );
+        for file in &group.files {
+            println!("     {}", style(split_file_name(group, file)).dim());
+        }
     }
+    println!();
+}
 
-    if should_commit_now {
-        println!("{}", style("executing commit command...").cyan());
-        let repo_dir_path = if repo_path == "." {
-            env::current_dir().context("Failed to get current directory")?
-        } else {
-            std::path::PathBuf::from(&repo_path)
-        };
+/// pick a file from the plan, returning its group and position
+fn pick_split_file(groups: &[ai::ChangeGroup], prompt: &str) -> Result<(usize, usize)> {
+    let files: Vec<(usize, usize)> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(g, group)| (0..group.files.len()).map(move |f| (g, f)))
+        .collect();
+    let items: Vec<String> = files
+        .iter()
+        .map(|&(g, f)| {
+            format!(
+                "{} ({})",
+                split_file_name(&groups[g], &groups[g].files[f]),
+                groups[g].label
+            )
+        })
+        .collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(0)
+        .items(&items)
+        .interact()?;
+    Ok(files[selection])
+}
 
-        let output = StdCommand::new("git")
-            .current_dir(repo_dir_path)
-            .args(["commit", "-m", &commit_message])
-            .output()
-            .context("failed to execute git commit command")?;
-
-        if output.status.success() {
-            println!("{}", style("\n✅ commit successful!").green().bold());
-            if let Ok(stdout) = String::from_utf8(output.stdout) {
-                if !stdout.trim().is_empty() {
-                    println!("{stdout}");
-                }
-            }
-            commit_succeeded = true;
-        } else {
-            eprintln!("{}", style("\n❌ commit failed:").red().bold());
-            if let Ok(stderr) = String::from_utf8(output.stderr) {
-                if !stderr.trim().is_empty() {
-                    eprintln!("{stderr}");
-                }
-            }
-            return Err(anyhow::anyhow!("git commit command failed"));
+fn move_split_file(groups: &mut Vec<ai::ChangeGroup>) -> Result<()> {
+    let (from, file) = pick_split_file(groups, "which file?")?;
+    let mut targets: Vec<String> = groups.iter().map(|g| g.label.clone()).collect();
+    targets.push("a new group of its own".to_string());
+    let to = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("move it to")
+        .default(0)
+        .items(&targets)
+        .interact()?;
+    if to == from {
+        return Ok(());
+    }
+    let path = groups[from].files[file].clone();
+    let hunks = groups[from].remove(&path);
+    match groups.get_mut(to) {
+        Some(group) => group.add(&path, hunks),
+        None => {
+            let mut group = ai::ChangeGroup {
+                label: path.clone(),
+                ..ai::ChangeGroup::default()
+            };
+            group.add(&path, hunks);
+            groups.push(group);
         }
     }
+    Ok(())
+}
 
-    Ok((commit_message, commit_succeeded))
+fn merge_split_groups(groups: &mut Vec<ai::ChangeGroup>) -> Result<()> {
+    if groups.len() < 2 {
+        return Ok(());
+    }
+    let labels: Vec<String> = groups.iter().map(|g| g.label.clone()).collect();
+    let into = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("merge into")
+        .default(0)
+        .items(&labels)
+        .interact()?;
+    let others: Vec<usize> = (0..groups.len()).filter(|&i| i != into).collect();
+    let other_labels: Vec<&String> = others.iter().map(|&i| &labels[i]).collect();
+    let picked = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("merge which group into {}?", labels[into]))
+        .default(0)
+        .items(&other_labels)
+        .interact()?;
+
+    let merged = groups.remove(others[picked]);
+    let into = if others[picked] < into {
+        into - 1
+    } else {
+        into
+    };
+    groups[into].label = format!("{} + {}", groups[into].label, merged.label);
+    for path in &merged.files {
+        groups[into].add(path, merged.hunks.get(path).cloned());
+    }
+    Ok(())
+}
+
+/// a file as listed in the plan, noting when only some of its hunks are in the group
+fn split_file_name(group: &ai::ChangeGroup, file: &str) -> String {
+    match group.hunks.get(file).map(Vec::len) {
+        Some(1) => format!("{file} (1 hunk)"),
+        Some(count) => format!("{file} ({count} hunks)"),
+        None => file.to_string(),
+    }
+}
+
+/// note which model wrote the message, since a fallback may have stepped in
+fn print_message_model(model: Option<&str>) {
+    if let Some(model) = model {
+        println!("{} {}", style("🤖 written by:").dim(), style(model).dim());
+    }
 }
 
 /// handles interactive model settings changes
@@ -724,7 +2087,7 @@ async fn handle_model_settings(config: &mut Config, args: &CoreCliArgs) -> Resul
         Ok(new_model) => {
             if new_model == "AUTO_COMPLEXITY" {
                 config.auto_select = true;
-                config.current_model = None;
+                config.set_preferred_model(None);
                 if let Err(e) = save_config(config) {
                     eprintln!(
                         "{} {}",
@@ -738,7 +2101,7 @@ async fn handle_model_settings(config: &mut Config, args: &CoreCliArgs) -> Resul
                     );
                 }
             } else {
-                config.current_model = Some(new_model.clone());
+                config.set_preferred_model(Some(new_model.clone()));
                 config.auto_select = false;
                 if let Err(e) = save_config(config) {
                     eprintln!(
@@ -757,7 +2120,7 @@ async fn handle_model_settings(config: &mut Config, args: &CoreCliArgs) -> Resul
             }
         }
         Err(e) => {
-            if e.to_string() != "cancelled" {
+            if !matches!(WizardError::find(&e), Some(WizardError::Cancelled)) {
                 eprintln!("{} {}", style("⚠️  model selection failed:").yellow(), e);
             }
         }
@@ -872,11 +2235,13 @@ async fn select_model_interactively(config: &Config) -> Result<String> {
 
     println!("{}", style("🤖 model selection").cyan().bold());
 
+    let provider_name = config.active_provider()?.display_name();
+
     // first, ask what they want to do
     let main_options = vec![
-        "select from configured models",
-        "browse all openrouter models",
-        "enable auto-complexity selection",
+        "select from configured models".to_string(),
+        format!("browse all {provider_name} models"),
+        "enable auto-complexity selection".to_string(),
     ];
 
     let main_choice = Select::with_theme(&ColorfulTheme::default())
@@ -890,7 +2255,7 @@ async fn select_model_interactively(config: &Config) -> Result<String> {
             // select from configured models
             let models = ai::get_available_models(config);
             if models.is_empty() {
-                return Err(anyhow::anyhow!("no models configured"));
+                return Err(WizardError::Config("no models configured".to_string()).into());
             }
 
             let model_descriptions: Vec<&str> = models.iter().map(|(_, desc)| *desc).collect();
@@ -904,8 +2269,8 @@ async fn select_model_interactively(config: &Config) -> Result<String> {
             Ok(models[selection].0.to_string())
         }
         1 => {
-            // browse all openrouter models with search
-            browse_openrouter_models().await
+            // browse every model the provider offers, with search
+            browse_provider_models(config).await
         }
         2 => {
             // enable auto-complexity selection (return special marker)
@@ -916,21 +2281,28 @@ async fn select_model_interactively(config: &Config) -> Result<String> {
     }
 }
 
-/// browse openrouter models with search functionality
-async fn browse_openrouter_models() -> Result<String> {
-    println!("{}", style("🔄 fetching models from openrouter...").cyan());
+/// browse the active provider's models with search functionality
+async fn browse_provider_models(config: &Config) -> Result<String> {
+    let provider_name = config.active_provider()?.display_name();
+    println!(
+        "{}",
+        style(format!("🔄 fetching models from {provider_name}...")).cyan()
+    );
 
-    let all_models = match fetch_openrouter_models().await {
+    let all_models = match fetch_provider_models(config).await {
         Ok(models) => models,
         Err(e) => {
             eprintln!("{} {}", style("⚠️  failed to fetch models:").yellow(), e);
             println!("{}", style("falling back to configured models").dim());
-            return Err(anyhow::anyhow!("failed to fetch openrouter models"));
+            return Err(anyhow::anyhow!("failed to fetch {} models", provider_name));
         }
     };
 
     if all_models.is_empty() {
-        return Err(anyhow::anyhow!("no models available from openrouter"));
+        return Err(anyhow::anyhow!(
+            "no models available from {}",
+            provider_name
+        ));
     }
 
     println!(
@@ -1157,16 +2529,14 @@ async fn intelligent_model_search(all_models: &[AvailableModel]) -> Result<Strin
         // handle input
         if let Event::Key(KeyEvent { code, .. }) = event::read()? {
             match code {
-                KeyCode::Up => {
-                    if !filtered_models.is_empty() && current_selection > 0 {
-                        current_selection -= 1;
-                    }
+                KeyCode::Up if !filtered_models.is_empty() && current_selection > 0 => {
+                    current_selection -= 1;
                 }
-                KeyCode::Down => {
-                    if !filtered_models.is_empty() && current_selection < filtered_models.len() - 1
-                    {
-                        current_selection += 1;
-                    }
+                KeyCode::Down
+                    if !filtered_models.is_empty()
+                        && current_selection < filtered_models.len() - 1 =>
+                {
+                    current_selection += 1;
                 }
                 KeyCode::Enter => {
                     if !filtered_models.is_empty() {
@@ -1179,14 +2549,12 @@ async fn intelligent_model_search(all_models: &[AvailableModel]) -> Result<Strin
                     }
                 }
                 KeyCode::Esc => {
-                    break Err(anyhow::anyhow!("cancelled"));
+                    break Err(WizardError::Cancelled.into());
                 }
-                KeyCode::Backspace => {
-                    if !search_query.is_empty() {
-                        search_query.pop();
-                        filtered_models = intelligent_filter(&model_names, &search_query);
-                        current_selection = 0;
-                    }
+                KeyCode::Backspace if !search_query.is_empty() => {
+                    search_query.pop();
+                    filtered_models = intelligent_filter(&model_names, &search_query);
+                    current_selection = 0;
                 }
                 KeyCode::Char(c) => {
                     search_query.push(c);
@@ -1291,7 +2659,7 @@ fn intelligent_filter(models: &[String], query: &str) -> Vec<String> {
         .collect();
 
     // sort by score (highest first)
-    scored_models.sort_by(|a, b| b.1.cmp(&a.1));
+    scored_models.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
 
     // return just the model names
     scored_models.into_iter().map(|(model, _)| model).collect()
@@ -1382,7 +2750,13 @@ pub fn load_config() -> Result<Config> {
 
     if config_path.exists() {
         let content = fs::read_to_string(&config_path).context("failed to read config file")?;
-        let config: Config = toml::from_str(&content).context("failed to parse config file")?;
+        let config: Config = toml::from_str(&content).map_err(|e| {
+            WizardError::Config(format!(
+                "failed to parse config file {}: {e}",
+                config_path.display()
+            ))
+        })?;
+        http::configure(&config.http);
         Ok(config)
     } else {
         // create default config file
@@ -1414,17 +2788,22 @@ pub fn save_config(config: &Config) -> Result<()> {
     Ok(())
 }
 
-/// get the path to the config file
-fn get_config_path() -> Result<std::path::PathBuf> {
+/// get the directory holding the config, model cache and usage ledger
+pub(crate) fn commit_wizard_dir() -> Result<std::path::PathBuf> {
     let config_dir = if let Ok(xdg_config) = env::var("XDG_CONFIG_HOME") {
         std::path::PathBuf::from(xdg_config)
     } else if let Ok(home) = env::var("HOME") {
         std::path::PathBuf::from(home).join(".config")
     } else {
-        return Err(anyhow::anyhow!("could not determine config directory"));
+        return Err(WizardError::Config("could not determine config directory".to_string()).into());
     };
 
-    Ok(config_dir.join("commit-wizard").join("config.toml"))
+    Ok(config_dir.join("commit-wizard"))
+}
+
+/// get the path to the config file
+fn get_config_path() -> Result<std::path::PathBuf> {
+    Ok(commit_wizard_dir()?.join("config.toml"))
 }
 
 // openrouter api structures for model fetching
@@ -1435,6 +2814,10 @@ struct OpenRouterModel {
     name: String,
     description: Option<String>,
     pricing: Option<ModelPricing>,
+    #[serde(default)]
+    context_length: Option<u32>,
+    #[serde(default)]
+    supported_parameters: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -1443,11 +2826,31 @@ struct ModelPricing {
     completion: String,
 }
 
+impl ModelPricing {
+    /// openrouter quotes usd per token as decimal strings
+    fn per_token(&self) -> Option<usage::TokenPricing> {
+        Some(usage::TokenPricing {
+            prompt: self.prompt.parse().ok()?,
+            completion: self.completion.parse().ok()?,
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct OpenRouterModelsResponse {
     data: Vec<OpenRouterModel>,
 }
 
+/// fetch the models offered by the active provider (openrouter results are cached)
+pub async fn fetch_provider_models(config: &Config) -> Result<Vec<AvailableModel>> {
+    let provider = config.active_provider()?;
+    if provider.provider == ProviderKind::Openrouter {
+        return fetch_openrouter_models().await;
+    }
+
+    ai::build_provider(&provider)?.list_models().await
+}
+
 /// fetch available models from openrouter api with caching
 pub async fn fetch_openrouter_models() -> Result<Vec<AvailableModel>> {
     // check for cached models first
@@ -1458,14 +2861,17 @@ pub async fn fetch_openrouter_models() -> Result<Vec<AvailableModel>> {
     let api_key = env::var("OPENROUTER_API_KEY")
         .context("OPENROUTER_API_KEY environment variable is not set")?;
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://openrouter.ai/api/v1/models")
-        .header("Authorization", format!("Bearer {api_key}"))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .context("failed to fetch models from openrouter api")?;
+    let client = http::client()?;
+    let url = format!("{}/models", ProviderConfig::default().base_url());
+    let response = http::send(
+        client
+            .get(url)
+            .timeout(http::settings().timeout_or(Duration::from_secs(30)))
+            .header("Authorization", format!("Bearer {api_key}"))
+            .header("Content-Type", "application/json"),
+    )
+    .await
+    .context("failed to fetch models from openrouter api")?;
 
     let models_response: OpenRouterModelsResponse = response
         .json()
@@ -1478,6 +2884,15 @@ pub async fn fetch_openrouter_models() -> Result<Vec<AvailableModel>> {
         available_models.push(AvailableModel {
             name: model.id,
             description,
+            context_length: model.context_length,
+            pricing: model.pricing.as_ref().and_then(ModelPricing::per_token),
+            structured_output: (!model.supported_parameters.is_empty()).then(|| {
+                model
+                    .supported_parameters
+                    .iter()
+                    .any(|p| p == "structured_outputs")
+            }),
+            params: GenerationParams::default(),
         });
     }
 
@@ -1530,7 +2945,12 @@ fn load_cached_models() -> Result<Vec<AvailableModel>> {
         return Err(anyhow::anyhow!("cache expired"));
     }
 
-    let content = fs::read_to_string(&cache_path)?;
+    read_cached_models()
+}
+
+/// read the models cache regardless of its age
+fn read_cached_models() -> Result<Vec<AvailableModel>> {
+    let content = fs::read_to_string(get_models_cache_path()?)?;
     let cached_models: Vec<AvailableModel> = serde_json::from_str(&content)?;
 
     Ok(cached_models)
@@ -1553,15 +2973,7 @@ fn save_cached_models(models: &[AvailableModel]) -> Result<()> {
 
 /// get the path to the models cache file
 fn get_models_cache_path() -> Result<std::path::PathBuf> {
-    let config_dir = if let Ok(xdg_config) = env::var("XDG_CONFIG_HOME") {
-        std::path::PathBuf::from(xdg_config)
-    } else if let Ok(home) = env::var("HOME") {
-        std::path::PathBuf::from(home).join(".config")
-    } else {
-        return Err(anyhow::anyhow!("could not determine config directory"));
-    };
-
-    Ok(config_dir.join("commit-wizard").join("models_cache.json"))
+    Ok(commit_wizard_dir()?.join("models_cache.json"))
 }
 
 /// test git diff processing without user interaction (for automated testing)
@@ -1577,11 +2989,10 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
     let _repo = match Repository::open(repo_path) {
         Ok(repo) => repo,
         Err(e) => {
-            return Err(anyhow::anyhow!(
-                "failed to open git repository at '{}': {}",
-                repo_path,
-                e
-            ));
+            return Err(WizardError::Git(format!(
+                "failed to open git repository at '{repo_path}': {e}"
+            ))
+            .into());
         }
     };
 
@@ -1643,13 +3054,34 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
         println!("\n{}", style("📝 detailed file analysis:").cyan());
         for (i, file) in diff_info.files.iter().enumerate() {
             println!(
-                "  {}. {} (+{} -{}) [{:?}]",
+                "  {}. {} (+{} -{}) [{:?}]{}",
                 i + 1,
                 file.path,
                 file.added_lines,
                 file.removed_lines,
-                file.file_type
+                file.file_type,
+                file.submodule
+                    .as_ref()
+                    .map(|submodule| submodule.describe())
+                    .or_else(|| file.status.describe())
+                    .map(|status| format!(" - {status}"))
+                    .unwrap_or_default()
             );
+            if let ignore::ContextRule::Excluded(rule) = &file.context {
+                println!("     {}", style(format!("content withheld: {rule}")).dim());
+            }
+            for finding in &file.secrets {
+                println!(
+                    "     {}",
+                    style(format!(
+                        "possible secret at line {}: {} ({})",
+                        finding.line,
+                        finding.kind.label(),
+                        finding.preview
+                    ))
+                    .yellow()
+                );
+            }
             if !file.change_hints.is_empty() {
                 let hint_strings: Vec<String> =
                     file.change_hints.iter().map(|h| format!("{h:?}")).collect();
@@ -1697,7 +3129,7 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
     );
 
     // load config for AI generation
-    let config = match load_config() {
+    let mut config = match load_config() {
         Ok(config) => config,
         Err(e) => {
             println!(
@@ -1709,19 +3141,51 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
         }
     };
 
+    config.profile_override = args.profile.clone();
+    config.stream = args.stream;
+
+    if args.offline {
+        let commit_message = crate::ai::generate_offline_commit(&diff_info)
+            .context("offline commit generation failed")?;
+        println!("✅ offline commit message generation successful!");
+        println!("\n{}", style("📝 generated commit message:").green().bold());
+        println!("{}", style("─".repeat(50)).dim());
+        println!("{}", style(&commit_message).yellow());
+        println!("{}", style("─".repeat(50)).dim());
+        println!(
+            "\n{}",
+            style("🎉 all tests passed! offline generation is working correctly.")
+                .green()
+                .bold()
+        );
+        return Ok((commit_message, false));
+    }
+
     // load .env file for testing
     dotenv().ok();
 
-    // check for API key
-    let api_key = std::env::var("OPENROUTER_API_KEY").ok();
-    if api_key.is_none() || api_key.as_ref().unwrap().trim().is_empty() {
+    // check for API key (local providers don't need one)
+    let provider = config.active_provider()?;
+    let key_env = provider.api_key_env().unwrap_or_default();
+    let api_key = std::env::var(&key_env).ok();
+    if provider.requires_api_key()
+        && (api_key.is_none() || api_key.as_ref().unwrap().trim().is_empty())
+    {
         println!(
             "{}",
-            style("⚠️  openrouter_api_key not set, skipping ai generation test").yellow()
+            style(format!(
+                "⚠️  {} not set, skipping ai generation test",
+                key_env.to_lowercase()
+            ))
+            .yellow()
         );
         println!(
             "{}",
-            style("💡 set openrouter_api_key environment variable to test ai generation").dim()
+            style(format!(
+                "💡 set {} environment variable to test ai generation",
+                key_env.to_lowercase()
+            ))
+            .dim()
         );
 
         println!(
@@ -1752,9 +3216,14 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
         }
         Err(e) => {
             if args.ai_smoke {
-                return Err(e.context(
-                    "ai smoke test failed (set OPENROUTER_API_KEY or disable --ai-smoke)",
-                ));
+                let hint = if http::is_replaying() {
+                    format!("{e}")
+                } else if provider.requires_api_key() {
+                    format!("set {key_env} or disable --ai-smoke")
+                } else {
+                    format!("is the {} server running?", provider.display_name())
+                };
+                return Err(e.context(format!("ai smoke test failed ({hint})")));
             }
             println!("{}", style(&format!("❌ ai generation failed: {e}")).red());
             println!(
@@ -1798,34 +3267,20 @@ async fn test_git_diff_processing(args: &CoreCliArgs) -> Result<(String, bool)>
                 .bold()
         );
 
-        let repo_dir_path = if repo_path == "." {
-            std::env::current_dir().context("Failed to get current directory")?
-        } else {
-            std::path::PathBuf::from(&repo_path)
-        };
-
-        let output = std::process::Command::new("git")
-            .current_dir(repo_dir_path)
-            .args(["commit", "-m", &commit_message])
-            .output()
-            .context("Failed to execute git commit command")?;
-
-        if output.status.success() {
-            println!("{}", style("✅ commit successful!").green().bold());
-            if let Ok(stdout) = String::from_utf8(output.stdout) {
-                if !stdout.trim().is_empty() {
-                    println!("{stdout}");
-                }
+        match commit::create_commit(
+            repo_path,
+            &commit_message,
+            &args.commit_options(&config.commit),
+        ) {
+            Ok(outcome) => {
+                println!("{}", style("✅ commit successful!").green().bold());
+                println!("{}", outcome.describe());
+                true
             }
-            true
-        } else {
-            println!("{}", style("❌ commit failed:").red().bold());
-            if let Ok(stderr) = String::from_utf8(output.stderr) {
-                if !stderr.trim().is_empty() {
-                    println!("{stderr}");
-                }
+            Err(e) => {
+                println!("{}", style(format!("❌ commit failed: {e:#}")).red().bold());
+                false
             }
-            false
         }
     } else {
         false
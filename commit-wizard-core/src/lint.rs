@@ -0,0 +1,543 @@
+// lint module - checks existing commit messages against the rules the generator follows
+
+use crate::ai::commit_message_violations;
+use crate::commit::comment_char;
+use crate::error::{ValidationRule, WizardError};
+use anyhow::{Context, Result};
+use console::style;
+use git2::{Oid, Repository, Sort};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// how the lint report is printed
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LintFormat {
+    #[default]
+    Text,
+    Json,
+    /// github actions workflow annotations
+    Github,
+    Junit,
+}
+
+/// one broken rule
+#[derive(Serialize, Debug, Clone)]
+pub struct Violation {
+    #[serde(skip)]
+    pub kind: ValidationRule,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// the result for one commit or message file
+#[derive(Serialize, Debug, Clone)]
+pub struct LintedMessage {
+    pub source: String, // commit id, or the message file's path
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<&'static str>, // why the message wasn't checked
+    pub violations: Vec<Violation>,
+}
+
+impl LintedMessage {
+    fn label(&self) -> String {
+        match Oid::from_str(&self.source) {
+            Ok(id) => format!("{} {}", &id.to_string()[..7], self.subject),
+            Err(_) => self.source.clone(),
+        }
+    }
+}
+
+/// every message checked, with a tally
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LintReport {
+    pub checked: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub results: Vec<LintedMessage>,
+}
+
+/// lint the commits in `range` (`a..b`, or `a` for `a..HEAD`), newest first
+pub fn lint_range(repo_path: &str, range: &str) -> Result<LintReport> {
+    let repo = Repository::discover(repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?;
+    if range.contains("...") {
+        return Err(WizardError::Config(format!(
+            "'{range}' is a symmetric range; use a..b or a single base commit"
+        ))
+        .into());
+    }
+
+    let resolve = |spec: &str| -> Result<Oid> {
+        let spec = if spec.is_empty() { "HEAD" } else { spec };
+        Ok(repo
+            .revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| {
+                WizardError::Config(format!("unknown revision '{spec}': {}", e.message()))
+            })?
+            .id())
+    };
+    let (from, to) = match range.split_once("..") {
+        Some((from, to)) => (resolve(from)?, resolve(to)?),
+        None => (resolve(range)?, resolve("HEAD")?),
+    };
+
+    let git_error = |e: git2::Error| WizardError::Git(e.message().to_string());
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    walk.set_sorting(Sort::TOPOLOGICAL).map_err(git_error)?;
+    walk.push(to).map_err(git_error)?;
+    walk.hide(from).map_err(git_error)?;
+
+    let mut report = LintReport::default();
+    for id in walk {
+        let commit = repo
+            .find_commit(id.map_err(git_error)?)
+            .map_err(git_error)?;
+        let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+        let skipped = if commit.parent_count() > 1 {
+            Some("merge commit")
+        } else {
+            exemption(&message)
+        };
+        report.push(lint_message(commit.id().to_string(), &message, skipped));
+    }
+    Ok(report)
+}
+
+/// lint a message file, e.g. the one a commit-msg hook is given, using the comment
+/// character configured for the repository at `repo_path`
+pub fn lint_message_file(repo_path: &str, path: &str) -> Result<LintReport> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+    // outside a repository git's default applies
+    let comment = match Repository::discover(repo_path) {
+        Ok(repo) => comment_char(&repo),
+        Err(_) => Some('#'),
+    };
+    let message = strip_template(&content, comment);
+
+    let mut report = LintReport::default();
+    report.push(lint_message(
+        path.to_string(),
+        &message,
+        exemption(&message),
+    ));
+    Ok(report)
+}
+
+// follows the comment character on the line git's editor template cuts the diff at
+const SCISSORS: &str = " ------------------------ >8";
+
+/// git's editor template: everything below the scissors and every comment is dropped.
+/// `None` is `core.commentChar=auto`, where the scissors line shows the character git picked
+fn strip_template(content: &str, comment: Option<char>) -> String {
+    let comment = comment
+        .or_else(|| {
+            content.lines().find_map(|line| {
+                let mut chars = line.chars();
+                let first = chars.next()?;
+                chars.as_str().starts_with(SCISSORS).then_some(first)
+            })
+        })
+        .unwrap_or('#');
+    content
+        .lines()
+        .take_while(|line| {
+            !line
+                .strip_prefix(comment)
+                .is_some_and(|rest| rest.starts_with(SCISSORS))
+        })
+        .filter(|line| !line.starts_with(comment))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// messages git writes itself, which aren't expected to be conventional
+fn exemption(message: &str) -> Option<&'static str> {
+    let subject = message.lines().next().unwrap_or_default();
+    if subject.starts_with("Merge ") {
+        Some("merge commit")
+    } else if ["fixup! ", "squash! ", "amend! "]
+        .iter()
+        .any(|prefix| subject.starts_with(prefix))
+    {
+        Some("fixup commit")
+    } else if subject.starts_with("Revert \"") {
+        Some("git revert")
+    } else {
+        None
+    }
+}
+
+fn lint_message(source: String, message: &str, skipped: Option<&'static str>) -> LintedMessage {
+    let message = message.trim();
+    let violations = match skipped {
+        Some(_) => Vec::new(),
+        None => commit_message_violations(message)
+            .into_iter()
+            .filter_map(|violation| {
+                let rule = violation.rule()?;
+                Some(Violation {
+                    kind: rule,
+                    rule: rule.code(),
+                    message: violation.to_string(),
+                })
+            })
+            .collect(),
+    };
+    LintedMessage {
+        source,
+        subject: message.lines().next().unwrap_or_default().to_string(),
+        skipped,
+        violations,
+    }
+}
+
+impl LintReport {
+    fn push(&mut self, result: LintedMessage) {
+        if result.skipped.is_some() {
+            self.skipped += 1;
+        } else {
+            self.checked += 1;
+            if !result.violations.is_empty() {
+                self.failed += 1;
+            }
+        }
+        self.results.push(result);
+    }
+
+    /// fail with a validation error when any message broke a rule
+    pub fn check(&self) -> Result<()> {
+        let Some(first) = self
+            .results
+            .iter()
+            .flat_map(|result| &result.violations)
+            .next()
+        else {
+            return Ok(());
+        };
+        let summary = format!(
+            "{} of {} commit message{} checked break{} conventional commit rules",
+            self.failed,
+            self.checked,
+            if self.checked == 1 { "" } else { "s" },
+            if self.failed == 1 { "s" } else { "" }
+        );
+        Err(WizardError::validation(first.kind, summary).into())
+    }
+
+    pub fn render(&self, format: LintFormat) -> String {
+        match format {
+            LintFormat::Text => self.render_text(),
+            LintFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default() + "\n",
+            LintFormat::Github => self.render_github(),
+            LintFormat::Junit => self.render_junit(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = String::new();
+        for result in &self.results {
+            if let Some(reason) = result.skipped {
+                let _ = writeln!(
+                    out,
+                    "{} {}",
+                    style("⏭️ ").dim(),
+                    style(format!("{} ({reason})", result.label())).dim()
+                );
+            } else if result.violations.is_empty() {
+                let _ = writeln!(out, "{} {}", style("✅"), result.label());
+            } else {
+                let _ = writeln!(out, "{} {}", style("❌"), style(result.label()).red());
+                for violation in &result.violations {
+                    let _ = writeln!(
+                        out,
+                        "   {} {}",
+                        style(format!("{}:", violation.rule)).yellow(),
+                        violation.message
+                    );
+                }
+            }
+        }
+        if self.failed == 0 {
+            let _ = writeln!(
+                out,
+                "\n{}",
+                style(match self.checked {
+                    0 => "no commit messages to check".to_string(),
+                    1 => "✅ the commit message follows conventional commits".to_string(),
+                    n => format!("✅ all {n} commit messages follow conventional commits"),
+                })
+                .green()
+            );
+        }
+        out
+    }
+
+    fn render_github(&self) -> String {
+        let mut out = String::new();
+        for result in &self.results {
+            // a message file can be pointed at; commits have no file to annotate
+            let location = match Oid::from_str(&result.source) {
+                Ok(_) => String::new(),
+                Err(_) => format!("file={},line=1,", escape_property(&result.source)),
+            };
+            for violation in &result.violations {
+                let _ = writeln!(
+                    out,
+                    "::error {location}title={}::{}",
+                    escape_property(&format!("commit message: {}", violation.rule)),
+                    escape_data(&format!("{} - {}", result.label(), violation.message))
+                );
+            }
+        }
+        out
+    }
+
+    fn render_junit(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let total = self.results.len();
+        let _ = writeln!(
+            out,
+            "<testsuites name=\"commit-wizard lint\" tests=\"{total}\" failures=\"{}\">",
+            self.failed
+        );
+        let _ = writeln!(
+            out,
+            "  <testsuite name=\"conventional commits\" tests=\"{total}\" failures=\"{}\" skipped=\"{}\">",
+            self.failed, self.skipped
+        );
+        for result in &self.results {
+            let _ = write!(
+                out,
+                "    <testcase classname=\"commits\" name=\"{}\"",
+                escape_xml(&result.label())
+            );
+            if let Some(reason) = result.skipped {
+                let _ = writeln!(
+                    out,
+                    ">\n      <skipped message=\"{reason}\"/>\n    </testcase>"
+                );
+            } else if let Some(first) = result.violations.first() {
+                let details: Vec<String> = result
+                    .violations
+                    .iter()
+                    .map(|v| format!("{}: {}", v.rule, v.message))
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    ">\n      <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>",
+                    first.rule,
+                    escape_xml(&first.message),
+                    escape_xml(&details.join("\n"))
+                );
+            } else {
+                out.push_str("/>\n");
+            }
+        }
+        out.push_str("  </testsuite>\n</testsuites>\n");
+        out
+    }
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, git};
+    use tempfile::TempDir;
+
+    /// a repository with one commit per message, oldest first
+    fn history(messages: &[&str]) -> (TempDir, Vec<Oid>) {
+        let (dir, repo) = test_support::scratch_repo();
+        let ids = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                test_support::write(&dir, "notes.md", &format!("{i}\n"));
+                test_support::commit(&repo, &["notes.md"], message)
+            })
+            .collect();
+        (dir, ids)
+    }
+
+    fn sources(report: &LintReport) -> Vec<String> {
+        report.results.iter().map(|r| r.source.clone()).collect()
+    }
+
+    fn report(source: &str, label: &str, message: &str) -> LintReport {
+        let mut report = LintReport::default();
+        report.push(LintedMessage {
+            source: source.to_string(),
+            subject: label.to_string(),
+            skipped: None,
+            violations: vec![Violation {
+                kind: ValidationRule::InvalidFormat,
+                rule: ValidationRule::InvalidFormat.code(),
+                message: message.to_string(),
+            }],
+        });
+        report
+    }
+
+    #[test]
+    fn ranges_and_base_commits_select_commits_newest_first() {
+        let (dir, ids) = history(&["feat: add notes", "wip", "fix: correct notes"]);
+        let path = test_support::path(&dir);
+
+        let all = lint_range(path, "HEAD~2").unwrap();
+        assert_eq!(sources(&all), [ids[2].to_string(), ids[1].to_string()]);
+        assert_eq!((all.checked, all.failed, all.skipped), (2, 1, 0));
+        assert!(all.check().is_err());
+
+        let middle = lint_range(path, "HEAD~2..HEAD~1").unwrap();
+        assert_eq!(sources(&middle), [ids[1].to_string()]);
+        // an empty end means HEAD
+        let newest = lint_range(path, "HEAD~1..").unwrap();
+        assert_eq!(sources(&newest), [ids[2].to_string()]);
+        assert!(newest.check().is_ok());
+        assert!(lint_range(path, "HEAD..HEAD").unwrap().results.is_empty());
+    }
+
+    #[test]
+    fn bad_ranges_are_refused() {
+        let (dir, _ids) = history(&["feat: add notes", "fix: correct notes"]);
+        let path = test_support::path(&dir);
+        let error = |range| lint_range(path, range).unwrap_err().to_string();
+        assert!(error("HEAD~1...HEAD").contains("symmetric range"));
+        assert!(error("nope..HEAD").contains("unknown revision 'nope'"));
+        assert!(error("HEAD~1..nope").contains("unknown revision 'nope'"));
+    }
+
+    #[test]
+    fn merge_commits_are_skipped_whatever_their_message() {
+        let (dir, ids) = history(&["feat: add notes", "fix: correct notes"]);
+        git(
+            dir.path(),
+            &["checkout", "-q", "-b", "side", &ids[0].to_string()],
+        );
+        test_support::write(&dir, "other.md", "side\n");
+        git(dir.path(), &["add", "other.md"]);
+        git(dir.path(), &["commit", "-q", "-m", "docs: add other notes"]);
+        git(dir.path(), &["checkout", "-q", "-"]);
+        git(
+            dir.path(),
+            &["merge", "-q", "--no-ff", "-m", "tie it together", "side"],
+        );
+
+        let report = lint_range(test_support::path(&dir), "HEAD~1").unwrap();
+        let merge = &report.results[0];
+        assert_eq!(merge.subject, "tie it together");
+        assert_eq!(merge.skipped, Some("merge commit"));
+        assert_eq!((report.checked, report.skipped, report.failed), (1, 1, 0));
+    }
+
+    #[test]
+    fn messages_git_writes_itself_are_exempt() {
+        assert_eq!(exemption("Merge branch 'main'"), Some("merge commit"));
+        for subject in ["fixup! feat: a", "squash! feat: a", "amend! feat: a"] {
+            assert_eq!(exemption(subject), Some("fixup commit"), "{subject}");
+        }
+        assert_eq!(
+            exemption("Revert \"feat: a\"\n\nThis reverts"),
+            Some("git revert")
+        );
+        assert_eq!(exemption("Reverted the cache"), None);
+        assert_eq!(exemption("feat: merge configs"), None);
+        assert_eq!(exemption(""), None);
+    }
+
+    #[test]
+    fn the_template_loses_comments_and_everything_below_the_scissors() {
+        let content = "feat: add notes\n\n#123 stays out too\nbody\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff --git a/notes.md b/notes.md\n";
+        assert_eq!(
+            strip_template(content, Some('#')),
+            "feat: add notes\n\nbody"
+        );
+
+        let content = "feat: add notes\n\n#123 is fixed\n; a comment\n; ------------------------ >8 ------------------------\n+added\n";
+        assert_eq!(
+            strip_template(content, Some(';')),
+            "feat: add notes\n\n#123 is fixed"
+        );
+        // with `auto` the scissors line says which character git chose
+        assert_eq!(
+            strip_template(content, None),
+            "feat: add notes\n\n#123 is fixed"
+        );
+        assert_eq!(strip_template("# note\nfix: a\n", None), "fix: a");
+    }
+
+    #[test]
+    fn message_files_use_the_repository_comment_char() {
+        let (dir, repo) = test_support::scratch_repo();
+        let file = dir.path().join("COMMIT_EDITMSG");
+        std::fs::write(&file, "; subject below\nfeat: add notes\n\n#123 is fixed\n").unwrap();
+        let lint = || {
+            lint_message_file(test_support::path(&dir), file.to_str().unwrap())
+                .unwrap()
+                .results
+                .remove(0)
+        };
+
+        assert_eq!(lint().subject, "; subject below");
+        repo.config()
+            .unwrap()
+            .set_str("core.commentChar", ";")
+            .unwrap();
+        let result = lint();
+        assert_eq!(result.subject, "feat: add notes");
+        assert!(result.violations.is_empty());
+        assert_eq!(result.source, file.to_str().unwrap());
+    }
+
+    #[test]
+    fn github_annotations_escape_data_and_properties() {
+        let rendered =
+            report("hooks/msg,1:txt", "", "50% of\r\nthe subject").render(LintFormat::Github);
+        assert_eq!(
+            rendered,
+            "::error file=hooks/msg%2C1%3Atxt,line=1,title=commit message%3A invalid_format::hooks/msg,1:txt - 50%25 of%0D%0Athe subject\n"
+        );
+
+        // commits have no file to point at
+        let id = Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        let rendered = report(&id.to_string(), "wip", "bad").render(LintFormat::Github);
+        assert_eq!(
+            rendered,
+            "::error title=commit message%3A invalid_format::0123456 wip - bad\n"
+        );
+    }
+
+    #[test]
+    fn junit_escapes_markup_in_names_and_failures() {
+        let rendered =
+            report("<msg> & \"quotes\"", "", "use <type>: & \"text\"").render(LintFormat::Junit);
+        assert!(rendered.contains(
+            "<testcase classname=\"commits\" name=\"&lt;msg&gt; &amp; &quot;quotes&quot;\">"
+        ));
+        assert!(rendered.contains(
+            "<failure type=\"invalid_format\" message=\"use &lt;type&gt;: &amp; &quot;text&quot;\">invalid_format: use &lt;type&gt;: &amp; &quot;text&quot;</failure>"
+        ));
+        assert!(rendered.contains("tests=\"1\" failures=\"1\" skipped=\"0\""));
+    }
+}
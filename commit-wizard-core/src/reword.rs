@@ -0,0 +1,359 @@
+// reword module - rewrites the messages of a range of commits, keeping their trees and authors
+
+use crate::commit::{git_error, identity_error, move_head, write_commit};
+use crate::error::WizardError;
+use anyhow::Result;
+use git2::{BranchType, Oid, Repository, RepositoryState, Sort};
+use std::collections::HashMap;
+
+/// the commits a reword will touch
+#[derive(Debug, Clone)]
+pub struct RewordPlan {
+    pub commits: Vec<Oid>,     // commits in the range, oldest first
+    pub descendants: Vec<Oid>, // commits after the range up to HEAD, which get new parents
+    pub head: Oid,
+}
+
+/// resolve `range` (`a..b`, or `a` for `a..HEAD`) against HEAD's history and make sure
+/// it's safe to rewrite
+pub fn plan_reword(repo_path: &str, range: &str, protected: &[String]) -> Result<RewordPlan> {
+    let repo = Repository::discover(repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?;
+    if repo.state() != RepositoryState::Clean {
+        return Err(WizardError::Git(
+            "a merge, rebase or similar is in progress; finish or abort it first".to_string(),
+        )
+        .into());
+    }
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| WizardError::Git("there are no commits to reword yet".to_string()))?
+        .id();
+    let resolve = |spec: &str| -> Result<Oid> {
+        Ok(repo
+            .revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|e| {
+                WizardError::Config(format!("unknown revision '{spec}': {}", e.message()))
+            })?
+            .id())
+    };
+    if range.contains("...") {
+        return Err(WizardError::Config(format!(
+            "'{range}' is a symmetric range; use a..b or a single base commit"
+        ))
+        .into());
+    }
+    let (from, to) = match range.split_once("..") {
+        Some((from, to)) => (
+            resolve(if from.is_empty() { "HEAD" } else { from })?,
+            resolve(if to.is_empty() { "HEAD" } else { to })?,
+        ),
+        None => (resolve(range)?, head),
+    };
+
+    // only HEAD's own history can be rewritten in place
+    let on_head = |id: Oid| id == head || repo.graph_descendant_of(head, id).unwrap_or(false);
+    if !on_head(to) {
+        return Err(WizardError::Config(format!(
+            "the end of '{range}' isn't part of the current branch"
+        ))
+        .into());
+    }
+    if !on_head(from) {
+        return Err(WizardError::Config(format!(
+            "the start of '{range}' isn't part of the current branch"
+        ))
+        .into());
+    }
+
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)
+        .map_err(git_error)?;
+    walk.push(head).map_err(git_error)?;
+    walk.hide(from).map_err(git_error)?;
+    let history = walk.collect::<Result<Vec<Oid>, _>>().map_err(git_error)?;
+
+    for id in &history {
+        if repo.find_commit(*id).map_err(git_error)?.parent_count() > 1 {
+            return Err(WizardError::Git(format!(
+                "{} is a merge commit; reword only works on linear history",
+                short(*id)
+            ))
+            .into());
+        }
+    }
+
+    let split = match history.iter().position(|id| *id == to) {
+        Some(at) => at + 1,
+        None => 0,
+    };
+    let (commits, descendants) = history.split_at(split);
+    if commits.is_empty() {
+        return Err(WizardError::Config(format!("'{range}' doesn't contain any commits")).into());
+    }
+
+    refuse_published(&repo, commits, protected)?;
+
+    Ok(RewordPlan {
+        commits: commits.to_vec(),
+        descendants: descendants.to_vec(),
+        head,
+    })
+}
+
+/// fail if any commit is already reachable from a protected remote-tracking branch,
+/// since rewriting it would mean force-pushing shared history
+fn refuse_published(repo: &Repository, commits: &[Oid], protected: &[String]) -> Result<()> {
+    let branches = repo.branches(Some(BranchType::Remote)).map_err(git_error)?;
+    for (branch, _) in branches.flatten() {
+        let Some(name) = branch.name().ok().flatten().map(str::to_string) else {
+            continue;
+        };
+        let Some((_, short_name)) = name.split_once('/') else {
+            continue;
+        };
+        if !protected.iter().any(|p| p == short_name) {
+            continue;
+        }
+        let Some(tip) = branch.get().target() else {
+            continue;
+        };
+        if let Some(published) = commits
+            .iter()
+            .find(|id| **id == tip || repo.graph_descendant_of(tip, **id).unwrap_or(false))
+        {
+            return Err(WizardError::Git(format!(
+                "{} is already on {name}, which is protected; rewording it would rewrite published history",
+                short(*published)
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// recreate the planned commits with the new messages (commits missing from `messages`
+/// keep theirs) and move the branch to the result. returns the new HEAD
+pub fn rewrite_messages(
+    repo_path: &str,
+    plan: &RewordPlan,
+    messages: &HashMap<Oid, String>,
+    sign: Option<bool>,
+) -> Result<Oid> {
+    let repo = Repository::discover(repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?;
+    let current = repo.head().ok().and_then(|head| head.target());
+    if current != Some(plan.head) {
+        return Err(WizardError::Git(
+            "HEAD moved while the messages were being reviewed; run reword again".to_string(),
+        )
+        .into());
+    }
+
+    // like a rebase, the rewritten commits get the current committer but keep their authors
+    let committer = repo.signature().map_err(identity_error)?;
+    let mut rewritten: HashMap<Oid, Oid> = HashMap::new();
+    let mut new_head = plan.head;
+    for id in plan.commits.iter().chain(&plan.descendants) {
+        let commit = repo.find_commit(*id).map_err(git_error)?;
+        let parents = commit
+            .parent_ids()
+            .map(|parent| repo.find_commit(*rewritten.get(&parent).unwrap_or(&parent)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(git_error)?;
+        let parent_refs: Vec<_> = parents.iter().collect();
+        let message = match messages.get(id) {
+            Some(message) => message.clone(),
+            None => String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+        };
+
+        new_head = write_commit(
+            &repo,
+            &commit.author(),
+            &committer,
+            &message,
+            &commit.tree().map_err(git_error)?,
+            &parent_refs,
+            sign,
+        )?;
+        rewritten.insert(*id, new_head);
+    }
+
+    // the trees are unchanged, so the index and working tree stay as they are
+    let reworded = messages.len();
+    move_head(
+        &repo,
+        new_head,
+        &format!(
+            "reword: rewrote {reworded} commit message{}",
+            if reworded == 1 { "" } else { "s" }
+        ),
+    )?;
+    // the old tip stays reachable for `git reset --soft ORIG_HEAD`
+    repo.reference("ORIG_HEAD", plan.head, true, "reword")
+        .map_err(git_error)?;
+    Ok(new_head)
+}
+
+pub(crate) fn short(id: Oid) -> String {
+    id.to_string()[..7].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, git};
+    use tempfile::TempDir;
+
+    /// a repository with three commits on notes.md, each by ada rather than the committer
+    fn history() -> (TempDir, Repository, Vec<Oid>) {
+        let (dir, repo) = test_support::scratch_repo();
+        let ids = ["one", "two", "three"]
+            .iter()
+            .map(|step| commit_as_ada(&dir, &repo, step))
+            .collect();
+        (dir, repo, ids)
+    }
+
+    fn commit_as_ada(dir: &TempDir, repo: &Repository, step: &str) -> Oid {
+        test_support::write(dir, "notes.md", &format!("{step}\n"));
+        git(dir.path(), &["add", "notes.md"]);
+        git(
+            dir.path(),
+            &[
+                "commit",
+                "-q",
+                "--author=ada <ada@example.com>",
+                "-m",
+                &format!("wip {step}"),
+            ],
+        );
+        repo.head().unwrap().target().unwrap()
+    }
+
+    fn plan_error(dir: &TempDir, range: &str, protected: &[String]) -> String {
+        plan_reword(test_support::path(dir), range, protected)
+            .expect_err("plan should be refused")
+            .to_string()
+    }
+
+    #[test]
+    fn plans_a_base_commit_or_a_range_on_head() {
+        let (dir, _repo, ids) = history();
+        let path = test_support::path(&dir);
+
+        let plan = plan_reword(path, "HEAD~2", &[]).unwrap();
+        assert_eq!(plan.commits, ids[1..]);
+        assert!(plan.descendants.is_empty());
+        assert_eq!(plan.head, ids[2]);
+
+        let plan = plan_reword(path, "HEAD~2..HEAD~1", &[]).unwrap();
+        assert_eq!(plan.commits, ids[1..2]);
+        assert_eq!(plan.descendants, ids[2..]);
+    }
+
+    #[test]
+    fn symmetric_and_empty_ranges_are_refused() {
+        let (dir, _repo, _ids) = history();
+        assert!(plan_error(&dir, "HEAD~2...HEAD", &[]).contains("symmetric range"));
+        assert!(plan_error(&dir, "HEAD..HEAD", &[]).contains("doesn't contain any commits"));
+        assert!(plan_error(&dir, "nope..HEAD", &[]).contains("unknown revision 'nope'"));
+    }
+
+    #[test]
+    fn ranges_off_the_current_branch_are_refused() {
+        let (dir, repo, ids) = history();
+        git(dir.path(), &["branch", "side", &ids[0].to_string()]);
+        git(dir.path(), &["checkout", "-q", "side"]);
+        commit_as_ada(&dir, &repo, "side");
+        git(dir.path(), &["checkout", "-q", "-"]);
+
+        assert!(plan_error(&dir, "HEAD~1..side", &[]).contains("end of 'HEAD~1..side'"));
+        assert!(plan_error(&dir, "side", &[]).contains("start of 'side'"));
+    }
+
+    #[test]
+    fn merge_commits_are_refused() {
+        let (dir, repo, ids) = history();
+        git(
+            dir.path(),
+            &["checkout", "-q", "-b", "side", &ids[1].to_string()],
+        );
+        test_support::write(&dir, "other.md", "side\n");
+        git(dir.path(), &["add", "other.md"]);
+        git(dir.path(), &["commit", "-q", "-m", "side work"]);
+        git(dir.path(), &["checkout", "-q", "-"]);
+        git(
+            dir.path(),
+            &[
+                "merge",
+                "-q",
+                "--no-ff",
+                "--no-edit",
+                "-m",
+                "merge side",
+                "side",
+            ],
+        );
+        let merge = repo.head().unwrap().target().unwrap();
+
+        assert!(plan_error(&dir, "HEAD~1", &[]).contains(&format!("{} is a merge", short(merge))));
+    }
+
+    #[test]
+    fn commits_on_a_protected_upstream_are_refused() {
+        let (dir, repo, ids) = history();
+        repo.reference("refs/remotes/origin/main", ids[1], true, "test")
+            .unwrap();
+        let protected = vec!["main".to_string()];
+
+        let error = plan_error(&dir, "HEAD~2", &protected);
+        assert!(error.contains(&format!("{} is already on origin/main", short(ids[1]))));
+        // only what's past the upstream tip, or an unprotected upstream, can be reworded
+        assert!(plan_reword(test_support::path(&dir), "HEAD~1", &protected).is_ok());
+        assert!(plan_reword(test_support::path(&dir), "HEAD~2", &["release".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn rewriting_keeps_trees_and_authors_and_moves_head() {
+        let (dir, repo, ids) = history();
+        let path = test_support::path(&dir);
+        let plan = plan_reword(path, "HEAD~2..HEAD~1", &[]).unwrap();
+        let messages = HashMap::from([(ids[1], "docs: write the second note\n".to_string())]);
+
+        let new_head = rewrite_messages(path, &plan, &messages, Some(false)).unwrap();
+
+        assert_eq!(repo.head().unwrap().target(), Some(new_head));
+        assert_eq!(repo.refname_to_id("ORIG_HEAD").unwrap(), ids[2]);
+        let rewritten = repo.find_commit(new_head).unwrap();
+        let reworded = rewritten.parent(0).unwrap();
+        assert_eq!(reworded.message(), Some("docs: write the second note\n"));
+        assert_eq!(rewritten.message(), Some("wip three\n"));
+        assert_eq!(reworded.parent_id(0).unwrap(), ids[0]);
+        for (new, old) in [(&rewritten, ids[2]), (&reworded, ids[1])] {
+            let old = repo.find_commit(old).unwrap();
+            assert_ne!(new.id(), old.id());
+            assert_eq!(new.tree_id(), old.tree_id());
+            assert_eq!(new.author().name(), Some("ada"));
+            assert_eq!(new.author().when(), old.author().when());
+            assert_eq!(new.committer().name(), Some("test"));
+        }
+    }
+
+    #[test]
+    fn rewriting_after_head_moved_is_refused() {
+        let (dir, repo, _ids) = history();
+        let path = test_support::path(&dir);
+        let plan = plan_reword(path, "HEAD~1", &[]).unwrap();
+        commit_as_ada(&dir, &repo, "four");
+
+        let error = rewrite_messages(path, &plan, &HashMap::new(), Some(false))
+            .expect_err("stale plan")
+            .to_string();
+        assert!(error.contains("HEAD moved"));
+        assert!(repo.find_reference("ORIG_HEAD").is_err());
+    }
+}
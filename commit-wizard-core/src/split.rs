@@ -0,0 +1,521 @@
+// split module - stages one group of changes at a time so each can be committed on its own
+
+use crate::ai::ChangeGroup;
+use crate::commit::git_error;
+use crate::error::WizardError;
+use crate::git::{sparse_paths, DiffInfo, FileStatus, Hunk, ModifiedFile};
+use anyhow::Result;
+use git2::{Index, IndexEntry, IndexEntryExtendedFlag, IndexTime, Oid, Repository};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// what the working tree looked like before splitting, so each group can be staged
+/// from it and anything left over put back afterwards
+pub struct SplitSnapshot {
+    // staged changes by path (none for a staged deletion), or none when splitting
+    // unstaged changes, which are staged straight from the working tree
+    staged: Option<BTreeMap<String, Option<IndexEntry>>>,
+    // the tree the diff's hunks were taken against: HEAD for staged changes, the index
+    // for unstaged ones
+    base: Option<Oid>,
+    committed: Vec<String>,                        // files committed whole
+    committed_hunks: BTreeMap<String, Vec<usize>>, // hunks of split files committed so far
+}
+
+impl SplitSnapshot {
+    /// remember the staged changes, if there are any, since the index is rebuilt per group
+    pub fn capture(repo_path: &str) -> Result<Self> {
+        let repo = open(repo_path)?;
+        let mut index = repo.index().map_err(git_error)?;
+        let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+        let diff = repo
+            .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)
+            .map_err(git_error)?;
+
+        let mut staged = BTreeMap::new();
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path() else {
+                continue;
+            };
+            // a staged deletion has no index entry left
+            staged.insert(path.to_string_lossy().into_owned(), index.get_path(path, 0));
+        }
+        let base = if staged.is_empty() {
+            index.write_tree().ok()
+        } else {
+            head_tree.map(|tree| tree.id())
+        };
+        Ok(Self {
+            staged: (!staged.is_empty()).then_some(staged),
+            base,
+            committed: Vec::new(),
+            committed_hunks: BTreeMap::new(),
+        })
+    }
+
+    /// make the index hold HEAD plus exactly the changes in `group`, building on the
+    /// hunks earlier groups already committed from the same files. a renamed file takes
+    /// the removal of its old path with it
+    pub fn stage(&self, repo_path: &str, diff_info: &DiffInfo, group: &ChangeGroup) -> Result<()> {
+        let repo = open(repo_path)?;
+        let mut index = repo.index().map_err(git_error)?;
+        let mut whole: Vec<String> = group
+            .files
+            .iter()
+            .filter(|path| !group.hunks.contains_key(*path))
+            .cloned()
+            .collect();
+        whole.extend(renamed_from(diff_info, group));
+        self.stage_whole(&repo, &mut index, &whole)?;
+        for (path, hunks) in &group.hunks {
+            let Some(file) = diff_info.files.iter().find(|file| &file.path == path) else {
+                continue;
+            };
+            let mut picked = self.committed_hunks.get(path).cloned().unwrap_or_default();
+            picked.extend(hunks);
+            self.stage_hunks(&repo, &mut index, file, &picked)?;
+        }
+        index.write().map_err(git_error)?;
+        Ok(())
+    }
+
+    /// note what a group's commit took, so later groups and the restore build on it
+    pub fn mark_committed(&mut self, diff_info: &DiffInfo, group: &ChangeGroup) {
+        self.committed.extend(renamed_from(diff_info, group));
+        for path in &group.files {
+            match group.hunks.get(path) {
+                Some(hunks) => self
+                    .committed_hunks
+                    .entry(path.clone())
+                    .or_default()
+                    .extend(hunks),
+                None => self.committed.push(path.clone()),
+            }
+        }
+    }
+
+    /// stage again whatever wasn't committed, as it was staged before splitting. a file
+    /// only partly committed is staged whole, leaving its other hunks staged. unstaged
+    /// changes that weren't committed were never staged, so stay as they are
+    pub fn restore(&self, repo_path: &str) -> Result<()> {
+        let Some(staged) = &self.staged else {
+            return Ok(());
+        };
+        let leftovers: Vec<String> = staged
+            .keys()
+            .filter(|path| !self.committed.contains(path))
+            .cloned()
+            .collect();
+        let repo = open(repo_path)?;
+        let mut index = repo.index().map_err(git_error)?;
+        self.stage_whole(&repo, &mut index, &leftovers)?;
+        index.write().map_err(git_error)?;
+        Ok(())
+    }
+
+    /// stage the whole change to each of `paths`, starting from HEAD when splitting
+    /// staged changes
+    fn stage_whole(&self, repo: &Repository, index: &mut Index, paths: &[String]) -> Result<()> {
+        match &self.staged {
+            Some(staged) => {
+                reset_to_head(repo, index)?;
+                for path in paths {
+                    match staged.get(path) {
+                        Some(Some(entry)) => index.add(entry).map_err(git_error)?,
+                        Some(None) => index.remove_path(Path::new(path)).map_err(git_error)?,
+                        None => {}
+                    }
+                }
+            }
+            None => {
+                let workdir = repo.workdir().ok_or_else(|| {
+                    WizardError::Git("bare repositories not supported".to_string())
+                })?;
+                for path in paths {
+                    let path = Path::new(path);
+                    if workdir.join(path).exists() {
+                        index.add_path(path).map_err(git_error)?;
+                    } else {
+                        index.remove_path(path).map_err(git_error)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// stage `file` as its base version with only the hunks at `picked` applied
+    fn stage_hunks(
+        &self,
+        repo: &Repository,
+        index: &mut Index,
+        file: &ModifiedFile,
+        picked: &[usize],
+    ) -> Result<()> {
+        // the full new version, as it was staged or as the working tree has it
+        let path = Path::new(&file.path);
+        let mut entry = match &self.staged {
+            Some(staged) => match staged.get(&file.path) {
+                Some(Some(entry)) => IndexEntry {
+                    path: entry.path.clone(),
+                    ..*entry
+                },
+                _ => return Ok(()),
+            },
+            None => {
+                index.add_path(path).map_err(git_error)?;
+                index
+                    .get_path(path, 0)
+                    .ok_or_else(|| WizardError::Git(format!("{} could not be staged", file.path)))?
+            }
+        };
+        let old = self.base_content(repo, file.status.source().unwrap_or(&file.path))?;
+        let new = repo.find_blob(entry.id).map_err(git_error)?;
+        let hunks: Vec<&Hunk> = picked.iter().filter_map(|&at| file.hunks.get(at)).collect();
+        let content = apply_hunks(&old, new.content(), &hunks);
+
+        entry.id = repo.blob(&content).map_err(git_error)?;
+        entry.file_size = content.len() as u32;
+        // the working tree file doesn't match this blob, so its stat data mustn't either
+        entry.ctime = IndexTime::new(0, 0);
+        entry.mtime = IndexTime::new(0, 0);
+        entry.dev = 0;
+        entry.ino = 0;
+        index.add(&entry).map_err(git_error)?;
+        Ok(())
+    }
+
+    /// the content at `path` in the base tree, empty if it wasn't there
+    fn base_content(&self, repo: &Repository, path: &str) -> Result<Vec<u8>> {
+        let Some(base) = self.base else {
+            return Ok(Vec::new());
+        };
+        let tree = repo.find_tree(base).map_err(git_error)?;
+        let Ok(entry) = tree.get_path(Path::new(path)) else {
+            return Ok(Vec::new());
+        };
+        let blob = repo.find_blob(entry.id()).map_err(git_error)?;
+        Ok(blob.content().to_vec())
+    }
+}
+
+/// the old paths of the files in `group` that were renamed
+fn renamed_from(diff_info: &DiffInfo, group: &ChangeGroup) -> Vec<String> {
+    diff_info
+        .files
+        .iter()
+        .filter(|file| group.files.contains(&file.path))
+        .filter_map(|file| match &file.status {
+            FileStatus::Renamed { from } => Some(from.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// rebuild a file from its old version with only some of its hunks applied. each hunk's
+/// lines are taken from the new version by position, since the hunk's own text may have
+/// been redacted or re-encoded
+fn apply_hunks(old: &[u8], new: &[u8], hunks: &[&Hunk]) -> Vec<u8> {
+    let old: Vec<&[u8]> = old.split_inclusive(|b| *b == b'\n').collect();
+    let new: Vec<&[u8]> = new.split_inclusive(|b| *b == b'\n').collect();
+    // a side with no lines in the hunk counts from the line before it
+    let start = |start: u32, lines: u32| match lines {
+        0 => start as usize,
+        _ => start.saturating_sub(1) as usize,
+    };
+
+    let mut hunks = hunks.to_vec();
+    hunks.sort_by_key(|hunk| hunk.old_start);
+    let mut content = Vec::new();
+    let mut at = 0;
+    for hunk in hunks {
+        let old_start = start(hunk.old_start, hunk.old_lines).max(at);
+        let new_start = start(hunk.new_start, hunk.new_lines);
+        let new_end = new_start + hunk.new_lines as usize;
+        content.extend(old.get(at..old_start).unwrap_or_default().concat());
+        content.extend(new.get(new_start..new_end).unwrap_or_default().concat());
+        at = old_start + hunk.old_lines as usize;
+    }
+    content.extend(old.get(at..).unwrap_or_default().concat());
+    content
+}
+
+fn open(repo_path: &str) -> Result<Repository> {
+    Ok(Repository::discover(repo_path)
+        .map_err(|e| WizardError::Git(format!("invalid git repository: {e}")))?)
+}
+
+fn reset_to_head(repo: &Repository, index: &mut Index) -> Result<()> {
+    // reading a tree drops the skip-worktree bits a sparse checkout relies on
+    let sparse = sparse_paths(repo);
+    match repo.head().ok().and_then(|head| head.peel_to_tree().ok()) {
+        Some(tree) => index.read_tree(&tree).map_err(git_error)?,
+        // nothing committed yet, so nothing is staged at HEAD either
+        None => index.clear().map_err(git_error)?,
+    }
+    for path in &sparse {
+        if let Some(mut entry) = index.get_path(Path::new(path), 0) {
+            entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry).map_err(git_error)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cluster_changes;
+    use crate::git::get_diff_info;
+    use crate::test_support;
+    use std::fs;
+
+    const BEFORE: &str = "pub fn total(price: u32, qty: u32) -> u32 {\n    let total = price * qty;\n    total\n}\n\n// one\n// two\n// three\n// four\n// five\n// six\n// seven\n// eight\n\npub fn name() -> &'static str {\n    \"orders\"\n}\n";
+    const FIX: &str = "    let total = price.checked_mul(qty).expect(\"total overflow error\");\n";
+    const FEATURE: &str = "\npub fn refund(total: u32) -> u32 {\n    total\n}\n";
+
+    fn after() -> String {
+        format!(
+            "{}{}",
+            BEFORE.replace("    let total = price * qty;\n", FIX),
+            FEATURE
+        )
+    }
+
+    /// a repository with orders.rs committed and then changed by a fix and a new function
+    fn scratch_repo(stage: bool) -> (tempfile::TempDir, Repository) {
+        let (dir, repo) = test_support::scratch_repo();
+        test_support::write(&dir, "src/services/orders.rs", BEFORE);
+        test_support::commit(&repo, &["src/services/orders.rs"], "initial");
+        test_support::write(&dir, "src/services/orders.rs", &after());
+        if stage {
+            test_support::stage(&repo, &["src/services/orders.rs"]);
+        }
+        (dir, repo)
+    }
+
+    fn content_at(repo: &Repository, tree: &git2::Tree) -> String {
+        let entry = tree.get_path(Path::new("src/services/orders.rs")).unwrap();
+        let blob = repo.find_blob(entry.id()).unwrap();
+        String::from_utf8(blob.content().to_vec()).unwrap()
+    }
+
+    fn head_content(repo: &Repository) -> String {
+        content_at(repo, &repo.head().unwrap().peel_to_tree().unwrap())
+    }
+
+    fn index_content(repo: &Repository) -> String {
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        content_at(repo, &repo.find_tree(index.write_tree().unwrap()).unwrap())
+    }
+
+    fn plan(dir: &tempfile::TempDir) -> (DiffInfo, Vec<ChangeGroup>) {
+        let diff_info = get_diff_info(dir.path().to_str().unwrap(), 100 * 1024, 10, false).unwrap();
+        let groups = cluster_changes(&diff_info);
+        (diff_info, groups)
+    }
+
+    #[test]
+    fn a_fix_and_a_feature_in_one_file_are_split_by_hunk() {
+        let (dir, _repo) = scratch_repo(true);
+        let (_, groups) = plan(&dir);
+        let labels: Vec<&str> = groups.iter().map(|g| g.label.as_str()).collect();
+        assert_eq!(labels, ["service layer fixes", "service layer features"]);
+        for (group, hunk) in groups.iter().zip([0, 1]) {
+            assert_eq!(group.files, ["src/services/orders.rs"]);
+            assert_eq!(group.hunks["src/services/orders.rs"], [hunk]);
+        }
+    }
+
+    #[test]
+    fn staged_hunks_are_committed_one_group_at_a_time() {
+        let (dir, repo) = scratch_repo(true);
+        let path = dir.path().to_str().unwrap();
+        let mut snapshot = SplitSnapshot::capture(path).unwrap();
+        let (diff_info, groups) = plan(&dir);
+
+        snapshot.stage(path, &diff_info, &groups[0]).unwrap();
+        test_support::commit(&repo, &[], "fix");
+        snapshot.mark_committed(&diff_info, &groups[0]);
+        assert_eq!(
+            head_content(&repo),
+            BEFORE.replace("    let total = price * qty;\n", FIX)
+        );
+
+        snapshot.stage(path, &diff_info, &groups[1]).unwrap();
+        test_support::commit(&repo, &[], "feature");
+        snapshot.mark_committed(&diff_info, &groups[1]);
+        snapshot.restore(path).unwrap();
+        assert_eq!(head_content(&repo), after());
+        assert_eq!(index_content(&repo), after());
+    }
+
+    #[test]
+    fn uncommitted_hunks_stay_staged() {
+        let (dir, repo) = scratch_repo(true);
+        let path = dir.path().to_str().unwrap();
+        let mut snapshot = SplitSnapshot::capture(path).unwrap();
+        let (diff_info, groups) = plan(&dir);
+
+        // only the feature is committed; the fix is left staged on top of it
+        snapshot.stage(path, &diff_info, &groups[1]).unwrap();
+        test_support::commit(&repo, &[], "feature");
+        snapshot.mark_committed(&diff_info, &groups[1]);
+        snapshot.restore(path).unwrap();
+        assert_eq!(head_content(&repo), format!("{BEFORE}{FEATURE}"));
+        assert_eq!(index_content(&repo), after());
+    }
+
+    #[test]
+    fn unstaged_hunks_are_staged_from_the_working_tree() {
+        let (dir, repo) = scratch_repo(false);
+        let path = dir.path().to_str().unwrap();
+        let mut snapshot = SplitSnapshot::capture(path).unwrap();
+        let (diff_info, groups) = plan(&dir);
+
+        snapshot.stage(path, &diff_info, &groups[1]).unwrap();
+        test_support::commit(&repo, &[], "feature");
+        snapshot.mark_committed(&diff_info, &groups[1]);
+        snapshot.restore(path).unwrap();
+        assert_eq!(head_content(&repo), format!("{BEFORE}{FEATURE}"));
+        assert_eq!(index_content(&repo), format!("{BEFORE}{FEATURE}"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("src/services/orders.rs")).unwrap(),
+            after()
+        );
+    }
+
+    /// orders.rs renamed to billing.rs alongside an unrelated docs change
+    fn renamed_repo(stage: bool) -> (tempfile::TempDir, Repository) {
+        let (dir, repo) = scratch_repo(false);
+        fs::write(dir.path().join("src/services/orders.rs"), BEFORE).unwrap();
+        fs::write(dir.path().join("notes.md"), "# notes\n").unwrap();
+        test_support::commit(&repo, &["notes.md"], "notes");
+        fs::rename(
+            dir.path().join("src/services/orders.rs"),
+            dir.path().join("src/services/billing.rs"),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("notes.md"),
+            "# notes\n\nbilling lives here now\n",
+        )
+        .unwrap();
+        if stage {
+            let mut index = repo.index().unwrap();
+            index
+                .remove_path(Path::new("src/services/orders.rs"))
+                .unwrap();
+            index
+                .add_path(Path::new("src/services/billing.rs"))
+                .unwrap();
+            index.add_path(Path::new("notes.md")).unwrap();
+            index.write().unwrap();
+        }
+        (dir, repo)
+    }
+
+    /// commit just the group holding the rename, then put the rest back
+    fn commit_rename(dir: &tempfile::TempDir, repo: &Repository) {
+        let path = dir.path().to_str().unwrap();
+        let mut snapshot = SplitSnapshot::capture(path).unwrap();
+        let (diff_info, groups) = plan(dir);
+        let group = groups
+            .iter()
+            .find(|group| group.files == ["src/services/billing.rs"])
+            .expect("the rename has a group of its own");
+        snapshot.stage(path, &diff_info, group).unwrap();
+        test_support::commit(repo, &[], "rename");
+        snapshot.mark_committed(&diff_info, group);
+        snapshot.restore(path).unwrap();
+    }
+
+    fn head_paths(repo: &Repository) -> Vec<String> {
+        let mut paths = Vec::new();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                paths.push(format!("{dir}{}", entry.name().unwrap()));
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .unwrap();
+        paths
+    }
+
+    fn staged_paths(repo: &Repository) -> Vec<String> {
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        let diff = repo
+            .diff_tree_to_index(Some(&head), Some(&index), None)
+            .unwrap();
+        diff.deltas()
+            .map(|delta| {
+                delta
+                    .new_file()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn staged_rename_commits_its_old_path_removal() {
+        let (dir, repo) = renamed_repo(true);
+        commit_rename(&dir, &repo);
+        assert_eq!(head_paths(&repo), ["notes.md", "src/services/billing.rs"]);
+        assert_eq!(staged_paths(&repo), ["notes.md"]);
+    }
+
+    #[test]
+    fn unstaged_rename_commits_its_old_path_removal() {
+        let (dir, repo) = renamed_repo(false);
+        commit_rename(&dir, &repo);
+        assert_eq!(head_paths(&repo), ["notes.md", "src/services/billing.rs"]);
+        assert!(staged_paths(&repo).is_empty());
+    }
+
+    #[test]
+    fn staging_a_group_keeps_sparse_checkout_bits() {
+        let (dir, repo) = scratch_repo(false);
+        test_support::write(&dir, "vendor/lib.rs", "fn vendored() {}\n");
+        test_support::write(&dir, "notes.md", "# notes\n");
+        test_support::commit(
+            &repo,
+            &["src/services/orders.rs", "vendor/lib.rs", "notes.md"],
+            "layout",
+        );
+        test_support::git(
+            dir.path(),
+            &["sparse-checkout", "set", "--no-cone", "/src/", "/notes.md"],
+        );
+
+        test_support::write(&dir, "notes.md", "# notes\n\nmore\n");
+        test_support::stage(&repo, &["src/services/orders.rs", "notes.md"]);
+
+        let path = dir.path().to_str().unwrap();
+        let mut snapshot = SplitSnapshot::capture(path).unwrap();
+        let (diff_info, groups) = plan(&dir);
+        let skipped = |repo: &Repository| {
+            let mut index = repo.index().unwrap();
+            index.read(true).unwrap();
+            let entry = index.get_path(Path::new("vendor/lib.rs"), 0).unwrap();
+            IndexEntryExtendedFlag::from_bits_truncate(entry.flags_extended).is_skip_worktree()
+        };
+        assert!(skipped(&repo));
+
+        snapshot.stage(path, &diff_info, &groups[0]).unwrap();
+        assert!(skipped(&repo));
+        test_support::commit(&repo, &[], "first group");
+        snapshot.mark_committed(&diff_info, &groups[0]);
+        snapshot.restore(path).unwrap();
+        assert!(skipped(&repo));
+        assert!(!dir.path().join("vendor/lib.rs").exists());
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(head.get_path(Path::new("vendor/lib.rs")).is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+// test_support module - scratch repositories shared by the unit tests
+
+use git2::{Oid, Repository, Signature};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tempfile::TempDir;
+
+/// an empty repository with its own identity and hooks directory
+pub fn scratch_repo() -> (TempDir, Repository) {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let repo = Repository::init(dir.path()).expect("init repository");
+    let mut config = repo.config().expect("config");
+    config.set_str("user.name", "test").expect("user.name");
+    config
+        .set_str("user.email", "test@example.com")
+        .expect("user.email");
+    // keep any global core.hooksPath out of the test
+    config
+        .set_str("core.hooksPath", ".git/hooks")
+        .expect("core.hooksPath");
+    // and any global signing setup out of commits made with the git cli
+    config
+        .set_bool("commit.gpgsign", false)
+        .expect("commit.gpgsign");
+    (dir, repo)
+}
+
+/// the repository's path as the `&str` the library functions take
+pub fn path(dir: &TempDir) -> &str {
+    dir.path().to_str().expect("utf-8 temp path")
+}
+
+/// write a file in the working tree, creating its directories
+pub fn write(dir: &TempDir, path: &str, content: &str) {
+    let file = dir.path().join(path);
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).expect("create directories");
+    }
+    fs::write(file, content).expect("write file");
+}
+
+/// add `paths` to the index, re-reading it first in case the code under test wrote it
+pub fn stage(repo: &Repository, paths: &[&str]) {
+    let mut index = repo.index().expect("index");
+    index.read(true).expect("read index");
+    for path in paths {
+        index.add_path(Path::new(path)).expect("stage file");
+    }
+    index.write().expect("write index");
+}
+
+/// stage `paths` and commit the index on HEAD as the test identity
+pub fn commit(repo: &Repository, paths: &[&str], message: &str) -> Oid {
+    stage(repo, paths);
+    let mut index = repo.index().expect("index");
+    let tree = repo
+        .find_tree(index.write_tree().expect("write tree"))
+        .expect("find tree");
+    let signature = Signature::now("test", "test@example.com").expect("signature");
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .expect("commit")
+}
+
+/// run the git cli in `dir`, failing the test if it does
+pub fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("run git");
+    assert!(status.success(), "git {args:?} failed");
+}
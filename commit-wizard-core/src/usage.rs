@@ -0,0 +1,306 @@
+// usage module - local ledger of tokens and spend for every generation
+
+use crate::ai::TokenUsage;
+use crate::error::WizardError;
+use crate::Config;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// price of a model in usd per token
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TokenPricing {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl TokenPricing {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        self.prompt * f64::from(usage.prompt_tokens)
+            + self.completion * f64::from(usage.completion_tokens)
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.prompt == 0.0 && self.completion == 0.0
+    }
+}
+
+/// one generation (including any retries or candidates) as recorded in the ledger
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Utc>,
+    pub provider: String,
+    pub model: String,
+    pub requests: u32,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    #[serde(default)]
+    pub estimated: bool, // the provider didn't report usage, so tokens were estimated locally
+    pub cost: Option<f64>, // usd; none when the model's pricing is unknown
+}
+
+impl UsageRecord {
+    /// price a generation against the model catalogue
+    pub fn new(
+        config: &Config,
+        model: &str,
+        usage: TokenUsage,
+        requests: u32,
+        estimated: bool,
+    ) -> Self {
+        let provider = config.active_provider().unwrap_or_default();
+        let cost = if provider.is_local() {
+            Some(0.0)
+        } else {
+            config.model_pricing(model).map(|p| p.cost(&usage))
+        };
+
+        Self {
+            timestamp: Utc::now(),
+            provider: provider.display_name(),
+            model: model.to_string(),
+            requests,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated,
+            cost,
+        }
+    }
+
+    fn total_tokens(&self) -> u64 {
+        u64::from(self.prompt_tokens) + u64::from(self.completion_tokens)
+    }
+}
+
+/// append a generation to the ledger. replayed fixtures cost nothing, so they're never recorded
+pub fn record_usage(record: &UsageRecord) -> Result<()> {
+    if crate::http::is_replaying() {
+        return Ok(());
+    }
+    let path = crate::commit_wizard_dir()?.join("usage.jsonl");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open usage ledger at {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+/// read every generation from the ledger, skipping lines that don't parse
+pub fn load_usage() -> Result<Vec<UsageRecord>> {
+    let path = crate::commit_wizard_dir()?.join("usage.jsonl");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read usage ledger at {}", path.display()))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// spend so far in the current calendar month (local time)
+pub fn month_to_date_spend(records: &[UsageRecord]) -> f64 {
+    let today = Local::now().date_naive();
+    records
+        .iter()
+        .filter(|r| {
+            let day = local_day(r);
+            day.year() == today.year() && day.month() == today.month()
+        })
+        .filter_map(|r| r.cost)
+        .fold(0.0, |total, cost| total + cost)
+}
+
+/// refuse premium models once the monthly spend cap has been reached
+pub fn check_spend_cap(config: &Config, model: &str) -> Result<()> {
+    let Some(cap) = config.monthly_spend_cap else {
+        return Ok(());
+    };
+    // only models known to be free, and local ones, stay usable; unknown pricing (a direct
+    // api profile, or a missing model cache) counts as premium so the cap can't fail open
+    let free = config
+        .model_pricing(model)
+        .is_some_and(|pricing| pricing.is_free());
+    if free || config.active_provider().is_ok_and(|p| p.is_local()) {
+        return Ok(());
+    }
+
+    let spent = month_to_date_spend(&load_usage()?);
+    if spent >= cap {
+        return Err(WizardError::SpendCapReached {
+            model: model.to_string(),
+            spent,
+            cap,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// one-line summary of what the generations behind a commit cost
+pub fn print_commit_usage(records: &[UsageRecord]) {
+    if records.is_empty() {
+        return;
+    }
+
+    let prompt: u64 = records.iter().map(|r| u64::from(r.prompt_tokens)).sum();
+    let completion: u64 = records.iter().map(|r| u64::from(r.completion_tokens)).sum();
+    let estimated = records.iter().any(|r| r.estimated);
+    let generations = if records.len() == 1 {
+        "1 generation".to_string()
+    } else {
+        format!("{} generations", records.len())
+    };
+
+    println!(
+        "{} {}",
+        style("💰 usage:").cyan(),
+        style(format!(
+            "{generations} · {}{prompt} prompt + {completion} completion tokens · {}",
+            if estimated { "~" } else { "" },
+            format_cost(total_cost(records.iter()))
+        ))
+        .dim()
+    );
+}
+
+/// print spend and tokens for the last `days` days, by model and by day
+pub fn print_usage_report(config: &Config, days: u32) -> Result<()> {
+    let records = load_usage()?;
+    let since = Local::now().date_naive() - chrono::Duration::days(i64::from(days) - 1);
+    let recent: Vec<&UsageRecord> = records.iter().filter(|r| local_day(r) >= since).collect();
+
+    println!(
+        "\n{}",
+        style(format!("📊 usage for the last {days} days"))
+            .cyan()
+            .bold()
+    );
+
+    if recent.is_empty() {
+        println!("{}", style("no generations recorded yet").dim());
+    } else {
+        let mut by_model: BTreeMap<&str, Vec<&UsageRecord>> = BTreeMap::new();
+        let mut by_day: BTreeMap<NaiveDate, Vec<&UsageRecord>> = BTreeMap::new();
+        for record in &recent {
+            by_model.entry(&record.model).or_default().push(record);
+            by_day.entry(local_day(record)).or_default().push(record);
+        }
+
+        println!("\n{}", style("by model:").cyan());
+        for (model, group) in &by_model {
+            print_group_line(model, group);
+        }
+
+        println!("\n{}", style("by day:").cyan());
+        for (day, group) in &by_day {
+            print_group_line(&day.to_string(), group);
+        }
+
+        println!();
+        print_group_line("total", &recent);
+    }
+
+    let spent = month_to_date_spend(&records);
+    match config.monthly_spend_cap {
+        Some(cap) => println!(
+            "\n{} {}",
+            style("this month:").cyan(),
+            style(format!("${spent:.4} of ${cap:.2} cap")).yellow()
+        ),
+        None => println!(
+            "\n{} {}",
+            style("this month:").cyan(),
+            style(format!("${spent:.4}")).yellow()
+        ),
+    }
+    if recent.iter().any(|r| r.cost.is_none()) {
+        println!(
+            "{}",
+            style("some generations used models with unknown pricing and are not costed").dim()
+        );
+    }
+
+    Ok(())
+}
+
+fn print_group_line(label: &str, group: &[&UsageRecord]) {
+    let tokens: u64 = group.iter().map(|r| r.total_tokens()).sum();
+    println!(
+        "  {:<44} {:>5} gen {:>10} tokens  {}",
+        label,
+        group.len(),
+        tokens,
+        format_cost(total_cost(group.iter().copied()))
+    );
+}
+
+/// summed cost, or none if no generation in the set could be priced
+fn total_cost<'a>(records: impl Iterator<Item = &'a UsageRecord>) -> Option<f64> {
+    records
+        .filter_map(|r| r.cost)
+        .fold(None, |total, cost| Some(total.unwrap_or(0.0) + cost))
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    match cost {
+        Some(cost) => format!("${cost:.4}"),
+        None => "cost unknown".to_string(),
+    }
+}
+
+fn local_day(record: &UsageRecord) -> NaiveDate {
+    record.timestamp.with_timezone(&Local).date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProviderConfig, ProviderKind};
+
+    fn capped_config() -> Config {
+        Config {
+            monthly_spend_cap: Some(0.0), // already reached, whatever the ledger says
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn spend_cap_blocks_models_with_unknown_pricing() {
+        let error = check_spend_cap(&capped_config(), "acme/unpriced-model").unwrap_err();
+        assert!(matches!(
+            WizardError::find(&error),
+            Some(WizardError::SpendCapReached { .. })
+        ));
+    }
+
+    #[test]
+    fn spend_cap_spares_free_and_local_models() {
+        let mut config = capped_config();
+        assert!(check_spend_cap(&config, "acme/some-model:free").is_ok());
+
+        config.profiles.insert(
+            "local".to_string(),
+            ProviderConfig {
+                provider: ProviderKind::Ollama,
+                ..ProviderConfig::default()
+            },
+        );
+        config.profile = Some("local".to_string());
+        assert!(check_spend_cap(&config, "llama3").is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+// replay tests - drive generation end to end against the fixtures in tests/fixtures/replay,
+// recorded from a local stand-in that answers each model with a scripted reply
+
+use commit_wizard_core::http::{self, API_BASE_ENV, FIXTURES_ENV, HTTP_MODE_ENV};
+use commit_wizard_core::{
+    generate_conventional_commit_with_model, get_diff_info, Config, DiffInfo, ProviderConfig,
+    ProviderKind, WizardError,
+};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// point every test at the fixtures and a throwaway config directory. set
+/// COMMIT_WIZARD_HTTP_MODE=record to re-record against the stand-in on 127.0.0.1:8098
+fn config_home() -> &'static Path {
+    static HOME: OnceLock<PathBuf> = OnceLock::new();
+    HOME.get_or_init(|| {
+        if env::var(HTTP_MODE_ENV).is_err() {
+            env::set_var(HTTP_MODE_ENV, "replay");
+        }
+        env::set_var(
+            FIXTURES_ENV,
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay"),
+        );
+        env::remove_var(API_BASE_ENV);
+        env::set_var("COMMIT_WIZARD_TEST_KEY", "stand-in");
+
+        let home = tempfile::tempdir().expect("temp dir").keep();
+        env::set_var("XDG_CONFIG_HOME", &home);
+        home
+    })
+}
+
+fn stand_in_config() -> Config {
+    let mut config = Config::default();
+    config.profiles.insert(
+        "stand-in".to_string(),
+        ProviderConfig {
+            provider: ProviderKind::OpenaiCompatible,
+            base_url: Some("http://127.0.0.1:8098/v1".to_string()),
+            api_key_env: Some("COMMIT_WIZARD_TEST_KEY".to_string()),
+            context_length: Some(32_768),
+            structured_output: Some(false),
+            ..ProviderConfig::default()
+        },
+    );
+    config.profile = Some("stand-in".to_string());
+    config
+}
+
+/// the same staged change for every test; the model name picks the scripted reply
+fn staged_change() -> DiffInfo {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let repo = git2::Repository::init(dir.path()).expect("init repository");
+    std::fs::create_dir(dir.path().join("src")).expect("create src");
+    std::fs::write(
+        dir.path().join("src/config.rs"),
+        "pub fn parse_config(text: &str) -> Result<Config, Error> {\n    toml::from_str(text).map_err(Error::from)\n}\n",
+    )
+    .expect("write file");
+    let mut index = repo.index().expect("index");
+    index
+        .add_path(Path::new("src/config.rs"))
+        .expect("stage file");
+    index.write().expect("write index");
+
+    get_diff_info(
+        dir.path().to_str().expect("utf-8 path"),
+        100 * 1024,
+        10,
+        false,
+    )
+    .expect("diff info")
+}
+
+async fn generate(model: &str) -> anyhow::Result<String> {
+    config_home();
+    generate_conventional_commit_with_model(
+        &staged_change(),
+        false,
+        false,
+        Some(model.to_string()),
+        &stand_in_config(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn clean_reply_is_accepted_and_not_recorded_in_the_ledger() {
+    let message = generate("stand-in/clean").await.unwrap();
+    assert_eq!(message, "feat(config): add config parser");
+    if http::is_replaying() {
+        assert!(!config_home().join("commit-wizard/usage.jsonl").exists());
+    }
+}
+
+#[tokio::test]
+async fn too_long_description_is_retried() {
+    let message = generate("stand-in/verbose").await.unwrap();
+    assert_eq!(message, "feat(config): add toml config parser");
+}
+
+#[tokio::test]
+async fn malformed_reply_is_recovered_by_fix_commit_format() {
+    let message = generate("stand-in/malformed").await.unwrap();
+    assert_eq!(message, "feat: add config parser");
+}
+
+#[tokio::test]
+async fn rate_limit_is_retried_after_the_requested_pause() {
+    let message = generate("stand-in/busy").await.unwrap();
+    assert_eq!(message, "feat(config): add config parser");
+}
+
+#[tokio::test]
+async fn persistent_rate_limit_is_reported_with_its_retry_after() {
+    let error = generate("stand-in/throttled").await.unwrap_err();
+    match WizardError::find(&error) {
+        Some(WizardError::RateLimited { body, .. }) => {
+            assert!(body.contains("retry after 2s"), "{body}")
+        }
+        other => panic!("expected a rate limit error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn server_errors_give_up_as_service_unavailable() {
+    let error = generate("stand-in/down").await.unwrap_err();
+    assert!(
+        matches!(
+            WizardError::find(&error),
+            Some(WizardError::ServiceUnavailable { status: 503, .. })
+        ),
+        "{error:#}"
+    );
+}
+
+#[tokio::test]
+async fn validation_failing_every_retry_warrants_a_fallback() {
+    let error = generate("stand-in/rambling").await.unwrap_err();
+    match WizardError::find(&error) {
+        Some(failure @ WizardError::Validation { attempts, .. }) => {
+            assert_eq!(*attempts, 4);
+            assert!(failure.warrants_fallback());
+        }
+        other => panic!("expected a validation error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn single_bad_reply_does_not_warrant_a_fallback() {
+    let error = generate("stand-in/unknown-type").await.unwrap_err();
+    match WizardError::find(&error) {
+        Some(failure @ WizardError::Validation { attempts, .. }) => {
+            assert_eq!(*attempts, 1);
+            assert!(!failure.warrants_fallback());
+        }
+        other => panic!("expected a validation error, got {other:?}"),
+    }
+}
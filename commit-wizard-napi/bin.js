@@ -10,6 +10,11 @@ try {
       process.exit(0);
     })
     .catch(error => {
+      // "[code] ..." errors come from the wizard itself, so the native binary would fail the same way
+      if (/^\[[a-z_:]+\]/.test(error.message)) {
+        process.exit(error.code === "Cancelled" ? 130 : 1);
+      }
+
       console.error("Could not run Node.js version, error:", error.message);
       
       // if napi version fails, fall back to CLI binary
@@ -12,6 +12,8 @@ use commit_wizard_core::{
   style, // for potential direct use of style in NAPI error messages
   CoreCliArgs,
   Parser, // <<< import the Parser trait
+  ValidationRule,
+  WizardError,
 };
 
 // removed mod ai, git, utils - they are in commit_wizard_core
@@ -50,20 +52,28 @@ pub async fn run_commit_wizard_cli(argv: Vec<String>) -> NapiResult<String> {
     }
     Err(e) => {
       // the core function already prints detailed errors.
-      // check if it's a validation error and provide more helpful feedback
-      let error_str = e.to_string();
-      let napi_err_msg = if error_str.contains("invalid scope") {
-        format!("NAPI: commit message validation failed - {e}\nTip: Ensure the scope only contains alphanumeric characters, hyphens, underscores, dots, or forward slashes")
-      } else if error_str.contains("invalid format") {
-        format!(
-          "NAPI: commit message format error - {e}\nExpected format: type(scope): description"
-        )
-      } else {
-        format!("NAPI: error during commit wizard execution: {e}")
+      // prefix the message with a stable code so javascript callers can branch on it
+      let wizard_error = WizardError::find(&e);
+      let code = match wizard_error {
+        Some(WizardError::Validation { rule, .. }) => format!("validation:{}", rule.code()),
+        Some(other) => other.code().to_string(),
+        None => "unknown".to_string(),
+      };
+      let napi_err_msg = match wizard_error.and_then(WizardError::rule) {
+        Some(ValidationRule::InvalidScope) => format!("[{code}] NAPI: commit message validation failed - {e}\nTip: Ensure the scope only contains alphanumeric characters, hyphens, underscores, dots, or forward slashes"),
+        Some(ValidationRule::InvalidFormat) => format!(
+          "[{code}] NAPI: commit message format error - {e}\nExpected format: type(scope): description"
+        ),
+        _ => format!("[{code}] NAPI: error during commit wizard execution: {e}"),
+      };
+      let status = match wizard_error {
+        Some(WizardError::Cancelled) => Status::Cancelled,
+        Some(WizardError::Validation { .. } | WizardError::Config(_)) => Status::InvalidArg,
+        _ => Status::GenericFailure,
       };
 
       eprintln!("{}", style(&napi_err_msg).red().bold());
-      Err(napi::Error::new(Status::GenericFailure, napi_err_msg))
+      Err(napi::Error::new(status, napi_err_msg))
     }
   }
 }